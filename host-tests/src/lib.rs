@@ -0,0 +1,52 @@
+//! Host builds of the kernel modules which only depend on `core`.
+//! 
+//! Each module is included directly from the kernel source tree, so a module
+//! added here must not use anything outside of `core` (or `alloc`).
+
+extern crate alloc;
+
+// ---------------------------------------------------------------------------
+// MODULE DECLARATIONS
+// ---------------------------------------------------------------------------
+
+#[path = "../../src/allocator/size_class.rs"]
+pub mod size_class;
+
+#[path = "../../src/symbols/demangle.rs"]
+pub mod demangle;
+
+#[path = "../../src/nvram/store.rs"]
+pub mod nvram_store;
+
+#[path = "../../src/rng/pool.rs"]
+pub mod rng_pool;
+
+#[path = "../../src/log/ratelimit.rs"]
+pub mod log_ratelimit;
+
+#[path = "../../src/fs/elevator.rs"]
+pub mod fs_elevator;
+
+#[path = "../../src/hash/soft.rs"]
+pub mod hash_soft;
+
+#[path = "../../src/compress/lz4.rs"]
+pub mod compress_lz4;
+
+#[path = "../../src/trace/ring.rs"]
+pub mod trace_ring;
+
+#[path = "../../src/interrupts/histogram.rs"]
+pub mod interrupts_histogram;
+
+#[path = "../../src/sensors/threshold.rs"]
+pub mod sensors_threshold;
+
+#[path = "../../src/fs/tar.rs"]
+pub mod fs_tar;
+
+#[path = "../../src/serial/xmodem/packet.rs"]
+pub mod xmodem_packet;
+
+#[path = "../../src/allocator/stats.rs"]
+pub mod allocator_stats;
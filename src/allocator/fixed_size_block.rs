@@ -3,7 +3,9 @@
 // ---------------------------------------------------------------------------
 
 use alloc::alloc::{Layout, GlobalAlloc};
-use super::Locked;
+use super::{Locked, redzone, stats};
+use super::size_class::{BLOCK_SIZES, list_index};
+use crate::memory::pressure::{self, Pressure};
 use core::ptr;
 use core::{mem, ptr::NonNull};
 
@@ -11,10 +13,17 @@ use core::{mem, ptr::NonNull};
 // CONSTANTS
 // ---------------------------------------------------------------------------
 
-/// Sizes of blocks to be used for the allocator.
-/// 
-/// Each size is a power of 2 to fit with block alignments.
-const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+/// The fraction of the heap set aside for large allocations, as a divisor of
+/// the heap size.
+const LARGE_RESERVE_DIVISOR: usize = 4;
+
+/// Memory pressure is raised once less than `1 / LOW_HEADROOM_DIVISOR` of the
+/// heap is free.
+const LOW_HEADROOM_DIVISOR: usize = 4;
+
+/// Memory pressure is critical once less than `1 / CRITICAL_HEADROOM_DIVISOR`
+/// of the heap is free.
+const CRITICAL_HEADROOM_DIVISOR: usize = 16;
 
 // ---------------------------------------------------------------------------
 // DATA STRUCTURES
@@ -22,10 +31,20 @@ const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
 
 /// The Fixed Size Block Allocator structure.
 /// 
-/// 
+/// Small allocations are served from per-size lists of free blocks, which are
+/// carved out of the fallback allocator when the lists are empty. Each size 
+/// has a second list of blocks which have already been zeroed. Allocations
+/// too large for any block come from a reserved region first, so that they 
+/// still succeed when the fallback heap is fragmented by blocks.
 pub struct FixedSizeBlockAllocator {
     list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
-    fallback_allocator: linked_list_allocator::Heap
+    zeroed_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
+    fallback_allocator: linked_list_allocator::Heap,
+    large_allocator: linked_list_allocator::Heap,
+    large_start: usize,
+    large_end: usize,
+    heap_size: usize,
+    in_use: usize
 }
 
 impl FixedSizeBlockAllocator {
@@ -34,49 +53,187 @@ impl FixedSizeBlockAllocator {
     pub const fn new() -> FixedSizeBlockAllocator {
         FixedSizeBlockAllocator {
             list_heads: [None; BLOCK_SIZES.len()],
-            fallback_allocator: linked_list_allocator::Heap::empty()
+            zeroed_heads: [None; BLOCK_SIZES.len()],
+            fallback_allocator: linked_list_allocator::Heap::empty(),
+            large_allocator: linked_list_allocator::Heap::empty(),
+            large_start: 0,
+            large_end: 0,
+            heap_size: 0,
+            in_use: 0
         }
     }
 
     /// Initiailise the allocator with the given heap bounds.
     /// 
+    /// The last `1 / LARGE_RESERVE_DIVISOR` of the heap is reserved for large
+    /// allocations.
+    /// 
     /// NOTE: UNSAFE
     ///     This function is unsafe because the caller must guarentee that the
     ///     given heap bounds are valid and the heap is unused. 
     /// 
     ///     This method must be called only once.
-    pub unsafe fn init(&mut self, heap_start: usize, heap_end: usize) {
-        self.fallback_allocator.init(heap_start, heap_end);
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        let large_size = heap_size / LARGE_RESERVE_DIVISOR;
+        let fallback_size = heap_size - large_size;
+
+        self.fallback_allocator.init(heap_start, fallback_size);
+        self.large_start = heap_start + fallback_size;
+        self.large_end = self.large_start + large_size;
+        self.large_allocator.init(self.large_start, large_size);
+        self.heap_size = heap_size;
+    }
+
+    /// The number of heap bytes not currently handed out, counting free 
+    /// blocks on the lists as free.
+    pub fn headroom(&self) -> usize {
+        self.heap_size.saturating_sub(self.in_use)
+    }
+
+    /// The memory pressure on the heap given the current headroom.
+    pub fn pressure(&self) -> Pressure {
+        let headroom = self.headroom();
+        if headroom < self.heap_size / CRITICAL_HEADROOM_DIVISOR {
+            Pressure::Critical
+        }
+        else if headroom < self.heap_size / LOW_HEADROOM_DIVISOR {
+            Pressure::Low
+        }
+        else {
+            Pressure::None
+        }
+    }
+
+    /// Return every free block held in the block lists to the fallback 
+    /// allocator, where it can be merged with its neighbours.
+    /// 
+    /// Returns the number of bytes reclaimed.
+    pub fn reclaim(&mut self) -> usize {
+        let mut reclaimed = 0;
+
+        for (index, &block_size) in BLOCK_SIZES.iter().enumerate() {
+            // Every block was originally allocated from the fallback with 
+            // this layout, so it is valid to deallocate it with it too.
+            let layout = Layout::from_size_align(block_size, block_size)
+                .unwrap();
+
+            loop {
+                let ptr = match pop_list(&mut self.list_heads[index])
+                    .or_else(|| pop_list(&mut self.zeroed_heads[index])) {
+                    Some(ptr) => NonNull::new(ptr).unwrap(),
+                    None => break
+                };
+
+                // NOTE: USE OF UNSAFE
+                //  The block is free (it was on a free list) and came from
+                //  the fallback allocator with `layout`.
+                unsafe { self.fallback_allocator.deallocate(ptr, layout) };
+                reclaimed += block_size;
+            }
+        }
+
+        reclaimed
+    }
+
+    /// Walk the free lists, checking each block is inside the block region of
+    /// the heap and aligned to its size, and that no list loops.
+    pub fn check_lists(&self) -> Result<(), &'static str> {
+        let block_start = self.large_end - self.heap_size;
+        let block_end = self.large_start;
+
+        for (index, &block_size) in BLOCK_SIZES.iter().enumerate() {
+            // A list can't hold more blocks than fit in the region
+            let max_blocks = (block_end - block_start) / block_size;
+
+            for head in [&self.list_heads[index], &self.zeroed_heads[index]]
+                .iter() {
+                let mut node = head.as_ref().map(|n| &**n);
+                let mut count = 0;
+
+                while let Some(current) = node {
+                    let addr = current as *const ListNode as usize;
+                    if addr < block_start || addr + block_size > block_end {
+                        return Err("block outside the heap");
+                    }
+                    if addr % block_size != 0 {
+                        return Err("misaligned block");
+                    }
+
+                    count += 1;
+                    if count > max_blocks {
+                        return Err("list loops");
+                    }
+                    node = current.next.as_ref().map(|n| &**n);
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// Allocate using the fallback allocator.
+    /// 
+    /// If the fallback heap can't satisfy the allocation the free blocks are
+    /// reclaimed and the allocation retried.
     fn fallback_alloc(&mut self, layout: Layout) -> *mut u8 {
+        if let Ok(ptr) = self.fallback_allocator.allocate_first_fit(layout) {
+            return ptr.as_ptr();
+        }
+
+        if self.reclaim() == 0 {
+            return ptr::null_mut();
+        }
+
         match self.fallback_allocator.allocate_first_fit(layout) {
             Ok(ptr) => ptr.as_ptr(),
             Err(_) => ptr::null_mut()
         }
     }
-}
 
-unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
+    /// Allocate a layout too large for any block, trying the large reserve 
+    /// before the fallback allocator.
+    /// 
+    /// With the `heap-redzones` feature the allocation is surrounded by 
+    /// poisoned redzones, which are checked when it is freed.
+    fn large_alloc(&mut self, layout: Layout) -> *mut u8 {
+        if !redzone::ENABLED {
+            return self.large_alloc_raw(layout);
+        }
 
-    /// Allocate memory using the fixed block allocator method.
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        // Acquire the lock on ourselves
-        let mut allocator = self.lock();
+        let (outer, front) = match redzone::outer_layout(&layout) {
+            Some(outer) => outer,
+            None => return ptr::null_mut()
+        };
+
+        let base = self.large_alloc_raw(outer);
+        if base.is_null() {
+            return base;
+        }
+
+        // NOTE: USE OF UNSAFE
+        //  `base` was just allocated with the outer layout.
+        unsafe { redzone::poison(base, front, &layout) }
+    }
 
+    /// Allocate the exact layout from the large reserve or the fallback 
+    /// allocator.
+    fn large_alloc_raw(&mut self, layout: Layout) -> *mut u8 {
+        match self.large_allocator.allocate_first_fit(layout) {
+            Ok(ptr) => ptr.as_ptr(),
+            Err(_) => self.fallback_alloc(layout)
+        }
+    }
+
+    /// Allocate memory for the layout, from the block lists if it fits in a
+    /// block or from the large reserve or fallback allocator if not.
+    fn allocate(&mut self, layout: Layout) -> *mut u8 {
         // Determine which block size is required
         match list_index(&layout) {
             Some(index) => {
                 // If the requested size can fit into a block attempt to get
-                // the needed head item
-                match allocator.list_heads[index].take() {
-                    Some(node) => {
-                        // If a valid node is available move the head upto the
-                        // next free block and return the found node.
-                        allocator.list_heads[index] = node.next.take();
-                        node as *mut ListNode as *mut u8
-                    },
+                // a free one from the lists
+                match self.pop_block(index) {
+                    Some(ptr) => ptr,
                     None => {
                         // If no valid node we should create a new one using 
                         // the fallback allocator
@@ -88,65 +245,224 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
                         let block_align = block_size;
                         let layout = Layout::from_size_align(
                             block_size, block_align).unwrap();
-                        allocator.fallback_alloc(layout)
+                        self.fallback_alloc(layout)
                     }
                 }
             },
-            None => allocator.fallback_alloc(layout)
+            None => self.large_alloc(layout)
+        }
+    }
+
+    /// Allocate zeroed memory for the layout.
+    /// 
+    /// Blocks already zeroed in the background only need their list node 
+    /// cleared, anything else is zeroed here.
+    fn allocate_zeroed(&mut self, layout: Layout) -> *mut u8 {
+        if let Some(index) = list_index(&layout) {
+            if let Some(ptr) = pop_list(&mut self.zeroed_heads[index]) {
+                // NOTE: USE OF UNSAFE
+                //  The block is at least as big as a list node, and only the 
+                //  node was written since the block was zeroed.
+                unsafe { ptr::write_bytes(ptr, 0, mem::size_of::<ListNode>()) };
+                return ptr;
+            }
+        }
+
+        let ptr = self.allocate(layout);
+        if !ptr.is_null() {
+            // NOTE: USE OF UNSAFE
+            //  The allocation is at least `layout.size()` bytes long.
+            unsafe { ptr::write_bytes(ptr, 0, layout.size()) };
         }
+        ptr
+    }
+
+    /// Pop a free block from the given list, preferring blocks which haven't
+    /// been zeroed so the zeroed ones are kept for `allocate_zeroed`.
+    fn pop_block(&mut self, index: usize) -> Option<*mut u8> {
+        pop_list(&mut self.list_heads[index])
+            .or_else(|| pop_list(&mut self.zeroed_heads[index]))
+    }
+
+    /// Zero up to `max` free blocks, moving them onto the zeroed lists.
+    /// 
+    /// Returns the number of blocks zeroed.
+    pub fn zero_blocks(&mut self, max: usize) -> usize {
+        let mut zeroed = 0;
+
+        for (index, &block_size) in BLOCK_SIZES.iter().enumerate() {
+            while zeroed < max {
+                let ptr = match pop_list(&mut self.list_heads[index]) {
+                    Some(ptr) => ptr,
+                    None => break
+                };
+
+                // NOTE: USE OF UNSAFE
+                //  The block is free and `block_size` bytes long. It is 
+                //  zeroed before the list node is written to its start.
+                unsafe {
+                    ptr::write_bytes(ptr, 0, block_size);
+                    push_list(&mut self.zeroed_heads[index], ptr);
+                }
+                zeroed += 1;
+            }
+        }
+
+        zeroed
+    }
+
+    /// Returns `true` if any free blocks are waiting to be zeroed.
+    pub fn has_dirty_blocks(&self) -> bool {
+        self.list_heads.iter().any(|head| head.is_some())
+    }
+
+    /// Returns `true` if the pointer lies in the large allocation reserve.
+    fn is_large_reserve(&self, ptr: *mut u8) -> bool {
+        let addr = ptr as usize;
+        addr >= self.large_start && addr < self.large_end
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
+
+    /// Allocate memory using the fixed block allocator method.
+    /// 
+    /// With the `zeroing-alloc` feature all memory is zeroed before it is 
+    /// handed out, so no stale data can leak between users of the heap.
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        stats::record(&layout);
+        self.alloc_relieving(layout, cfg!(feature = "zeroing-alloc"))
+    }
+
+    /// Allocate zeroed memory, using a block zeroed in the background by 
+    /// `zero_free_blocks` if one is available.
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        stats::record(&layout);
+        self.alloc_relieving(layout, true)
     }
 
     /// Deallocate memory previously assigned using an `alloc` call.
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         // Lock the allocator reference
         let mut allocator = self.lock();
+        allocator.in_use -= charged_size(&layout);
 
         // Find which block size the memory uses
         match list_index(&layout) {
             Some(index) => {
                 // If the layout would fit into a block
 
-                // Get a node pointing to the current head
-                let new_node = ListNode {
-                    next: allocator.list_heads[index].take()
-                };
-
                 // Verify that the block has the size and alignment required 
                 // for storing the new node
                 assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
                 assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
 
-                let new_node_ptr = ptr as *mut ListNode;
-                new_node_ptr.write(new_node);
-                allocator.list_heads[index] = Some(&mut *new_node_ptr);
+                // Push the block onto the head of the list
+                push_list(&mut allocator.list_heads[index], ptr);
+
+                // Let the zeroing task know there's a new dirty block, once 
+                // the lock is released so it can take it.
+                drop(allocator);
+                super::ZEROING_WAKER.wake();
             },
             None => {
+                // Check and strip the redzones to get the real allocation
+                let (ptr, layout) = if redzone::ENABLED {
+                    let (outer, front) = redzone::outer_layout(&layout)
+                        .unwrap();
+                    (redzone::release(ptr, front, &layout), outer)
+                }
+                else {
+                    (ptr, layout)
+                };
+
                 // If the layout could not be fit into a block it would have
-                // been allocated using the fallback allocator, so dealloc 
-                // using that.
+                // been allocated from the large reserve or the fallback 
+                // allocator, so dealloc using whichever it came from.
+                let is_large = allocator.is_large_reserve(ptr);
                 let ptr = NonNull::new(ptr).unwrap();
-                allocator.fallback_allocator.deallocate(ptr, layout);
+                if is_large {
+                    allocator.large_allocator.deallocate(ptr, layout);
+                }
+                else {
+                    allocator.fallback_allocator.deallocate(ptr, layout);
+                }
             }
         }
     }
 }
 
+impl Locked<FixedSizeBlockAllocator> {
+
+    /// Allocate memory for the layout, zeroing it if requested.
+    /// 
+    /// If the heap is exhausted registered subsystems are asked to free 
+    /// memory before the allocation is retried, and if the heap is running 
+    /// low the memory pressure monitor is woken.
+    fn alloc_relieving(&self, layout: Layout, zeroed: bool) -> *mut u8 {
+        let allocate = |allocator: &mut FixedSizeBlockAllocator| {
+            let ptr = if zeroed {
+                allocator.allocate_zeroed(layout)
+            }
+            else {
+                allocator.allocate(layout)
+            };
+            if !ptr.is_null() {
+                allocator.in_use += charged_size(&layout);
+            }
+            (ptr, allocator.pressure())
+        };
+
+        // The lock must be released before relieving pressure, since the 
+        // shrinkers will free memory.
+        let (mut ptr, mut level) = allocate(&mut self.lock());
+        if ptr.is_null() && pressure::relieve(Pressure::Critical) > 0 {
+            let retry = allocate(&mut self.lock());
+            ptr = retry.0;
+            level = retry.1;
+        }
+
+        if level != Pressure::None {
+            pressure::notify();
+        }
+
+        ptr
+    }
+}
+
 /// A node in the allocation list
 struct ListNode {
     next: Option<&'static mut ListNode>
 }
 
-
 // ---------------------------------------------------------------------------
 // PRIVATE FUNCTIONS
 // ---------------------------------------------------------------------------
 
-/// Get the index of the block size that this particular layout should fit in.
-/// 
-/// Will bin the layout into the first block size larger than or equal to the
-/// required size.
-fn list_index(layout: &Layout) -> Option<usize> {
-    let required_size = layout.size().max(layout.align());
-    BLOCK_SIZES.iter().position(|&s| s >= required_size)
+/// The number of heap bytes used by an allocation with the given layout.
+fn charged_size(layout: &Layout) -> usize {
+    match list_index(layout) {
+        Some(index) => BLOCK_SIZES[index],
+        None => layout.size()
+    }
 }
 
+/// Pop the head block off a free list.
+fn pop_list(head: &mut Option<&'static mut ListNode>) -> Option<*mut u8> {
+    let node = head.take()?;
+    *head = node.next.take();
+    Some(node as *mut ListNode as *mut u8)
+}
+
+/// Push a free block onto the head of a free list.
+/// 
+/// NOTE: UNSAFE
+///     The caller must guarentee that `ptr` is a free block large and aligned
+///     enough to hold a `ListNode`, and that it is not on any other list.
+unsafe fn push_list(head: &mut Option<&'static mut ListNode>, ptr: *mut u8) {
+    let new_node_ptr = ptr as *mut ListNode;
+    new_node_ptr.write(ListNode {
+        next: head.take()
+    });
+    *head = Some(&mut *new_node_ptr);
+}
@@ -3,6 +3,11 @@
 // ---------------------------------------------------------------------------
 
 use alloc::alloc::Layout;
+use core::fmt;
+use core::{future::Future, pin::Pin, task::{Context, Poll}};
+use futures_util::task::AtomicWaker;
+use crate::memory::pressure::{self, Pressure};
+use crate::task;
 use x86_64::{
     structures::paging::{
         mapper::MapToError, 
@@ -12,23 +17,43 @@ use x86_64::{
         PageTableFlags, 
         Size4KiB
     },
+    PhysAddr,
     VirtAddr
 };
+use spin::Mutex;
 
 // ---------------------------------------------------------------------------
 // MODULES
 // ---------------------------------------------------------------------------
 
 pub mod fixed_size_block;
+pub mod redzone;
+pub mod size_class;
+pub mod stats;
 use fixed_size_block::FixedSizeBlockAllocator;
 
 // ---------------------------------------------------------------------------
 // STATICS AND CONSTNATS
 // ---------------------------------------------------------------------------
 
-pub const HEAP_START: usize = 0x4444_4444_0000;
+/// The start of the heap, at the bottom of the heap window.
+pub const HEAP_START: usize = crate::memory::layout::HEAP.start as usize;
 pub const HEAP_SIZE: usize = 10240;
 
+/// The number of pages mapped for the heap.
+const HEAP_PAGES: usize = (HEAP_SIZE + 4095) / 4096;
+
+/// The physical ranges backing the heap, recorded by `init_heap`.
+static HEAP_PHYS_RANGES: Mutex<[Option<(PhysAddr, PhysAddr)>; HEAP_PAGES]> = 
+    Mutex::new([None; HEAP_PAGES]);
+
+/// The maximum number of blocks zeroed by `zero_free_blocks` before it 
+/// yields to other tasks.
+const ZEROING_BATCH_SIZE: usize = 8;
+
+/// Waker for the `zero_free_blocks` task, woken when a block is freed.
+static ZEROING_WAKER: AtomicWaker = AtomicWaker::new();
+
 #[global_allocator]
 static ALLOCATOR: Locked<FixedSizeBlockAllocator> = Locked::new(
     FixedSizeBlockAllocator::new());
@@ -56,14 +81,70 @@ impl<A> Locked<A> {
     pub fn lock(&self) -> spin::MutexGuard<A> {
         self.inner.lock()
     }
+
+    /// Try to lock the mutex, returning `None` if it is already locked.
+    pub fn try_lock(&self) -> Option<spin::MutexGuard<A>> {
+        self.inner.try_lock()
+    }
+}
+
+/// Future which resolves once the block allocator has free blocks which 
+/// haven't been zeroed.
+struct DirtyBlocks;
+
+impl Future for DirtyBlocks {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if ALLOCATOR.lock().has_dirty_blocks() {
+            return Poll::Ready(());
+        }
+
+        // Register before checking again so a block freed in between isn't 
+        // missed
+        ZEROING_WAKER.register(&cx.waker());
+
+        if ALLOCATOR.lock().has_dirty_blocks() {
+            ZEROING_WAKER.take();
+            Poll::Ready(())
+        }
+        else {
+            Poll::Pending
+        }
+    }
 }
 
 /// Contains information about the kernel heap.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HeapInfo {
-    start_virt_addr: VirtAddr,
-    start_phys_addr: Page,
-    size: usize
+    /// The virtual address of the start of the heap.
+    pub start_virt_addr: VirtAddr,
+
+    /// The physical ranges backing the heap, in virtual address order. 
+    /// Contiguous frames are merged into a single range.
+    pub phys_ranges: [Option<(PhysAddr, PhysAddr)>; HEAP_PAGES],
+
+    /// The size of the heap in bytes.
+    pub size: usize,
+
+    /// The number of bytes currently allocated.
+    pub used: usize,
+
+    /// The number of bytes free, counting free blocks as free.
+    pub free: usize
+}
+
+impl fmt::Display for HeapInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Kernel heap: {} KiB at {:#x}", 
+            self.size / 1024, self.start_virt_addr.as_u64())?;
+        for (start, end) in self.phys_ranges.iter().flatten() {
+            writeln!(f, "    phys: {:#012x} - {:#012x}", 
+                start.as_u64(), end.as_u64())?;
+        }
+        write!(f, "    used: {} bytes, free: {} bytes ({}%)", 
+            self.used, self.free, 100 * self.used / self.size.max(1))
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -88,7 +169,9 @@ pub fn init_heap(
     for page in page_range {
         let frame = frame_allocator.allocate_frame()
             .ok_or(MapToError::FrameAllocationFailed)?;
-        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        record_phys_frame(frame.start_address());
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE 
+            | PageTableFlags::NO_EXECUTE;
         mapper.map_to(page, frame, flags, frame_allocator)?.flush();
     }
 
@@ -97,12 +180,101 @@ pub fn init_heap(
         ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
     }
 
+    // Free blocks are returned to the general heap under memory pressure
+    pressure::register("block allocator", |_| reclaim())
+        .expect("[ALLOC-ERROR] Failed to register the block shrinker");
+
     // Return the heap information
-    Ok(HeapInfo {
-        start_virt_addr: heap_start,
-        start_phys_addr: page_range.start,
-        size: HEAP_SIZE
-    })
+    Ok(heap_info())
+}
+
+/// Get the current information about the kernel heap.
+pub fn heap_info() -> HeapInfo {
+    let headroom = ALLOCATOR.lock().headroom();
+    build_heap_info(headroom, *HEAP_PHYS_RANGES.lock())
+}
+
+/// Get the current information about the kernel heap without waiting on any
+/// locks, returning `None` if the heap is locked.
+/// 
+/// This is for use from interrupt handlers, which may have interrupted the 
+/// holder of the lock.
+pub fn try_heap_info() -> Option<HeapInfo> {
+    let headroom = ALLOCATOR.try_lock()?.headroom();
+    let phys_ranges = *HEAP_PHYS_RANGES.try_lock()?;
+    Some(build_heap_info(headroom, phys_ranges))
+}
+
+/// Return the free blocks held by the block allocator to the general heap.
+/// 
+/// This happens automatically when an allocation would otherwise fail, but 
+/// can be called to defragment the heap ahead of a large allocation. Returns
+/// the number of bytes reclaimed.
+pub fn reclaim() -> usize {
+    ALLOCATOR.lock().reclaim()
+}
+
+/// Check that every block on the block allocator's free lists is a valid 
+/// block inside the heap.
+pub fn check_free_lists() -> Result<(), &'static str> {
+    ALLOCATOR.lock().check_lists()
+}
+
+/// Get the memory pressure on the kernel heap.
+pub fn pressure() -> Pressure {
+    ALLOCATOR.lock().pressure()
+}
+
+/// Background task which zeroes freed blocks, so that `alloc_zeroed` can 
+/// hand them out without zeroing them on the allocation path.
+/// 
+/// Blocks are zeroed in batches of `ZEROING_BATCH_SIZE`, yielding between 
+/// each batch.
+pub async fn zero_free_blocks() {
+    loop {
+        DirtyBlocks.await;
+        ALLOCATOR.lock().zero_blocks(ZEROING_BATCH_SIZE);
+        task::yield_now().await;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Build the heap information from the allocator's headroom.
+fn build_heap_info(
+    headroom: usize, 
+    phys_ranges: [Option<(PhysAddr, PhysAddr)>; HEAP_PAGES]
+) -> HeapInfo {
+    HeapInfo {
+        start_virt_addr: VirtAddr::new(HEAP_START as u64),
+        phys_ranges,
+        size: HEAP_SIZE,
+        used: HEAP_SIZE - headroom,
+        free: headroom
+    }
+}
+
+/// Record a frame mapped into the heap, extending the last range if the frame
+/// follows on from it.
+fn record_phys_frame(start: PhysAddr) {
+    let end = start + 4096u64;
+    let mut ranges = HEAP_PHYS_RANGES.lock();
+
+    let last = ranges.iter().rposition(|r| r.is_some());
+    if let Some(index) = last {
+        if let Some((_, range_end)) = ranges[index].as_mut() {
+            if *range_end == start {
+                *range_end = end;
+                return;
+            }
+        }
+    }
+
+    // There's a slot for every page so this can't fail
+    let next = last.map(|i| i + 1).unwrap_or(0);
+    ranges[next] = Some((start, end));
 }
 
 #[alloc_error_handler]
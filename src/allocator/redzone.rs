@@ -0,0 +1,209 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::alloc::Layout;
+use core::{future::Future, pin::Pin, task::{Context, Poll}};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use futures_util::task::AtomicWaker;
+use spin::Mutex;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// Whether large allocations are surrounded by redzones, enabled by the 
+/// `heap-redzones` feature.
+pub const ENABLED: bool = cfg!(feature = "heap-redzones");
+
+/// The minimum size of the redzone on either side of an allocation.
+pub const REDZONE_SIZE: usize = 16;
+
+/// The value redzone bytes are filled with.
+const POISON: u8 = 0xfd;
+
+/// The maximum number of live allocations tracked for the scrubber. Any 
+/// beyond this are still checked when they are freed.
+const MAX_TRACKED: usize = 32;
+
+/// The number of redzoned allocations between each wake of the scrubber.
+const SCRUB_INTERVAL: usize = 16;
+
+/// The live redzoned allocations.
+static TRACKED: Mutex<[Option<Redzoned>; MAX_TRACKED]> = 
+    Mutex::new([None; MAX_TRACKED]);
+
+/// The number of redzoned allocations since the scrubber last ran.
+static SINCE_SCRUB: AtomicUsize = AtomicUsize::new(0);
+
+/// Waker for the `scrubber` task.
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A live allocation surrounded by redzones.
+#[derive(Clone, Copy)]
+struct Redzoned {
+    /// The address handed to the user.
+    addr: usize,
+
+    /// The size of the user's allocation.
+    size: usize,
+
+    /// The size of the redzone before the allocation.
+    front: usize
+}
+
+impl Redzoned {
+
+    /// Check both redzones are still fully poisoned, panicking if not.
+    /// 
+    /// NOTE: UNSAFE
+    ///     The caller must guarentee the allocation is still live.
+    unsafe fn check(&self) {
+        let front = (self.addr - self.front) as *const u8;
+        let back = (self.addr + self.size) as *const u8;
+
+        if let Some(offset) = find_unpoisoned(front, self.front) {
+            panic!("[ALLOC-ERROR] Heap underrun {} bytes before {:#x}", 
+                self.front - offset, self.addr);
+        }
+        if let Some(offset) = find_unpoisoned(back, REDZONE_SIZE) {
+            panic!("[ALLOC-ERROR] Heap overrun {} bytes past the end of {:#x} \
+                (size {})", offset, self.addr, self.size);
+        }
+    }
+}
+
+/// Future which resolves once the scrubber should run.
+struct ScrubDue;
+
+impl Future for ScrubDue {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if SINCE_SCRUB.load(Ordering::Relaxed) >= SCRUB_INTERVAL {
+            return Poll::Ready(());
+        }
+
+        WAKER.register(&cx.waker());
+
+        if SINCE_SCRUB.load(Ordering::Relaxed) >= SCRUB_INTERVAL {
+            WAKER.take();
+            Poll::Ready(())
+        }
+        else {
+            Poll::Pending
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the layout to allocate for a redzoned allocation, and the offset of 
+/// the user's allocation within it.
+/// 
+/// The front redzone is rounded up to the allocation's alignment so the 
+/// user's pointer stays aligned.
+pub fn outer_layout(layout: &Layout) -> Option<(Layout, usize)> {
+    let align = layout.align();
+    let front = (REDZONE_SIZE + align - 1) & !(align - 1);
+    let size = front.checked_add(layout.size())?.checked_add(REDZONE_SIZE)?;
+
+    let outer = Layout::from_size_align(size, align).ok()?;
+    Some((outer, front))
+}
+
+/// Poison the redzones of a newly allocated outer block and start tracking 
+/// it, returning the pointer to hand to the user.
+/// 
+/// NOTE: UNSAFE
+///     The caller must guarentee `base` points to a block allocated with the
+///     layout given by `outer_layout(layout)`.
+pub unsafe fn poison(base: *mut u8, front: usize, layout: &Layout) -> *mut u8 {
+    let user = base.add(front);
+    base.write_bytes(POISON, front);
+    user.add(layout.size()).write_bytes(POISON, REDZONE_SIZE);
+
+    let mut tracked = TRACKED.lock();
+    if let Some(slot) = tracked.iter_mut().find(|r| r.is_none()) {
+        *slot = Some(Redzoned {
+            addr: user as usize,
+            size: layout.size(),
+            front
+        });
+    }
+    drop(tracked);
+
+    if SINCE_SCRUB.fetch_add(1, Ordering::Relaxed) + 1 >= SCRUB_INTERVAL {
+        WAKER.wake();
+    }
+
+    user
+}
+
+/// Check the redzones of an allocation being freed and stop tracking it, 
+/// returning the pointer to the outer block.
+/// 
+/// Panics if either redzone has been written.
+/// 
+/// NOTE: UNSAFE
+///     The caller must guarentee `user` was returned by `poison` for the 
+///     same layout and hasn't been freed.
+pub unsafe fn release(user: *mut u8, front: usize, layout: &Layout) -> *mut u8 {
+    let mut tracked = TRACKED.lock();
+    if let Some(slot) = tracked.iter_mut()
+        .find(|r| r.map(|r| r.addr) == Some(user as usize)) {
+        *slot = None;
+    }
+    drop(tracked);
+
+    Redzoned {
+        addr: user as usize,
+        size: layout.size(),
+        front
+    }.check();
+
+    user.sub(front)
+}
+
+/// Check the redzones of every tracked allocation, panicking on the first 
+/// corrupted one.
+/// 
+/// Returns the number of allocations checked.
+pub fn scrub() -> usize {
+    let tracked = TRACKED.lock();
+
+    // NOTE: USE OF UNSAFE
+    //  Allocations are untracked before they're freed, and that can't happen
+    //  while the lock is held.
+    tracked.iter().flatten()
+        .map(|r| unsafe { r.check() })
+        .count()
+}
+
+/// Background task which scrubs the redzones of live allocations after every
+/// `SCRUB_INTERVAL` redzoned allocations.
+pub async fn scrubber() {
+    loop {
+        ScrubDue.await;
+        SINCE_SCRUB.store(0, Ordering::Relaxed);
+        scrub();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Find the offset of the first byte in the zone which isn't poisoned.
+/// 
+/// NOTE: UNSAFE
+///     The caller must guarentee `start..start + len` is readable.
+unsafe fn find_unpoisoned(start: *const u8, len: usize) -> Option<usize> {
+    (0..len).find(|&i| start.add(i).read_volatile() != POISON)
+}
@@ -0,0 +1,75 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+// NOTE: This module only depends on `core` so that it can be built and tested
+// on the host by the `host-tests` crate.
+
+use core::alloc::Layout;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// Sizes of blocks to be used for the allocator.
+/// 
+/// Each size is a power of 2 to fit with block alignments.
+pub const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the index of the block size that this particular layout should fit in.
+/// 
+/// Will bin the layout into the first block size larger than or equal to the
+/// required size.
+pub fn list_index(layout: &Layout) -> Option<usize> {
+    let required_size = layout.size().max(layout.align());
+    BLOCK_SIZES.iter().position(|&s| s >= required_size)
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    fn index_of(size: usize, align: usize) -> Option<usize> {
+        list_index(&Layout::from_size_align(size, align).unwrap())
+    }
+
+    #[test]
+    fn block_sizes_are_powers_of_two() {
+        for &size in BLOCK_SIZES {
+            assert!(size.is_power_of_two());
+        }
+    }
+
+    #[test]
+    fn exact_sizes_use_their_own_block() {
+        for (i, &size) in BLOCK_SIZES.iter().enumerate() {
+            assert_eq!(index_of(size, 1), Some(i));
+        }
+    }
+
+    #[test]
+    fn sizes_round_up() {
+        assert_eq!(index_of(1, 1), Some(0));
+        assert_eq!(index_of(9, 1), Some(1));
+        assert_eq!(index_of(2047, 1), Some(BLOCK_SIZES.len() - 1));
+    }
+
+    #[test]
+    fn alignment_can_force_a_bigger_block() {
+        assert_eq!(index_of(8, 64), Some(3));
+    }
+
+    #[test]
+    fn large_layouts_use_the_fallback() {
+        assert_eq!(index_of(4096, 8), None);
+        assert_eq!(index_of(8, 4096), None);
+    }
+}
@@ -0,0 +1,295 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+// NOTE: This module only depends on `core` so that the suggestions can be 
+// tested on the host by the `host-tests` crate.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use super::size_class::{BLOCK_SIZES, list_index};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of power of two buckets in the histogram, covering 8 bytes to
+/// 4 KiB. Larger requests are counted in a final overflow bucket.
+const POW2_BUCKETS: usize = 10;
+
+/// The smallest bucket size, as a power of two.
+const MIN_BUCKET_SHIFT: usize = 3;
+
+/// The share of allocations (in percent) a size needs before the report 
+/// suggests giving it its own block size.
+const SUGGEST_THRESHOLD_PERCENT: usize = 1;
+
+/// The extra rounding loss, as a percentage of the bytes requested, below 
+/// which a suggested size is dropped and its requests left to round up into
+/// the next size.
+const MERGE_THRESHOLD_PERCENT: usize = 2;
+
+/// The alignment suggested block sizes are rounded up to.
+const SUGGEST_ALIGN: usize = 8;
+
+/// Initial value for the bucket arrays, only used to build them.
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of allocation requests in each bucket.
+static COUNTS: [AtomicUsize; POW2_BUCKETS + 1] = [ZERO; POW2_BUCKETS + 1];
+
+/// Total bytes requested in each bucket.
+static REQUESTED: [AtomicUsize; POW2_BUCKETS + 1] = [ZERO; POW2_BUCKETS + 1];
+
+/// Total bytes lost to rounding requests up to the current block sizes.
+static WASTED: AtomicUsize = AtomicUsize::new(0);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Display wrapper printing the allocation size report.
+pub struct Report;
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total: usize = COUNTS.iter().map(|c| c.load(Ordering::Relaxed))
+            .sum();
+        writeln!(f, "Allocation size histogram ({} allocations):", total)?;
+
+        if total == 0 {
+            return Ok(());
+        }
+
+        for bucket in 0..=POW2_BUCKETS {
+            let count = COUNTS[bucket].load(Ordering::Relaxed);
+            let requested = REQUESTED[bucket].load(Ordering::Relaxed);
+            let percent = count * 100 / total;
+
+            if bucket < POW2_BUCKETS {
+                write!(f, "  <= {:>5} B", bucket_size(bucket))?;
+            }
+            else {
+                write!(f, "   > {:>5} B", bucket_size(POW2_BUCKETS - 1))?;
+            }
+            writeln!(f, ": {:>8} ({:>3}%), avg {} B", 
+                count, percent, requested.checked_div(count).unwrap_or(0))?;
+        }
+
+        let mut buckets = [Bucket { count: 0, requested: 0 }; POW2_BUCKETS];
+        for (bucket, stats) in buckets.iter_mut().enumerate() {
+            stats.count = COUNTS[bucket].load(Ordering::Relaxed);
+            stats.requested = REQUESTED[bucket].load(Ordering::Relaxed);
+        }
+
+        let mut sizes = [0; POW2_BUCKETS + 1];
+        let len = suggest(&buckets, &mut sizes);
+        let sizes = &sizes[..len];
+
+        writeln!(f, "Bytes lost to block rounding: {} (suggested sizes: ~{})", 
+            WASTED.load(Ordering::Relaxed), estimated_waste(&buckets, sizes))?;
+        writeln!(f, "Current BLOCK_SIZES:   {:?}", BLOCK_SIZES)?;
+        writeln!(f, "Suggested BLOCK_SIZES: {:?}", sizes)
+    }
+}
+
+/// The requests recorded in one power of two bucket.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    count: usize,
+    requested: usize
+}
+
+impl Bucket {
+    /// The average request size, or `None` if there were no requests.
+    fn average(&self) -> Option<usize> {
+        self.requested.checked_div(self.count)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Record an allocation request in the histogram.
+pub fn record(layout: &Layout) {
+    let size = layout.size().max(layout.align());
+    let bucket = bucket_index(size);
+
+    COUNTS[bucket].fetch_add(1, Ordering::Relaxed);
+    REQUESTED[bucket].fetch_add(size, Ordering::Relaxed);
+
+    if let Some(index) = list_index(layout) {
+        WASTED.fetch_add(BLOCK_SIZES[index] - size, Ordering::Relaxed);
+    }
+}
+
+/// Get the total number of allocations recorded.
+pub fn total_allocations() -> usize {
+    COUNTS.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+}
+
+/// Get the allocation size report, for printing.
+pub fn report() -> Report {
+    Report
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the histogram bucket for a request of `size` bytes.
+fn bucket_index(size: usize) -> usize {
+    (0..POW2_BUCKETS)
+        .find(|&bucket| size <= bucket_size(bucket))
+        .unwrap_or(POW2_BUCKETS)
+}
+
+/// Get the largest size held in the given power of two bucket.
+fn bucket_size(bucket: usize) -> usize {
+    1 << (bucket + MIN_BUCKET_SHIFT)
+}
+
+/// Suggest block sizes for the recorded requests, filling `sizes` in 
+/// ascending order and returning how many were suggested.
+/// 
+/// Only the average size of each bucket is recorded, so every request in a 
+/// bucket is treated as being the average size. Each bucket with at least 
+/// `SUGGEST_THRESHOLD_PERCENT` of the requests gets a size fitting its 
+/// average, rounded up to `SUGGEST_ALIGN`, so sizes needn't be powers of two.
+/// The largest current block size is always kept so no more requests fall 
+/// through to the fallback allocator. Sizes whose removal would add less than
+/// `MERGE_THRESHOLD_PERCENT` of the requested bytes to the rounding loss are 
+/// then dropped, cheapest first.
+fn suggest(
+    buckets: &[Bucket; POW2_BUCKETS], 
+    sizes: &mut [usize; POW2_BUCKETS + 1]
+) -> usize {
+    let largest = BLOCK_SIZES[BLOCK_SIZES.len() - 1];
+    let total: usize = buckets.iter().map(|b| b.count).sum();
+    let requested: usize = buckets.iter().map(|b| b.requested).sum();
+    let mut len = 0;
+
+    for bucket in buckets.iter() {
+        let average = match bucket.average() {
+            Some(average) => average,
+            None => continue
+        };
+        if bucket.count * 100 < total * SUGGEST_THRESHOLD_PERCENT {
+            continue;
+        }
+
+        let size = align_up(average.max(1), SUGGEST_ALIGN);
+        if size < largest && (len == 0 || size > sizes[len - 1]) {
+            sizes[len] = size;
+            len += 1;
+        }
+    }
+    sizes[len] = largest;
+    len += 1;
+
+    // Drop the cheapest size to remove until every removal costs too much
+    loop {
+        let waste = estimated_waste(buckets, &sizes[..len]);
+        let cheapest = (0..len - 1)
+            .map(|i| {
+                let mut without = *sizes;
+                without.copy_within(i + 1..len, i);
+                (i, estimated_waste(buckets, &without[..len - 1]) - waste)
+            })
+            .min_by_key(|&(_, extra)| extra);
+
+        let limit = requested * MERGE_THRESHOLD_PERCENT;
+        match cheapest {
+            Some((i, extra)) if extra * 100 < limit => {
+                sizes.copy_within(i + 1..len, i);
+                len -= 1;
+            },
+            _ => return len
+        }
+    }
+}
+
+/// Estimate the bytes lost to rounding requests up to `sizes`, treating 
+/// every request in a bucket as the bucket's average size.
+/// 
+/// Requests larger than every size go to the fallback allocator, so aren't 
+/// counted.
+fn estimated_waste(buckets: &[Bucket], sizes: &[usize]) -> usize {
+    buckets.iter()
+        .filter_map(|bucket| {
+            let average = bucket.average()?;
+            let size = sizes.iter().find(|&&s| s >= average)?;
+            Some(bucket.count * (size - average))
+        })
+        .sum()
+}
+
+/// Round `value` up to a multiple of `align`, which must be a power of two.
+fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    /// Build the buckets from `(bucket, count, average)` triples.
+    fn buckets(stats: &[(usize, usize, usize)]) -> [Bucket; POW2_BUCKETS] {
+        let mut buckets = [Bucket { count: 0, requested: 0 }; POW2_BUCKETS];
+        for &(bucket, count, average) in stats {
+            buckets[bucket] = Bucket { count, requested: count * average };
+        }
+        buckets
+    }
+
+    fn suggested(stats: &[(usize, usize, usize)]) -> Vec<usize> {
+        let mut sizes = [0; POW2_BUCKETS + 1];
+        let len = suggest(&buckets(stats), &mut sizes);
+        sizes[..len].to_vec()
+    }
+
+    #[test]
+    fn no_requests_keep_the_largest_size() {
+        assert_eq!(suggested(&[]), [2048]);
+    }
+
+    #[test]
+    fn sizes_fit_the_averages() {
+        // 24 and 40 byte requests aren't powers of two, and 40 rounds up to 
+        // 8 byte alignment
+        assert_eq!(suggested(&[(2, 1000, 24), (3, 1000, 37)]), 
+            [24, 40, 2048]);
+    }
+
+    #[test]
+    fn rare_sizes_are_ignored() {
+        assert_eq!(suggested(&[(2, 1000, 24), (6, 5, 500)]), [24, 2048]);
+    }
+
+    #[test]
+    fn close_sizes_are_merged() {
+        // Rounding the 30 byte requests up to 40 costs much less than 2% of 
+        // the bytes requested
+        assert_eq!(suggested(&[(2, 10, 30), (3, 1000, 33)]), [40, 2048]);
+
+        // But not when the smaller size is as common
+        assert_eq!(suggested(&[(2, 1000, 30), (3, 1000, 33)]), 
+            [32, 40, 2048]);
+    }
+
+    #[test]
+    fn waste_is_estimated_from_averages() {
+        let buckets = buckets(&[(2, 10, 20), (4, 2, 100)]);
+        assert_eq!(estimated_waste(&buckets, &[24, 128]), 10 * 4 + 2 * 28);
+
+        // Requests too large for every size go to the fallback allocator
+        assert_eq!(estimated_waste(&buckets, &[24]), 10 * 4);
+    }
+}
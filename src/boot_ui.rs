@@ -0,0 +1,142 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use crate::{print, println, serial_println};
+use crate::vga_buffer::{self, Colour};
+use bootloader::BootInfo;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// Whether the boot UI should print each stage on its own line rather than
+/// drawing the progress bar.
+pub const VERBOSE: bool = cfg!(feature = "verbose-boot");
+
+/// The number of characters inside the progress bar.
+const BAR_WIDTH: usize = 40;
+
+/// The width the stage name is padded to, so a shorter name fully overwrites
+/// a longer one when the bar is redrawn.
+const NAME_WIDTH: usize = 24;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Progress display for the kernel initialisation stages.
+///
+/// By default this draws a single progress bar line which is redrawn as each
+/// stage runs. With the `verbose-boot` feature each stage is instead printed
+/// as `<name>... complete` on its own line.
+pub struct BootProgress {
+    total: usize,
+    done: usize
+}
+
+impl BootProgress {
+
+    /// Create a new progress display for the given number of stages.
+    pub fn new(total: usize) -> BootProgress {
+        let progress = BootProgress {
+            total,
+            done: 0
+        };
+
+        if !VERBOSE {
+            progress.draw("Starting");
+        }
+
+        progress
+    }
+
+    /// Run a single initialisation stage, updating the display before and
+    /// after it runs.
+    pub fn stage<T>(&mut self, name: &str, f: impl FnOnce() -> T) -> T {
+        if VERBOSE {
+            print!("{}... ", name);
+        }
+        else {
+            self.draw(name);
+        }
+
+        let result = f();
+        self.done += 1;
+
+        if VERBOSE {
+            println!("complete");
+        }
+        else {
+            self.draw(name);
+        }
+
+        result
+    }
+
+    /// Finish the progress display, moving onto a new line.
+    pub fn finish(self) {
+        if !VERBOSE {
+            self.draw("Complete");
+            println!();
+        }
+    }
+
+    /// Redraw the progress bar on the current line.
+    fn draw(&self, name: &str) {
+        let filled = BAR_WIDTH * self.done / self.total.max(1);
+
+        print!("\r[");
+        vga_buffer::set_colour(Colour::LightGreen, Colour::Black);
+        for _ in 0..filled {
+            print!("#");
+        }
+        vga_buffer::reset_colour();
+        for _ in filled..BAR_WIDTH {
+            print!(" ");
+        }
+        print!("] {}/{} {:<width$}",
+            self.done, self.total, name, width = NAME_WIDTH);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Log the handoff information passed by the bootloader to the serial port.
+/// 
+/// Only logs with the `verbose-boot` feature, since the memory map can be 
+/// long. This is intended for debugging mapping issues on new hardware.
+pub fn log_boot_info(boot_info: &BootInfo) {
+    if !VERBOSE {
+        return;
+    }
+
+    match crate::memory::physical_memory_offset(boot_info) {
+        Some(offset) => serial_println!(
+            "[BOOT] physical_memory_offset: {:#x}", offset.as_u64()),
+        None => serial_println!("[BOOT] physical_memory_offset: none")
+    }
+
+    let regions = boot_info.memory_map.iter();
+    serial_println!("[BOOT] memory_map: {} regions", regions.len());
+    for (i, region) in boot_info.memory_map.iter().enumerate() {
+        let start = region.range.start_addr();
+        let end = region.range.end_addr();
+        serial_println!("[BOOT]   {:>3}: {:#012x} - {:#012x} {:>8} KiB {:?}",
+            i, start, end, (end - start) / 1024, region.region_type);
+    }
+
+    match boot_info.tls_template() {
+        Some(tls) => serial_println!(
+            "[BOOT] tls_template: start {:#x}, file size {}, mem size {}",
+            tls.start_addr, tls.file_size, tls.mem_size),
+        None => serial_println!("[BOOT] tls_template: none")
+    }
+
+    // The bootloader doesn't pass these, but log them so it's clear they 
+    // were looked for
+    serial_println!("[BOOT] framebuffer: not provided by bootloader");
+    serial_println!("[BOOT] rsdp: not provided by bootloader");
+}
@@ -0,0 +1,184 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::string::String;
+use spin::Mutex;
+use pc_keyboard::{DecodedKey, KeyCode};
+use crate::print;
+use crate::vga_buffer::{self, BUFFER_HEIGHT, BUFFER_WIDTH};
+
+// ---------------------------------------------------------------------------
+// STATICS
+// ---------------------------------------------------------------------------
+
+/// The in-kernel clipboard, holding the text of the last copied selection.
+static CLIPBOARD: Mutex<String> = Mutex::new(String::new());
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The state of a `Selection` after handling a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionState {
+    /// The selection is still active and wants more keys.
+    Active,
+
+    /// The selection has been copied or cancelled and should be dropped.
+    Finished
+}
+
+/// A keyboard-driven selection of a region of the screen.
+///
+/// The arrow keys move the selection cursor, space marks the start of the
+/// region, enter copies the region into the clipboard and escape cancels the
+/// selection. The selected region is highlighted by inverting its colours.
+pub struct Selection {
+    /// The position of the selection cursor as a (row, column) pair.
+    cursor: (usize, usize),
+
+    /// The position the region was marked from, if it has been marked.
+    mark: Option<(usize, usize)>,
+
+    /// The inclusive range of screen cells currently highlighted.
+    highlighted: Option<(usize, usize)>
+}
+
+impl Selection {
+
+    /// Begin a new selection with the cursor at the current writer position.
+    pub fn begin() -> Selection {
+        let mut selection = Selection {
+            cursor: vga_buffer::position(),
+            mark: None,
+            highlighted: None
+        };
+        selection.refresh();
+        selection
+    }
+
+    /// Handle a single key press while in selection mode.
+    pub fn handle_key(&mut self, key: DecodedKey) -> SelectionState {
+        let (row, col) = self.cursor;
+
+        match key {
+            DecodedKey::RawKey(KeyCode::ArrowUp) =>
+                self.cursor.0 = row.saturating_sub(1),
+            DecodedKey::RawKey(KeyCode::ArrowDown) =>
+                self.cursor.0 = (row + 1).min(BUFFER_HEIGHT - 1),
+            DecodedKey::RawKey(KeyCode::ArrowLeft) =>
+                self.cursor.1 = col.saturating_sub(1),
+            DecodedKey::RawKey(KeyCode::ArrowRight) =>
+                self.cursor.1 = (col + 1).min(BUFFER_WIDTH - 1),
+            DecodedKey::Unicode(' ') => self.mark = Some(self.cursor),
+            DecodedKey::Unicode('\n') => {
+                self.copy();
+                self.finish();
+                return SelectionState::Finished;
+            },
+            DecodedKey::Unicode('\u{1b}')
+            | DecodedKey::RawKey(KeyCode::Escape) => {
+                self.finish();
+                return SelectionState::Finished;
+            },
+            _ => ()
+        }
+
+        self.refresh();
+        SelectionState::Active
+    }
+
+    /// Get the inclusive range of cells covered by the selection, as linear
+    /// indexes into the screen.
+    fn range(&self) -> Option<(usize, usize)> {
+        let mark = self.mark?;
+        let a = mark.0 * BUFFER_WIDTH + mark.1;
+        let b = self.cursor.0 * BUFFER_WIDTH + self.cursor.1;
+        Some((a.min(b), a.max(b)))
+    }
+
+    /// Update the highlighted cells and the hardware cursor to match the
+    /// current selection.
+    fn refresh(&mut self) {
+        // Inverting is its own inverse, so clear the old highlight by
+        // inverting it again before highlighting the new range.
+        if let Some(range) = self.highlighted.take() {
+            invert_range(range);
+        }
+
+        if let Some(range) = self.range() {
+            invert_range(range);
+            self.highlighted = Some(range);
+        }
+
+        vga_buffer::set_cursor(self.cursor.0, self.cursor.1);
+    }
+
+    /// Copy the selected text into the clipboard.
+    ///
+    /// Trailing spaces on each row are dropped and rows are separated by
+    /// newlines.
+    fn copy(&self) {
+        let (start, end) = match self.range() {
+            Some(range) => range,
+            None => return
+        };
+
+        let mut text = String::new();
+        let mut line = String::new();
+        for idx in start..=end {
+            let (row, col) = (idx / BUFFER_WIDTH, idx % BUFFER_WIDTH);
+            line.push(char::from(vga_buffer::read_char(row, col)));
+
+            // At the end of a row push the trimmed line into the text
+            if col == BUFFER_WIDTH - 1 || idx == end {
+                text.push_str(line.trim_end());
+                if idx != end {
+                    text.push('\n');
+                }
+                line.clear();
+            }
+        }
+
+        *CLIPBOARD.lock() = text;
+    }
+
+    /// Remove the highlight and return the hardware cursor to the writer.
+    fn finish(&mut self) {
+        if let Some(range) = self.highlighted.take() {
+            invert_range(range);
+        }
+
+        let (row, col) = vga_buffer::position();
+        vga_buffer::set_cursor(row, col);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get a copy of the current clipboard contents.
+pub fn contents() -> String {
+    CLIPBOARD.lock().clone()
+}
+
+/// Paste the clipboard contents into the console input.
+pub fn paste() {
+    // Copy the contents out first so the clipboard isn't locked while
+    // printing.
+    let text = contents();
+    print!("{}", text);
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Invert every cell in the inclusive range of linear screen indexes.
+fn invert_range((start, end): (usize, usize)) {
+    for idx in start..=end {
+        vga_buffer::invert_cell(idx / BUFFER_WIDTH, idx % BUFFER_WIDTH);
+    }
+}
@@ -0,0 +1,321 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The shortest match which can be encoded.
+const MIN_MATCH: usize = 4;
+
+/// The last 5 bytes of a block are always literals.
+const LAST_LITERALS: usize = 5;
+
+/// The last match must start at least 12 bytes before the end of the block.
+const MF_LIMIT: usize = 12;
+
+/// The furthest back a match can be.
+const MAX_OFFSET: usize = 0xffff;
+
+/// The length nibble value meaning more length bytes follow.
+const RUN_MASK: usize = 0xf;
+
+/// The number of bits of hash used to index the match finder table.
+const HASH_LOG: u32 = 11;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Errors which can occur when compressing or decompressing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lz4Error {
+    /// The output buffer is too small.
+    OutputTooSmall,
+
+    /// The compressed data is truncated or invalid.
+    Corrupt
+}
+
+impl fmt::Display for Lz4Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lz4Error::OutputTooSmall => write!(f, "output buffer too small"),
+            Lz4Error::Corrupt => write!(f, "compressed data is corrupt")
+        }
+    }
+}
+
+/// A position in an output buffer which is checked on every write.
+struct Output<'a> {
+    buf: &'a mut [u8],
+    len: usize
+}
+
+impl<'a> Output<'a> {
+
+    /// Append a byte.
+    fn push(&mut self, byte: u8) -> Result<(), Lz4Error> {
+        *self.buf.get_mut(self.len).ok_or(Lz4Error::OutputTooSmall)? = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Append a slice.
+    fn extend(&mut self, bytes: &[u8]) -> Result<(), Lz4Error> {
+        self.buf.get_mut(self.len..self.len + bytes.len())
+            .ok_or(Lz4Error::OutputTooSmall)?
+            .copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    /// Append the bytes of a length beyond what fits in a token nibble.
+    fn push_length(&mut self, mut len: usize) -> Result<(), Lz4Error> {
+        while len >= 0xff {
+            self.push(0xff)?;
+            len -= 0xff;
+        }
+        self.push(len as u8)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// The largest compressed size of `len` bytes of input, for sizing output 
+/// buffers.
+pub fn max_compressed_len(len: usize) -> usize {
+    len + len / 255 + 16
+}
+
+/// Compress `input` into `output` as an LZ4 block, returning the compressed
+/// length.
+/// 
+/// This is a greedy single pass compressor with a small match table kept on
+/// the stack, so it doesn't allocate and can be used on crash paths.
+pub fn compress(input: &[u8], output: &mut [u8]) -> Result<usize, Lz4Error> {
+    let mut out = Output { buf: output, len: 0 };
+    let mut table = [0u32; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut pos = 0;
+
+    if input.len() > MF_LIMIT {
+        let match_limit = input.len() - MF_LIMIT;
+        let match_end_limit = input.len() - LAST_LITERALS;
+
+        while pos < match_limit {
+            let sequence = read_u32(input, pos);
+            let slot = &mut table[hash(sequence)];
+            let candidate = *slot as usize;
+
+            // Table entries are positions plus one, so 0 is empty
+            *slot = pos as u32 + 1;
+
+            if candidate == 0 || pos - (candidate - 1) > MAX_OFFSET 
+                || read_u32(input, candidate - 1) != sequence {
+                pos += 1;
+                continue;
+            }
+            let start = candidate - 1;
+
+            let mut len = MIN_MATCH;
+            while pos + len < match_end_limit 
+                && input[start + len] == input[pos + len] {
+                len += 1;
+            }
+
+            write_sequence(&mut out, &input[anchor..pos], pos - start, len)?;
+            pos += len;
+            anchor = pos;
+        }
+    }
+
+    // The block ends with a sequence of just literals
+    let literals = &input[anchor..];
+    out.push((literals.len().min(RUN_MASK) << 4) as u8)?;
+    if literals.len() >= RUN_MASK {
+        out.push_length(literals.len() - RUN_MASK)?;
+    }
+    out.extend(literals)?;
+
+    Ok(out.len)
+}
+
+/// Decompress an LZ4 block from `input` into `output`, returning the 
+/// decompressed length.
+pub fn decompress(input: &[u8], output: &mut [u8]) 
+    -> Result<usize, Lz4Error> {
+
+    let mut pos = 0;
+    let mut out = 0;
+
+    loop {
+        let token = *input.get(pos).ok_or(Lz4Error::Corrupt)? as usize;
+        pos += 1;
+
+        // Copy the literals
+        let mut literal_len = token >> 4;
+        if literal_len == RUN_MASK {
+            literal_len += read_length(input, &mut pos)?;
+        }
+        let literals = input.get(pos..pos + literal_len)
+            .ok_or(Lz4Error::Corrupt)?;
+        output.get_mut(out..out + literal_len)
+            .ok_or(Lz4Error::OutputTooSmall)?
+            .copy_from_slice(literals);
+        pos += literal_len;
+        out += literal_len;
+
+        // The last sequence has no match
+        if pos == input.len() {
+            return Ok(out);
+        }
+
+        // Copy the match, a byte at a time as it may overlap itself
+        let offset = input.get(pos..pos + 2).ok_or(Lz4Error::Corrupt)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        pos += 2;
+        if offset == 0 || offset > out {
+            return Err(Lz4Error::Corrupt);
+        }
+
+        let mut match_len = token & RUN_MASK;
+        if match_len == RUN_MASK {
+            match_len += read_length(input, &mut pos)?;
+        }
+        match_len += MIN_MATCH;
+        if out + match_len > output.len() {
+            return Err(Lz4Error::OutputTooSmall);
+        }
+        for i in out..out + match_len {
+            output[i] = output[i - offset];
+        }
+        out += match_len;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Write a sequence of literals followed by a match.
+fn write_sequence(
+    out: &mut Output, 
+    literals: &[u8], 
+    offset: usize, 
+    match_len: usize
+) -> Result<(), Lz4Error> {
+    let match_code = match_len - MIN_MATCH;
+    let token = literals.len().min(RUN_MASK) << 4 | match_code.min(RUN_MASK);
+    out.push(token as u8)?;
+
+    if literals.len() >= RUN_MASK {
+        out.push_length(literals.len() - RUN_MASK)?;
+    }
+    out.extend(literals)?;
+    out.extend(&(offset as u16).to_le_bytes())?;
+    if match_code >= RUN_MASK {
+        out.push_length(match_code - RUN_MASK)?;
+    }
+    Ok(())
+}
+
+/// Read the extra bytes of a length, which continue while they are 255.
+fn read_length(input: &[u8], pos: &mut usize) -> Result<usize, Lz4Error> {
+    let mut len = 0;
+    loop {
+        let byte = *input.get(*pos).ok_or(Lz4Error::Corrupt)?;
+        *pos += 1;
+        len += byte as usize;
+        if byte != 0xff {
+            return Ok(len);
+        }
+    }
+}
+
+/// Read 4 bytes from `pos` as a little endian word.
+fn read_u32(input: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([
+        input[pos], input[pos + 1], input[pos + 2], input[pos + 3]
+    ])
+}
+
+/// Hash 4 bytes of input into a match table index.
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> usize {
+        let mut compressed = [0u8; 8192];
+        let len = compress(input, &mut compressed).unwrap();
+        assert!(len <= max_compressed_len(input.len()));
+
+        let mut output = [0u8; 4096];
+        let out_len = decompress(&compressed[..len], &mut output).unwrap();
+        assert_eq!(&output[..out_len], input);
+        len
+    }
+
+    #[test]
+    fn decompress_known_block() {
+        // "abc", then a 6 byte match 3 back, then 5 literals
+        let block = [0x32, b'a', b'b', b'c', 3, 0, 
+            0x50, b'x', b'y', b'z', b'a', b'b'];
+        let mut output = [0u8; 32];
+        let len = decompress(&block, &mut output).unwrap();
+        assert_eq!(&output[..len], b"abcabcabcxyzab");
+
+        // Too small, truncated, and bad offsets
+        assert_eq!(decompress(&block, &mut output[..8]), 
+            Err(Lz4Error::OutputTooSmall));
+        assert_eq!(decompress(&block[..5], &mut output), 
+            Err(Lz4Error::Corrupt));
+        assert_eq!(decompress(&[0x10, b'a', 2, 0], &mut output), 
+            Err(Lz4Error::Corrupt));
+    }
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(round_trip(b""), 1);
+        round_trip(b"short");
+
+        // Repetitive data compresses well, including long runs
+        let mut repetitive = [0u8; 4000];
+        for (i, byte) in repetitive.iter_mut().enumerate() {
+            *byte = b"the quick brown fox "[i % 20];
+        }
+        assert!(round_trip(&repetitive) < 100);
+        assert!(round_trip(&[7u8; 4000]) < 50);
+
+        // Incompressible data still round trips
+        let mut noise = [0u8; 4000];
+        let mut state = 0x1234_5678u32;
+        for byte in noise.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        round_trip(&noise);
+    }
+
+    #[test]
+    fn compress_output_too_small() {
+        let mut output = [0u8; 4];
+        assert_eq!(compress(b"hello world, hello world", &mut output), 
+            Err(Lz4Error::OutputTooSmall));
+    }
+}
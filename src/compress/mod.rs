@@ -0,0 +1,5 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod lz4;
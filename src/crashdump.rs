@@ -0,0 +1,212 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::{fmt, mem, slice, str};
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use core::sync::atomic::{compiler_fence, Ordering};
+use conquer_once::spin::OnceCell;
+use x86_64::{PhysAddr, VirtAddr};
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use crate::println;
+use crate::memory::BootInfoFrameAllocator;
+use crate::interrupts::nmi;
+use crate::symbols;
+use crate::vga_buffer::{self, BUFFER_WIDTH};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The size of the crash dump region.
+pub const CRASHDUMP_SIZE: u64 = 4 * 4096;
+
+/// Magic value marking a valid dump, "SCOSDUMP".
+const CRASHDUMP_MAGIC: u64 = 0x504d_5544_534f_4353;
+
+/// The number of console lines included in the dump.
+const DUMP_LINES: usize = 16;
+
+/// The virtual address of the crash dump region, set by `init`.
+static REGION: OnceCell<VirtAddr> = OnceCell::uninit();
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Header at the start of the crash dump region.
+#[repr(C)]
+struct DumpHeader {
+    magic: u64,
+    len: u32,
+    checksum: u32
+}
+
+/// Writer which fills the text area of the dump, dropping anything that
+/// doesn't fit.
+struct DumpWriter {
+    buf: &'static mut [u8],
+    len: usize
+}
+
+impl fmt::Write for DumpWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let count = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + count]
+            .copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Initialise the crash dump region.
+///
+/// The region is the last `CRASHDUMP_SIZE` bytes of the highest usable
+/// region in the memory map, so it is at the same physical address after a
+/// warm reboot on the same machine. It is reserved from the frame allocator
+/// so the kernel never overwrites it.
+///
+/// This must be called before any frames are allocated.
+pub fn init(
+    memory_map: &MemoryMap,
+    phys_offset: VirtAddr,
+    frame_allocator: &mut BootInfoFrameAllocator
+) {
+    let region = memory_map.iter()
+        .filter(|r| r.region_type == MemoryRegionType::Usable)
+        .filter(|r| r.range.end_addr() - r.range.start_addr() >= CRASHDUMP_SIZE)
+        .max_by_key(|r| r.range.end_addr());
+
+    let end = match region {
+        Some(region) => region.range.end_addr(),
+        None => {
+            println!("[DUMP-ERROR] No usable region for the crash dump");
+            return;
+        }
+    };
+    let start = end - CRASHDUMP_SIZE;
+
+    frame_allocator.reserve(PhysAddr::new(start), PhysAddr::new(end));
+    REGION.try_init_once(|| phys_offset + start)
+        .expect("crashdump::init must only be called once");
+}
+
+/// Print the dump left by the previous boot, if there is one, and clear it.
+pub fn print_previous() {
+    if let Some(text) = previous() {
+        vga_buffer::divider(b'!');
+        println!("Previous boot crashed:\n");
+        println!("{}", text);
+        vga_buffer::divider(b'!');
+    }
+
+    clear();
+}
+
+/// Get the text of the dump left by the previous boot, if there is a valid
+/// one.
+pub fn previous() -> Option<&'static str> {
+    let header = header()?;
+    if header.magic != CRASHDUMP_MAGIC
+        || header.len as usize > text_capacity() {
+        return None;
+    }
+
+    let text = &text_area()?[..header.len as usize];
+    if checksum(text) != header.checksum {
+        return None;
+    }
+
+    str::from_utf8(text).ok()
+}
+
+/// Write a crash dump for the given panic.
+///
+/// This is called from the panic handler so does not allocate or take any
+/// locks. Does nothing if the region hasn't been initialised yet.
+pub fn write(info: &PanicInfo) {
+    let buf = match text_area() {
+        Some(buf) => buf,
+        None => return
+    };
+    let mut writer = DumpWriter { buf, len: 0 };
+
+    // Errors can't happen as the writer truncates instead
+    let _ = writeln!(writer, "{}", info);
+
+    let _ = writeln!(writer);
+    let _ = symbols::write_backtrace(&mut writer);
+
+    if let Some(area) = nmi::crash_area() {
+        let _ = writeln!(writer, "\nNMI received: {} at {:#x}",
+            area.reason_str(), area.instruction_pointer);
+    }
+
+    let _ = writeln!(writer, "\nLast console lines:");
+    let mut lines = [[b' '; BUFFER_WIDTH]; DUMP_LINES];
+    vga_buffer::recent_lines(&mut lines);
+    for line in lines.iter() {
+        let line = str::from_utf8(line).unwrap_or("<invalid>");
+        let _ = writeln!(writer, "  | {}", line.trim_end());
+    }
+
+    let len = writer.len;
+    let sum = checksum(&writer.buf[..len]);
+
+    // Make sure the text is written before the header marks it as valid
+    compiler_fence(Ordering::SeqCst);
+
+    if let Some(header) = header() {
+        header.len = len as u32;
+        header.checksum = sum;
+        header.magic = CRASHDUMP_MAGIC;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the dump header, if the region is initialised.
+fn header() -> Option<&'static mut DumpHeader> {
+    let region = REGION.try_get().ok()?;
+
+    // NOTE: USE OF UNSAFE
+    //  The region is mapped through the physical memory offset and reserved
+    //  from the frame allocator, so nothing else uses it.
+    Some(unsafe { &mut *region.as_mut_ptr::<DumpHeader>() })
+}
+
+/// The number of bytes of text which fit in the region.
+fn text_capacity() -> usize {
+    CRASHDUMP_SIZE as usize - mem::size_of::<DumpHeader>()
+}
+
+/// Get the text area following the header, if the region is initialised.
+fn text_area() -> Option<&'static mut [u8]> {
+    let region = REGION.try_get().ok()?;
+    let start = *region + mem::size_of::<DumpHeader>();
+
+    // NOTE: USE OF UNSAFE
+    //  See `header`.
+    Some(unsafe {
+        slice::from_raw_parts_mut(start.as_mut_ptr::<u8>(), text_capacity())
+    })
+}
+
+/// Invalidate the dump so it isn't reported again.
+fn clear() {
+    if let Some(header) = header() {
+        header.magic = 0;
+    }
+}
+
+/// A simple rotating checksum used to detect a stale or corrupt dump.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |sum, &b| sum.rotate_left(5) ^ b as u32)
+}
@@ -0,0 +1,259 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use x86_64::VirtAddr;
+use x86_64::registers::rflags::RFlags;
+use x86_64::structures::idt::InterruptStackFrame;
+use crate::emergency_println;
+use crate::symbols::SymbolisedAddr;
+use super::{read_dr, write_dr, DEBUG_ADDR_REGS};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The watchpoints set in each debug address register.
+static SLOTS: Mutex<[Option<Watchpoint>; DEBUG_ADDR_REGS]> = 
+    Mutex::new([None; DEBUG_ADDR_REGS]);
+
+/// The number of times each slot has been hit.
+static HITS: [AtomicUsize; DEBUG_ADDR_REGS] = [
+    AtomicUsize::new(0), AtomicUsize::new(0), 
+    AtomicUsize::new(0), AtomicUsize::new(0)
+];
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The access which triggers a watchpoint, encoded as the DR7 R/W bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum Condition {
+    /// Executing the instruction at the address.
+    Execute = 0b00,
+
+    /// Writing to the address.
+    Write = 0b01,
+
+    /// Reading or writing the address.
+    ReadWrite = 0b11
+}
+
+/// The size of the watched range, encoded as the DR7 LEN bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum Length {
+    Byte = 0b00,
+    Word = 0b01,
+    Qword = 0b10,
+    Dword = 0b11
+}
+
+impl Length {
+
+    /// The length in bytes.
+    pub fn bytes(self) -> u64 {
+        match self {
+            Length::Byte => 1,
+            Length::Word => 2,
+            Length::Dword => 4,
+            Length::Qword => 8
+        }
+    }
+}
+
+/// A hardware watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: VirtAddr,
+    pub condition: Condition,
+    pub len: Length
+}
+
+/// Errors which can occur when setting a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwBreakpointError {
+    /// All the debug address registers are in use.
+    NoFreeSlot,
+
+    /// The address isn't aligned to the length.
+    Misaligned,
+
+    /// Execute breakpoints must have a length of one byte.
+    BadLength
+}
+
+impl fmt::Display for HwBreakpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            HwBreakpointError::NoFreeSlot => "no free debug register",
+            HwBreakpointError::Misaligned => "address not aligned to length",
+            HwBreakpointError::BadLength => 
+                "execute breakpoints must be one byte"
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Set a watchpoint, returning the slot it was set in.
+/// 
+/// The debug exception is raised after a data access completes, so the 
+/// reported instruction pointer is the one after the access. Execute 
+/// breakpoints are raised before the instruction runs, and the handler sets
+/// the resume flag so it runs on return instead of faulting again.
+pub fn set(addr: VirtAddr, condition: Condition, len: Length) 
+    -> Result<usize, HwBreakpointError> {
+
+    if condition == Condition::Execute && len != Length::Byte {
+        return Err(HwBreakpointError::BadLength);
+    }
+    if addr.as_u64() % len.bytes() != 0 {
+        return Err(HwBreakpointError::Misaligned);
+    }
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut slots = SLOTS.lock();
+        let slot = slots.iter().position(|s| s.is_none())
+            .ok_or(HwBreakpointError::NoFreeSlot)?;
+
+        slots[slot] = Some(Watchpoint { addr, condition, len });
+        HITS[slot].store(0, Ordering::Relaxed);
+        write_dr(slot, addr.as_u64());
+
+        let shift = 16 + 4 * slot;
+        let mut dr7 = read_dr(7) & !(0b1111 << shift);
+        dr7 |= ((len as u64) << 2 | condition as u64) << shift;
+        dr7 |= 1 << (2 * slot);
+        write_dr(7, dr7);
+
+        Ok(slot)
+    })
+}
+
+/// Clear the watchpoint in `slot`, returning it if one was set.
+pub fn clear(slot: usize) -> Option<Watchpoint> {
+    if slot >= DEBUG_ADDR_REGS {
+        return None;
+    }
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let watchpoint = SLOTS.lock()[slot].take()?;
+        write_dr(7, read_dr(7) & !(1 << (2 * slot)));
+        write_dr(slot, 0);
+        Some(watchpoint)
+    })
+}
+
+/// Get the watchpoint set in `slot`.
+pub fn get(slot: usize) -> Option<Watchpoint> {
+    x86_64::instructions::interrupts::without_interrupts(|| 
+        SLOTS.lock().get(slot).copied().flatten())
+}
+
+/// The number of times the watchpoint in `slot` has been hit since it was 
+/// set.
+pub fn hits(slot: usize) -> usize {
+    HITS.get(slot).map_or(0, |hits| hits.load(Ordering::Relaxed))
+}
+
+/// Report any watchpoints flagged as hit in DR6.
+/// 
+/// This doesn't take the slots lock, since the exception could have 
+/// interrupted `set` or `clear`, so the watchpoint is described from the 
+/// debug registers.
+pub(super) fn handle(dr6: u64, stack_frame: &mut InterruptStackFrame) {
+    let dr7 = read_dr(7);
+
+    for slot in 0..DEBUG_ADDR_REGS {
+        if dr6 & (1 << slot) == 0 {
+            continue;
+        }
+
+        HITS[slot].fetch_add(1, Ordering::Relaxed);
+        emergency_println!("[DEBUG] Watchpoint {} ({:#x}) hit by {}", 
+            slot, read_dr(slot), 
+            SymbolisedAddr(stack_frame.instruction_pointer.as_u64()));
+
+        // An execute breakpoint faults before the instruction runs, so 
+        // without the resume flag it would fault again on return
+        if (dr7 >> (16 + 4 * slot)) & 0b11 == Condition::Execute as u64 {
+            // NOTE: USE OF UNSAFE
+            //  Only the resume flag is set, which just suppresses 
+            //  instruction breakpoints for the next instruction.
+            unsafe {
+                stack_frame.as_mut().cpu_flags |= RFlags::RESUME_FLAG.bits();
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_write_watchpoint() {
+    use crate::{serial_print, serial_println};
+    serial_print!("debug::hw_breakpoints::write_watchpoint ");
+
+    static mut WATCHED: u64 = 0;
+
+    // NOTE: USE OF UNSAFE
+    //  The static is only accessed by this test, through volatile accesses 
+    //  so the write isn't optimised away.
+    unsafe {
+        let addr = VirtAddr::new(&WATCHED as *const u64 as u64);
+        let slot = set(addr, Condition::Write, Length::Qword).unwrap();
+
+        core::ptr::write_volatile(&mut WATCHED, 1);
+        assert_eq!(hits(slot), 1);
+
+        // Reads don't trigger a write watchpoint
+        assert_eq!(core::ptr::read_volatile(&WATCHED), 1);
+        assert_eq!(hits(slot), 1);
+
+        assert_eq!(clear(slot).map(|w| w.addr), Some(addr));
+        core::ptr::write_volatile(&mut WATCHED, 2);
+        assert_eq!(hits(slot), 1);
+    }
+
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn test_execute_breakpoint() {
+    use crate::{serial_print, serial_println};
+    serial_print!("debug::hw_breakpoints::execute_breakpoint ");
+
+    #[inline(never)]
+    fn target(x: u64) -> u64 {
+        x + 1
+    }
+
+    // Call through a volatile read so the call isn't inlined
+    let func: fn(u64) -> u64 = target;
+    // NOTE: USE OF UNSAFE
+    //  Reading a local through a valid reference.
+    let func = unsafe { core::ptr::read_volatile(&func) };
+
+    let addr = VirtAddr::new(target as usize as u64);
+    let slot = set(addr, Condition::Execute, Length::Byte).unwrap();
+
+    // Returning at all shows the handler resumed past the breakpoint
+    assert_eq!(func(1), 2);
+    assert_eq!(hits(slot), 1);
+    assert_eq!(func(2), 3);
+    assert_eq!(hits(slot), 2);
+
+    clear(slot);
+    serial_println!("[ok]");
+}
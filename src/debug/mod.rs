@@ -0,0 +1,86 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod hw_breakpoints;
+pub mod trace;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use x86_64::structures::idt::InterruptStackFrame;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The number of hardware breakpoint address registers, DR0 to DR3.
+pub const DEBUG_ADDR_REGS: usize = 4;
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Handle a debug exception, reporting whatever caused it.
+/// 
+/// Called from the debug exception handler, which can interrupt any code so 
+/// this must not take locks which that code could hold.
+pub(crate) fn handle_exception(stack_frame: &mut InterruptStackFrame) {
+    let dr6 = read_dr(6);
+    hw_breakpoints::handle(dr6, stack_frame);
+    if dr6 & trace::DR6_SINGLE_STEP != 0 {
+        trace::handle(stack_frame);
+    }
+
+    // DR6 is never cleared by the CPU
+    write_dr(6, 0);
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read a debug register.
+/// 
+/// Panics if `n` isn't one of DR0-DR3, DR6 or DR7.
+fn read_dr(n: usize) -> u64 {
+    let value: u64;
+
+    // NOTE: USE OF UNSAFE
+    //  Reading debug registers has no side effects, and the kernel always 
+    //  runs in ring 0 where they are accessible.
+    unsafe {
+        match n {
+            0 => llvm_asm!("mov %dr0, $0" : "=r"(value) ::: "volatile"),
+            1 => llvm_asm!("mov %dr1, $0" : "=r"(value) ::: "volatile"),
+            2 => llvm_asm!("mov %dr2, $0" : "=r"(value) ::: "volatile"),
+            3 => llvm_asm!("mov %dr3, $0" : "=r"(value) ::: "volatile"),
+            6 => llvm_asm!("mov %dr6, $0" : "=r"(value) ::: "volatile"),
+            7 => llvm_asm!("mov %dr7, $0" : "=r"(value) ::: "volatile"),
+            _ => panic!("[DEBUG-ERROR] No debug register DR{}", n)
+        }
+    }
+
+    value
+}
+
+/// Write a debug register.
+/// 
+/// Panics if `n` isn't one of DR0-DR3, DR6 or DR7.
+fn write_dr(n: usize, value: u64) {
+    // NOTE: USE OF UNSAFE
+    //  Debug registers only cause debug exceptions, which are handled by 
+    //  `handle_exception`, and the kernel always runs in ring 0.
+    unsafe {
+        match n {
+            0 => llvm_asm!("mov $0, %dr0" :: "r"(value) :: "volatile"),
+            1 => llvm_asm!("mov $0, %dr1" :: "r"(value) :: "volatile"),
+            2 => llvm_asm!("mov $0, %dr2" :: "r"(value) :: "volatile"),
+            3 => llvm_asm!("mov $0, %dr3" :: "r"(value) :: "volatile"),
+            6 => llvm_asm!("mov $0, %dr6" :: "r"(value) :: "volatile"),
+            7 => llvm_asm!("mov $0, %dr7" :: "r"(value) :: "volatile"),
+            _ => panic!("[DEBUG-ERROR] No debug register DR{}", n)
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use x86_64::registers::rflags::{self, RFlags};
+use x86_64::structures::idt::InterruptStackFrame;
+use crate::emergency_println;
+use crate::symbols::SymbolisedAddr;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// DR6 bit set when the debug exception was caused by a single step.
+pub(super) const DR6_SINGLE_STEP: u64 = 1 << 14;
+
+/// Set while a trace is running.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether each step is printed with its symbol.
+static SYMBOLS: AtomicBool = AtomicBool::new(false);
+
+/// The number of steps traced so far.
+static STEPS: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of steps after which tracing stops.
+static MAX_STEPS: AtomicUsize = AtomicUsize::new(0);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The result of a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSummary {
+    /// The number of instructions traced.
+    pub steps: usize,
+
+    /// `true` if tracing stopped at `max_steps` before the code finished.
+    pub truncated: bool
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Run `f`, logging the address of each instruction it executes to serial.
+/// 
+/// Tracing stops after `max_steps` instructions. With `symbols` each address
+/// is printed with the symbol containing it, which is much slower. Interrupt 
+/// handlers aren't traced, since the CPU clears the trap flag on entry.
+/// 
+/// Panics if a trace is already running.
+pub fn trace<T>(max_steps: usize, symbols: bool, f: impl FnOnce() -> T) 
+    -> (T, TraceSummary) {
+
+    assert!(!ACTIVE.swap(true, Ordering::AcqRel), 
+        "[DEBUG-ERROR] A trace is already running");
+    STEPS.store(0, Ordering::Relaxed);
+    MAX_STEPS.store(max_steps, Ordering::Relaxed);
+    SYMBOLS.store(symbols, Ordering::Relaxed);
+
+    set_trap_flag(true);
+    let result = f();
+    set_trap_flag(false);
+
+    ACTIVE.store(false, Ordering::Release);
+
+    let steps = STEPS.load(Ordering::Relaxed);
+    (result, TraceSummary { steps, truncated: steps >= max_steps })
+}
+
+/// Log a single step, and stop tracing once the step limit is reached.
+pub(super) fn handle(stack_frame: &mut InterruptStackFrame) {
+    let rip = stack_frame.instruction_pointer.as_u64();
+
+    if !ACTIVE.load(Ordering::Acquire) {
+        clear_frame_trap_flag(stack_frame);
+        return;
+    }
+
+    let step = STEPS.fetch_add(1, Ordering::Relaxed) + 1;
+    if SYMBOLS.load(Ordering::Relaxed) {
+        emergency_println!("[TRACE] {:>6} {}", step, SymbolisedAddr(rip));
+    }
+    else {
+        emergency_println!("[TRACE] {:>6} {:#x}", step, rip);
+    }
+
+    if step >= MAX_STEPS.load(Ordering::Relaxed) {
+        emergency_println!("[TRACE] Step limit reached");
+        clear_frame_trap_flag(stack_frame);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Set or clear the trap flag of the current code.
+fn set_trap_flag(set: bool) {
+    let mut flags = rflags::read();
+    flags.set(RFlags::TRAP_FLAG, set);
+
+    // NOTE: USE OF UNSAFE
+    //  Only the trap flag is changed, which raises debug exceptions handled 
+    //  by `debug::handle_exception`.
+    unsafe { rflags::write(flags) };
+}
+
+/// Clear the trap flag the interrupted code will return with.
+fn clear_frame_trap_flag(stack_frame: &mut InterruptStackFrame) {
+    // NOTE: USE OF UNSAFE
+    //  Only the trap flag is cleared, which just stops single stepping.
+    unsafe {
+        stack_frame.as_mut().cpu_flags &= !RFlags::TRAP_FLAG.bits();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_trace_steps() {
+    use crate::{serial_print, serial_println};
+    serial_print!("debug::trace::trace_steps ");
+
+    // An atomic is used so the loop can't be optimised away
+    let sum = core::sync::atomic::AtomicU64::new(0);
+    let add_up_to = |n| for x in 0..n {
+        sum.fetch_add(x, Ordering::Relaxed);
+    };
+
+    let ((), summary) = trace(1000, false, || add_up_to(4));
+    assert_eq!(sum.load(Ordering::Relaxed), 6);
+    assert!(summary.steps > 0 && !summary.truncated);
+    assert!(!rflags::read().contains(RFlags::TRAP_FLAG));
+
+    let ((), summary) = trace(3, false, || add_up_to(100));
+    assert_eq!(summary, TraceSummary { steps: 3, truncated: true });
+
+    serial_println!("[ok]");
+}
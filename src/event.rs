@@ -0,0 +1,166 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::{sync::{Arc, Weak}, vec::Vec};
+use core::{pin::Pin, task::{Context, Poll}};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_queue::ArrayQueue;
+use futures_util::{stream::Stream, task::AtomicWaker};
+use pc_keyboard::KeyCode;
+use spin::Mutex;
+use crate::memory::pressure::Pressure;
+use crate::sensors::Reading;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of events each subscriber can have waiting before further 
+/// events are dropped.
+const SUBSCRIBER_QUEUE_SIZE: usize = 32;
+
+/// Published when a device is added, with the name of the device.
+pub static DEVICE_ADDED: Channel<&'static str> = Channel::new();
+
+/// Published when a network interface comes up, with the interface name.
+pub static NETWORK_UP: Channel<&'static str> = Channel::new();
+
+/// Published when memory pressure is raised.
+pub static LOW_MEMORY: Channel<Pressure> = Channel::new();
+
+/// Published when a temperature sensor reaches the warning threshold.
+pub static THERMAL_WARNING: Channel<Reading> = Channel::new();
+
+/// Published when an unbound function key is pressed.
+pub static HOTKEY: Channel<KeyCode> = Channel::new();
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A publish/subscribe channel for events of type `T`.
+/// 
+/// Every subscriber receives its own copy of each event published after it 
+/// subscribed. Publishing takes a lock, so must not be done from interrupt 
+/// handlers.
+pub struct Channel<T> {
+    subscribers: Mutex<Vec<Weak<SubscriberInner<T>>>>
+}
+
+/// The state shared between a channel and one of its subscribers.
+struct SubscriberInner<T> {
+    queue: ArrayQueue<T>,
+    waker: AtomicWaker,
+    dropped: AtomicUsize
+}
+
+/// A subscription to a channel, which is a stream of its events.
+/// 
+/// The subscription ends when this is dropped.
+pub struct Subscriber<T> {
+    inner: Arc<SubscriberInner<T>>
+}
+
+impl<T: Clone> Channel<T> {
+
+    /// Create a new channel with no subscribers.
+    pub const fn new() -> Channel<T> {
+        Channel {
+            subscribers: Mutex::new(Vec::new())
+        }
+    }
+
+    /// Subscribe to the channel.
+    pub fn subscribe(&self) -> Subscriber<T> {
+        let inner = Arc::new(SubscriberInner {
+            queue: ArrayQueue::new(SUBSCRIBER_QUEUE_SIZE),
+            waker: AtomicWaker::new(),
+            dropped: AtomicUsize::new(0)
+        });
+
+        self.subscribers.lock().push(Arc::downgrade(&inner));
+        Subscriber { inner }
+    }
+
+    /// Publish an event to every subscriber.
+    /// 
+    /// If a subscriber's queue is full the event is dropped for that 
+    /// subscriber only. Returns the number of subscribers the event was 
+    /// delivered to.
+    pub fn publish(&self, event: T) -> usize {
+        let mut subscribers = self.subscribers.lock();
+        let mut delivered = 0;
+
+        // Deliver to live subscribers, forgetting any which have been dropped
+        subscribers.retain(|weak| match weak.upgrade() {
+            Some(sub) => {
+                if sub.queue.push(event.clone()).is_ok() {
+                    sub.waker.wake();
+                    delivered += 1;
+                }
+                else {
+                    sub.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                true
+            },
+            None => false
+        });
+
+        delivered
+    }
+}
+
+impl<T> Subscriber<T> {
+
+    /// The number of events dropped because this subscriber's queue was full.
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Stream for Subscriber<T> {
+    type Item = T;
+
+    /// Get the next event. The stream never ends.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        if let Ok(event) = self.inner.queue.pop() {
+            return Poll::Ready(Some(event));
+        }
+
+        self.inner.waker.register(&cx.waker());
+
+        match self.inner.queue.pop() {
+            Ok(event) => {
+                self.inner.waker.take();
+                Poll::Ready(Some(event))
+            },
+            Err(crossbeam_queue::PopError) => Poll::Pending
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_publish_subscribe() {
+    use futures_util::task::noop_waker_ref;
+    use crate::{serial_print, serial_println};
+    serial_print!("event::publish_subscribe ");
+
+    let channel: Channel<u32> = Channel::new();
+    let mut sub = channel.subscribe();
+    assert_eq!(channel.publish(7), 1);
+
+    let mut cx = Context::from_waker(noop_waker_ref());
+    assert_eq!(Pin::new(&mut sub).poll_next(&mut cx), Poll::Ready(Some(7)));
+    assert_eq!(Pin::new(&mut sub).poll_next(&mut cx), Poll::Pending);
+
+    // Dropped subscribers no longer receive events
+    drop(sub);
+    assert_eq!(channel.publish(8), 0);
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,104 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::{boxed::Box, collections::BTreeMap};
+use super::{BlockDevice, BlockDeviceError};
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A copy-on-write overlay over a base `BlockDevice`.
+/// 
+/// Writes go to an in-memory delta and the base is only ever read, so it can
+/// be read-only. Resetting the overlay discards the delta, returning the 
+/// device to the base image, so destructive tests can be run repeatedly 
+/// against the same image.
+pub struct CowDevice<D: BlockDevice> {
+    base: D,
+    delta: BTreeMap<u64, Box<[u8]>>
+}
+
+impl<D: BlockDevice> CowDevice<D> {
+
+    /// Create a new overlay with no changes from `base`.
+    pub fn new(base: D) -> CowDevice<D> {
+        CowDevice {
+            base,
+            delta: BTreeMap::new()
+        }
+    }
+
+    /// Discard every write, returning to the contents of the base.
+    pub fn reset(&mut self) {
+        self.delta.clear();
+    }
+
+    /// The number of blocks which differ from the base.
+    pub fn dirty_blocks(&self) -> usize {
+        self.delta.len()
+    }
+
+    /// Get the base device.
+    pub fn base(&self) -> &D {
+        &self.base
+    }
+
+    /// Discard the overlay, returning the unmodified base device.
+    pub fn into_base(self) -> D {
+        self.base
+    }
+
+    /// Check a block index and buffer length are valid for the device.
+    fn check(&self, index: u64, buf_len: usize) 
+        -> Result<(), BlockDeviceError> {
+
+        if buf_len != self.block_size() {
+            Err(BlockDeviceError::BadBufferSize)
+        }
+        else if index >= self.block_count() {
+            Err(BlockDeviceError::OutOfRange)
+        }
+        else {
+            Ok(())
+        }
+    }
+}
+
+impl<D: BlockDevice> BlockDevice for CowDevice<D> {
+
+    fn block_size(&self) -> usize {
+        self.base.block_size()
+    }
+
+    fn block_count(&self) -> u64 {
+        self.base.block_count()
+    }
+
+    fn read_block(&self, index: u64, buf: &mut [u8]) 
+        -> Result<(), BlockDeviceError> {
+
+        self.check(index, buf.len())?;
+        match self.delta.get(&index) {
+            Some(block) => {
+                buf.copy_from_slice(block);
+                Ok(())
+            },
+            None => self.base.read_block(index, buf)
+        }
+    }
+
+    fn write_block(&mut self, index: u64, buf: &[u8]) 
+        -> Result<(), BlockDeviceError> {
+
+        self.check(index, buf.len())?;
+        match self.delta.get_mut(&index) {
+            Some(block) => block.copy_from_slice(buf),
+            None => {
+                self.delta.insert(index, buf.into());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::vec::Vec;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The direction of a block request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Read,
+    Write
+}
+
+/// The blocks touched by a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    pub op: Op,
+
+    /// The first block.
+    pub start: u64,
+
+    /// The number of blocks.
+    pub count: u64
+}
+
+impl Extent {
+
+    /// The block after the last block of the extent.
+    pub fn end(&self) -> u64 {
+        self.start + self.count
+    }
+
+    /// Returns `true` if the two extents share any blocks.
+    pub fn overlaps(&self, other: &Extent) -> bool {
+        self.start < other.end() && other.start < self.end()
+    }
+
+    /// Returns `true` if the order of the two requests matters, because they
+    /// overlap and at least one is a write.
+    pub fn conflicts(&self, other: &Extent) -> bool {
+        (self.op == Op::Write || other.op == Op::Write) && self.overlaps(other)
+    }
+
+    /// Returns `true` if `next` carries on directly from this extent in the 
+    /// same direction, so the two can be issued as one.
+    pub fn merges_with(&self, next: &Extent) -> bool {
+        self.op == next.op && self.end() == next.start
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// The number of requests, from the front of `extents` in submission order, 
+/// which can be reordered among themselves.
+/// 
+/// The batch ends before the first request which conflicts with an earlier 
+/// one, so reads never see writes submitted after them and writes land in 
+/// submission order.
+pub fn batch_len(extents: &[Extent]) -> usize {
+    for (i, extent) in extents.iter().enumerate() {
+        if extents[..i].iter().any(|earlier| earlier.conflicts(extent)) {
+            return i;
+        }
+    }
+    extents.len()
+}
+
+/// Get the order to issue a batch of requests in, as indices into `batch`.
+/// 
+/// This is a circular scan: requests at or after `head` (where the previous 
+/// batch finished) go first in ascending order, then the scan wraps to the 
+/// lowest block. Requests for the same block keep their submission order.
+pub fn order(batch: &[Extent], head: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..batch.len()).collect();
+    order.sort_by_key(|&i| (batch[i].start < head, batch[i].start));
+    order
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    fn read(start: u64, count: u64) -> Extent {
+        Extent { op: Op::Read, start, count }
+    }
+
+    fn write(start: u64, count: u64) -> Extent {
+        Extent { op: Op::Write, start, count }
+    }
+
+    #[test]
+    fn batches_end_at_conflicts() {
+        // Overlapping reads can be reordered
+        assert_eq!(batch_len(&[read(0, 4), read(2, 4), write(8, 1)]), 3);
+
+        // A read after an overlapping write can't
+        assert_eq!(batch_len(&[write(0, 4), read(6, 1), read(3, 1)]), 2);
+
+        // Nor can a write after an overlapping read
+        assert_eq!(batch_len(&[read(5, 1), write(5, 1)]), 1);
+        assert_eq!(batch_len(&[]), 0);
+    }
+
+    #[test]
+    fn order_scans_from_head() {
+        let batch = [read(50, 1), read(10, 1), read(70, 1), read(30, 1)];
+        assert_eq!(order(&batch, 25), [3, 0, 2, 1]);
+        assert_eq!(order(&batch, 0), [1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn contiguous_extents_merge() {
+        assert!(read(0, 4).merges_with(&read(4, 2)));
+        assert!(!read(0, 4).merges_with(&write(4, 2)));
+        assert!(!read(0, 4).merges_with(&read(5, 2)));
+    }
+}
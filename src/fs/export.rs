@@ -0,0 +1,36 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use crate::serial;
+use crate::crashdump;
+use super::tar::TarWriter;
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Stream the given `(name, data)` files over serial port 1 as a tar archive.
+/// 
+/// On the host the archive can be cut out of the captured serial output, it
+/// starts at the first header and ends with 1024 zero bytes.
+pub fn export_over_serial(files: &[(&str, &[u8])]) {
+    let mut tar = TarWriter::new(serial::send_bytes);
+    for (name, data) in files {
+        tar.append(name, data);
+    }
+    tar.finish();
+}
+
+/// Export the crash dump from the previous boot over serial, if there is one.
+/// 
+/// Returns `false` if there was no dump to export.
+pub fn export_crash_dump() -> bool {
+    match crashdump::previous() {
+        Some(text) => {
+            export_over_serial(&[("crashdump.txt", text.as_bytes())]);
+            true
+        },
+        None => false
+    }
+}
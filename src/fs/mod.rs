@@ -0,0 +1,54 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod testing;
+pub mod export;
+pub mod tar;
+pub mod elevator;
+pub mod queue;
+pub mod cow;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Errors returned by a `BlockDevice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockDeviceError {
+    /// The block index is past the end of the device.
+    OutOfRange,
+
+    /// The buffer is not exactly one block long.
+    BadBufferSize,
+
+    /// The device does not support writing.
+    ReadOnly
+}
+
+// ---------------------------------------------------------------------------
+// TRAITS
+// ---------------------------------------------------------------------------
+
+/// A device storing data in fixed size blocks, such as a disk.
+pub trait BlockDevice {
+
+    /// The size of each block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// The number of blocks on the device.
+    fn block_count(&self) -> u64;
+
+    /// Read the block at `index` into `buf`, which must be one block long.
+    fn read_block(&self, index: u64, buf: &mut [u8]) 
+        -> Result<(), BlockDeviceError>;
+
+    /// Write `buf`, which must be one block long, to the block at `index`.
+    fn write_block(&mut self, index: u64, buf: &[u8]) 
+        -> Result<(), BlockDeviceError>;
+
+    /// The total size of the device in bytes.
+    fn size(&self) -> u64 {
+        self.block_count() * self.block_size() as u64
+    }
+}
@@ -0,0 +1,275 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::{collections::VecDeque, sync::Arc, vec, vec::Vec};
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_util::task::AtomicWaker;
+use spin::Mutex;
+use crate::interrupts;
+use crate::sync::WaitQueue;
+use super::{BlockDevice, BlockDeviceError};
+use super::elevator::{self, Extent, Op};
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The result of a block request: the data read, or nothing for a write.
+pub type RequestResult = Result<Vec<u8>, BlockDeviceError>;
+
+/// A queue of requests for a `BlockDevice`, giving it an async interface.
+/// 
+/// Requests are collected until `dispatch` is called (by the `worker` task, 
+/// or directly) then issued in batches. Within a batch requests are sorted 
+/// into a circular scan of the device, and contiguous requests are issued 
+/// together. Requests which overlap a write are never reordered around it.
+pub struct RequestQueue<D: BlockDevice> {
+    name: &'static str,
+    inner: Mutex<Inner<D>>,
+    ready: WaitQueue
+}
+
+/// Statistics of the requests completed by a queue.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueueStats {
+    /// The number of requests completed.
+    pub completed: u64,
+
+    /// The number of requests issued together with the one before them.
+    pub merged: u64,
+
+    /// The total and worst time from submission to completion, in timer 
+    /// ticks.
+    pub total_latency_ticks: u64,
+    pub max_latency_ticks: u64
+}
+
+impl QueueStats {
+
+    /// The mean time from submission to completion, in timer ticks.
+    pub fn mean_latency_ticks(&self) -> u64 {
+        self.total_latency_ticks / self.completed.max(1)
+    }
+}
+
+impl fmt::Display for QueueStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} requests ({} merged), latency mean {} max {} ticks", 
+            self.completed, self.merged, self.mean_latency_ticks(), 
+            self.max_latency_ticks)
+    }
+}
+
+/// Future resolving to the result of a request.
+pub struct RequestFuture {
+    completion: Arc<Completion>
+}
+
+impl Future for RequestFuture {
+    type Output = RequestResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<RequestResult> {
+        if let Some(result) = self.completion.result.lock().take() {
+            return Poll::Ready(result);
+        }
+
+        self.completion.waker.register(cx.waker());
+
+        match self.completion.result.lock().take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending
+        }
+    }
+}
+
+/// The queue state shared with the dispatcher.
+struct Inner<D> {
+    device: D,
+    pending: VecDeque<Pending>,
+    head: u64,
+    stats: QueueStats
+}
+
+/// A submitted request.
+struct Pending {
+    extent: Extent,
+    data: Vec<u8>,
+    submitted: u64,
+    completion: Arc<Completion>
+}
+
+/// Where the result of a request is left for its future.
+struct Completion {
+    result: Mutex<Option<RequestResult>>,
+    waker: AtomicWaker
+}
+
+impl Completion {
+
+    /// Store the result and wake the future.
+    fn complete(&self, result: RequestResult) {
+        *self.result.lock() = Some(result);
+        self.waker.wake();
+    }
+}
+
+impl<D: BlockDevice> RequestQueue<D> {
+
+    /// Create a new queue for `device`.
+    pub fn new(name: &'static str, device: D) -> RequestQueue<D> {
+        RequestQueue {
+            name,
+            inner: Mutex::new(Inner {
+                device,
+                pending: VecDeque::new(),
+                head: 0,
+                stats: QueueStats::default()
+            }),
+            ready: WaitQueue::new()
+        }
+    }
+
+    /// The name of the queue.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Queue a read of `count` blocks from `start`.
+    pub fn read(&self, start: u64, count: u64) -> RequestFuture {
+        let extent = Extent { op: Op::Read, start, count };
+        self.submit(extent, Vec::new())
+    }
+
+    /// Queue a write of `data`, which must be a whole number of blocks, from 
+    /// `start`.
+    pub fn write(&self, start: u64, data: Vec<u8>) -> RequestFuture {
+        let block_size = self.inner.lock().device.block_size();
+        if data.len() % block_size != 0 {
+            return completed(Err(BlockDeviceError::BadBufferSize));
+        }
+
+        let count = (data.len() / block_size) as u64;
+        self.submit(Extent { op: Op::Write, start, count }, data)
+    }
+
+    /// Issue every queued request, returning the number completed.
+    pub fn dispatch(&self) -> usize {
+        let mut inner = self.inner.lock();
+        let mut completed = 0;
+
+        while !inner.pending.is_empty() {
+            let extents: Vec<Extent> = inner.pending.iter()
+                .map(|p| p.extent)
+                .collect();
+            let batch: Vec<Pending> = inner.pending
+                .drain(..elevator::batch_len(&extents))
+                .collect();
+            let order = elevator::order(&extents[..batch.len()], inner.head);
+
+            let mut previous: Option<Extent> = None;
+            for &i in order.iter() {
+                let request = &batch[i];
+                if previous.map_or(false, |p| p.merges_with(&request.extent)) {
+                    inner.stats.merged += 1;
+                }
+                previous = Some(request.extent);
+
+                let result = inner.issue(request);
+                inner.head = request.extent.end();
+
+                let latency = interrupts::ticks() - request.submitted;
+                inner.stats.completed += 1;
+                inner.stats.total_latency_ticks += latency;
+                inner.stats.max_latency_ticks = 
+                    inner.stats.max_latency_ticks.max(latency);
+
+                request.completion.complete(result);
+                completed += 1;
+            }
+        }
+
+        completed
+    }
+
+    /// Get the statistics of the requests completed so far.
+    pub fn stats(&self) -> QueueStats {
+        self.inner.lock().stats
+    }
+
+    /// Run `f` with the device, for example to inspect it.
+    pub fn with_device<R>(&self, f: impl FnOnce(&mut D) -> R) -> R {
+        f(&mut self.inner.lock().device)
+    }
+
+    /// Task which dispatches requests as they are queued.
+    pub async fn worker(&self) {
+        loop {
+            self.ready.wait_until(|| !self.inner.lock().pending.is_empty())
+                .await;
+            self.dispatch();
+        }
+    }
+
+    /// Add a request to the queue.
+    fn submit(&self, extent: Extent, data: Vec<u8>) -> RequestFuture {
+        let completion = Arc::new(Completion {
+            result: Mutex::new(None),
+            waker: AtomicWaker::new()
+        });
+
+        self.inner.lock().pending.push_back(Pending {
+            extent,
+            data,
+            submitted: interrupts::ticks(),
+            completion: completion.clone()
+        });
+        self.ready.wake_all();
+
+        RequestFuture { completion }
+    }
+}
+
+impl<D: BlockDevice> Inner<D> {
+
+    /// Perform a request on the device, one block at a time.
+    fn issue(&mut self, request: &Pending) -> RequestResult {
+        let block_size = self.device.block_size();
+        let blocks = request.extent.start..request.extent.end();
+        let len = block_size * request.extent.count as usize;
+
+        match request.extent.op {
+            Op::Read => {
+                let mut data = vec![0; len];
+                for (block, buf) in blocks.zip(data.chunks_mut(block_size)) {
+                    self.device.read_block(block, buf)?;
+                }
+                Ok(data)
+            },
+            Op::Write => {
+                let chunks = request.data.chunks(block_size);
+                for (block, buf) in blocks.zip(chunks) {
+                    self.device.write_block(block, buf)?;
+                }
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get a future which has already completed with `result`.
+fn completed(result: RequestResult) -> RequestFuture {
+    RequestFuture {
+        completion: Arc::new(Completion {
+            result: Mutex::new(Some(result)),
+            waker: AtomicWaker::new()
+        })
+    }
+}
@@ -0,0 +1,155 @@
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The size of tar headers and the unit file data is padded to.
+pub const TAR_BLOCK_SIZE: usize = 512;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Writer producing a ustar archive, passing each 512 byte block to a sink.
+pub struct TarWriter<F: FnMut(&[u8])> {
+    sink: F
+}
+
+impl<F: FnMut(&[u8])> TarWriter<F> {
+
+    /// Create a new writer sending the archive to `sink`.
+    pub fn new(sink: F) -> TarWriter<F> {
+        TarWriter { sink }
+    }
+
+    /// Append a regular file to the archive.
+    /// 
+    /// Names longer than 100 bytes are truncated.
+    pub fn append(&mut self, name: &str, data: &[u8]) {
+        (self.sink)(&header(name, data.len()));
+
+        // Write the data in whole blocks, zero padding the last one
+        for chunk in data.chunks(TAR_BLOCK_SIZE) {
+            if chunk.len() == TAR_BLOCK_SIZE {
+                (self.sink)(chunk);
+            }
+            else {
+                let mut block = [0u8; TAR_BLOCK_SIZE];
+                block[..chunk.len()].copy_from_slice(chunk);
+                (self.sink)(&block);
+            }
+        }
+    }
+
+    /// Finish the archive by writing the two empty end blocks.
+    pub fn finish(mut self) {
+        let block = [0u8; TAR_BLOCK_SIZE];
+        (self.sink)(&block);
+        (self.sink)(&block);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Build the ustar header block for a regular file.
+pub fn header(name: &str, size: usize) -> [u8; TAR_BLOCK_SIZE] {
+    let mut header = [0u8; TAR_BLOCK_SIZE];
+
+    let name = name.as_bytes();
+    let name_len = name.len().min(100);
+    header[..name_len].copy_from_slice(&name[..name_len]);
+
+    write_octal(&mut header[100..108], 0o644);   // mode
+    write_octal(&mut header[108..116], 0);       // uid
+    write_octal(&mut header[116..124], 0);       // gid
+    write_octal(&mut header[124..136], size as u64);
+    write_octal(&mut header[136..148], 0);       // mtime
+    header[156] = b'0';                          // regular file
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is calculated with the checksum field set to spaces, and
+    // is written as 6 octal digits, a NUL and a space.
+    header[148..156].copy_from_slice(b"        ");
+    let sum: u64 = header.iter().map(|&b| b as u64).sum();
+    write_octal(&mut header[148..155], sum);
+    header[155] = b' ';
+
+    header
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Write `value` into `field` as zero padded octal followed by a NUL.
+fn write_octal(field: &mut [u8], mut value: u64) {
+    let digits = field.len() - 1;
+    for i in (0..digits).rev() {
+        field[i] = b'0' + (value & 0o7) as u8;
+        value >>= 3;
+    }
+    field[digits] = 0;
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn header_matches_ustar() {
+        // The header Python's tarfile writes for the same file in 
+        // USTAR_FORMAT, with empty user and group names
+        let mut expected = [0u8; TAR_BLOCK_SIZE];
+        for &(offset, bytes) in [
+            (0, &b"crashdump.txt"[..]),
+            (100, b"0000644"),
+            (108, b"0000000"),
+            (116, b"0000000"),
+            (124, b"00000000015"),
+            (136, b"00000000000"),
+            (148, b"010470\0 0"),
+            (257, b"ustar\x0000")
+        ].iter() {
+            expected[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        assert_eq!(&header("crashdump.txt", 13)[..], &expected[..]);
+    }
+
+    #[test]
+    fn long_names_truncated() {
+        let name = "a".repeat(120);
+        let header = header(&name, 0);
+        assert_eq!(&header[..100], &name.as_bytes()[..100]);
+        assert_eq!(&header[100..108], b"0000644\0");
+    }
+
+    #[test]
+    fn archive_padding() {
+        let mut blocks = Vec::new();
+        let mut tar = TarWriter::new(|block: &[u8]| {
+            assert_eq!(block.len(), TAR_BLOCK_SIZE);
+            blocks.push(block.to_vec());
+        });
+
+        let data = [0xaau8; TAR_BLOCK_SIZE + 1];
+        tar.append("a", &data);
+        tar.append("empty", &[]);
+        tar.finish();
+
+        // A header and two data blocks, a header alone, and the end blocks
+        assert_eq!(blocks.len(), 6);
+        assert_eq!(&blocks[1][..], &data[..TAR_BLOCK_SIZE]);
+        assert_eq!(blocks[2][0], 0xaa);
+        assert!(blocks[2][1..].iter().all(|&b| b == 0));
+        assert_eq!(&blocks[3][..5], b"empty");
+        assert!(blocks[4..].iter().all(|b| b.iter().all(|&b| b == 0)));
+    }
+}
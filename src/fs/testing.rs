@@ -0,0 +1,97 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::{vec, vec::Vec};
+use super::{BlockDevice, BlockDeviceError};
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A RAM-backed `BlockDevice` for testing filesystem code without a disk.
+/// 
+/// The disk can be pre-populated from an image embedded with 
+/// `include_bytes!`, so tests can run against known filesystem images.
+pub struct RamDisk {
+    block_size: usize,
+    data: Vec<u8>,
+    read_only: bool
+}
+
+impl RamDisk {
+
+    /// Create a new zeroed disk with the given geometry.
+    pub fn new(block_size: usize, block_count: usize) -> RamDisk {
+        RamDisk {
+            block_size,
+            data: vec![0; block_size * block_count],
+            read_only: false
+        }
+    }
+
+    /// Create a new disk holding a copy of `image`.
+    /// 
+    /// The image is padded with zeroes up to a whole number of blocks.
+    pub fn from_image(block_size: usize, image: &[u8]) -> RamDisk {
+        let block_count = (image.len() + block_size - 1) / block_size;
+        let mut disk = RamDisk::new(block_size, block_count);
+        disk.data[..image.len()].copy_from_slice(image);
+        disk
+    }
+
+    /// Make the disk reject all writes.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Get the raw contents of the disk.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get the byte range of the given block, checking the arguments.
+    fn block_range(&self, index: u64, buf_len: usize) 
+        -> Result<core::ops::Range<usize>, BlockDeviceError> {
+
+        if buf_len != self.block_size {
+            return Err(BlockDeviceError::BadBufferSize);
+        }
+        if index >= self.block_count() {
+            return Err(BlockDeviceError::OutOfRange);
+        }
+
+        let start = index as usize * self.block_size;
+        Ok(start..start + self.block_size)
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        (self.data.len() / self.block_size) as u64
+    }
+
+    fn read_block(&self, index: u64, buf: &mut [u8]) 
+        -> Result<(), BlockDeviceError> {
+
+        let range = self.block_range(index, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn write_block(&mut self, index: u64, buf: &[u8]) 
+        -> Result<(), BlockDeviceError> {
+
+        if self.read_only {
+            return Err(BlockDeviceError::ReadOnly);
+        }
+
+        let range = self.block_range(index, buf.len())?;
+        self.data[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
@@ -2,12 +2,20 @@
 // USE STATEMENTS
 // ---------------------------------------------------------------------------
 
+use core::cell::UnsafeCell;
+use core::fmt;
 use x86_64::{VirtAddr, structures::tss::TaskStateSegment};
 use x86_64::structures::gdt::{
     GlobalDescriptorTable, Descriptor, SegmentSelector
 };
+use x86_64::structures::paging::{
+    Mapper, Page, PageTableFlags, Size4KiB, FrameAllocator, 
+    mapper::MapToError
+};
 use x86_64::instructions::{segmentation::set_cs, tables::load_tss};
-use lazy_static::lazy_static;
+use conquer_once::spin::OnceCell;
+use crate::memory::layout::IST_STACKS;
+use crate::stack::StackBounds;
 
 // ---------------------------------------------------------------------------
 // STATIC INITIALISATIONS
@@ -16,47 +24,52 @@ use lazy_static::lazy_static;
 /// The index of the double fault CPU exception in the Interrupt Stack Table.
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
-lazy_static! {
-    /// Task State Segment static reference.
-    /// 
-    /// This is initialised using `lazy_static` so that we get advanced static
-    /// init capabilities.
-    static ref TSS: TaskStateSegment = {
-        let mut tss = TaskStateSegment::new();
+/// The index of the non-maskable interrupt in the Interrupt Stack Table.
+pub const NMI_IST_INDEX: u16 = 1;
 
-        // TODO: Use proper stack initialisation once memory management is 
-        // added.
-        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
-            const STACK_SIZE: usize = 4096;
-            static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+/// The index of the machine check CPU exception in the Interrupt Stack Table.
+pub const MACHINE_CHECK_IST_INDEX: u16 = 2;
 
-            // NOTE: USE OF UNSAFE
-            //  The reference to a mutable static here is unsafe because the
-            //  compiler can't guarentee race condition safety with mutable
-            //  statics. This will be removed when the above TODO is solved.
-            let stack_start = VirtAddr::from_ptr(unsafe { &STACK });
-            let stack_end = stack_start + STACK_SIZE;
-            stack_end
-        };
-
-        tss
-    };
-}
+/// The index of the page fault CPU exception in the Interrupt Stack Table, 
+/// used only with the `page-fault-ist` feature.
+pub const PAGE_FAULT_IST_INDEX: u16 = 3;
 
-lazy_static! {
-    /// Global descriptor table.
-    /// 
-    /// This is initialised using `lazy_static` so that we get advanced static
-    /// init capabilities.
-    static ref GDT: (GlobalDescriptorTable, Selectors) = {
-        let mut gdt = GlobalDescriptorTable::new();
+/// Whether page faults run on their own stack.
+pub const PAGE_FAULT_IST: bool = cfg!(feature = "page-fault-ist");
 
-        let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
-        let tss_selector = gdt.add_entry(Descriptor::tss_segment(&TSS));
+/// The maximum number of CPUs which can have tables.
+pub const MAX_CPUS: usize = 16;
 
-        (gdt, Selectors { code_selector, tss_selector })
-    };
-}
+/// The CPU which runs `init`.
+pub const BOOT_CPU: usize = 0;
+
+/// The number of Interrupt Stack Table entries in use.
+const IST_COUNT: usize = 4;
+
+/// The number of stacks allocated for each CPU: the IST stacks and the ring 0
+/// stack.
+const STACKS_PER_CPU: usize = IST_COUNT + 1;
+
+/// The size of the static stacks used until `init_stacks` is called.
+const BOOT_STACK_SIZE: usize = 4096;
+
+/// The number of pages in each allocated stack.
+const STACK_PAGES: u64 = 5;
+
+/// The size of a page.
+const PAGE_SIZE: u64 = 4096;
+
+/// Stacks for the boot CPU's Interrupt Stack Table during early boot, before 
+/// there is a frame allocator to build the real ones with.
+static mut BOOT_STACKS: [[u8; BOOT_STACK_SIZE]; IST_COUNT] = 
+    [[0; BOOT_STACK_SIZE]; IST_COUNT];
+
+/// The TSS of each CPU.
+static TSS: [OnceCell<TssCell>; MAX_CPUS] = [OnceCell::uninit(); MAX_CPUS];
+
+/// The GDT of each CPU, which references that CPU's TSS.
+static GDT: [OnceCell<(GlobalDescriptorTable, Selectors)>; MAX_CPUS] = 
+    [OnceCell::uninit(); MAX_CPUS];
 
 // ---------------------------------------------------------------------------
 // STRUCTURE DEFINITIONS
@@ -67,15 +80,192 @@ struct Selectors {
     tss_selector: SegmentSelector
 }
 
+/// A TSS, which is read by the CPU on each interrupt and so can have its 
+/// stacks replaced after it is loaded.
+struct TssCell(UnsafeCell<TaskStateSegment>);
+
+// NOTE: USE OF UNSAFE
+//  Each TSS is only written by `init_stacks` with interrupts disabled, which 
+//  is called once during init.
+unsafe impl Sync for TssCell {}
+
+/// Errors which can occur when initialising a CPU's tables.
+#[derive(Debug)]
+pub enum GdtError {
+    /// The CPU number is `MAX_CPUS` or above.
+    InvalidCpu(usize),
+
+    /// The CPU's tables have already been initialised.
+    AlreadyInitialised(usize),
+
+    /// The CPU's stacks couldn't be mapped.
+    Map(MapToError<Size4KiB>)
+}
+
+impl fmt::Display for GdtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GdtError::InvalidCpu(cpu) => write!(f, "invalid CPU {}", cpu),
+            GdtError::AlreadyInitialised(cpu) => 
+                write!(f, "CPU {} already initialised", cpu),
+            GdtError::Map(e) => write!(f, "cannot map stacks: {:?}", e)
+        }
+    }
+}
+
+impl From<MapToError<Size4KiB>> for GdtError {
+    fn from(e: MapToError<Size4KiB>) -> Self {
+        GdtError::Map(e)
+    }
+}
+
 // ---------------------------------------------------------------------------
 // PUBLIC FUNCTIONS
 // ---------------------------------------------------------------------------
 
-/// Initialise the Global Descriptor Table.
+/// Initialise the Global Descriptor Table of the boot CPU.
+/// 
+/// The boot CPU starts on small static interrupt stacks, `init_stacks` 
+/// replaces them once memory management is up.
 pub fn init() {
+    let mut tss = TaskStateSegment::new();
+
+    // NMIs and machine checks can arrive at any point, including while the 
+    // current stack is broken, so they get stacks of their own as does the 
+    // double fault handler.
+    for (index, stack) in tss.interrupt_stack_table.iter_mut()
+        .take(IST_COUNT).enumerate() {
+
+        // NOTE: USE OF UNSAFE
+        //  The reference to a mutable static here is unsafe because the
+        //  compiler can't guarentee race condition safety with mutable
+        //  statics. Each stack is only referenced here, and is only used by 
+        //  the CPU.
+        let stack_start = VirtAddr::from_ptr(unsafe { &BOOT_STACKS[index] });
+        *stack = stack_start + BOOT_STACK_SIZE;
+    }
+
+    load(BOOT_CPU, tss).expect("gdt::init must only be called once");
+}
+
+/// Replace the boot CPU's Interrupt Stack Table stacks with larger ones 
+/// mapped in the IST stacks region, and give it a ring 0 stack.
+pub fn init_stacks(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>
+) -> Result<(), GdtError> {
+    let tops = map_stacks(BOOT_CPU, mapper, frame_allocator)?;
+    let tss = TSS[BOOT_CPU].try_get()
+        .map_err(|_| GdtError::InvalidCpu(BOOT_CPU))?;
+
+    // NOTE: USE OF UNSAFE
+    //  Interrupts are disabled so no handler is running on the old stacks 
+    //  while the entries are swapped.
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        set_stacks(&mut *tss.0.get(), &tops);
+    });
+
+    Ok(())
+}
+
+/// Build and load the tables of another CPU, with stacks from the memory 
+/// manager.
+/// 
+/// This must be run on the CPU being initialised, as it is brought up.
+pub fn init_cpu(
+    cpu: usize,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>
+) -> Result<(), GdtError> {
+    if cpu >= MAX_CPUS {
+        return Err(GdtError::InvalidCpu(cpu));
+    }
+    if TSS[cpu].is_initialized() {
+        return Err(GdtError::AlreadyInitialised(cpu));
+    }
+
+    let tops = map_stacks(cpu, mapper, frame_allocator)?;
+    let mut tss = TaskStateSegment::new();
+    set_stacks(&mut tss, &tops);
+
+    load(cpu, tss)
+}
+
+/// Get the top of the stack used by the given Interrupt Stack Table entry on
+/// the given CPU, or `None` if the CPU hasn't been initialised.
+pub fn ist_stack_top(cpu: usize, index: u16) -> Option<VirtAddr> {
+    let tss = TSS.get(cpu)?.try_get().ok()?;
+
+    // NOTE: USE OF UNSAFE
+    //  The entries are only written during init.
+    Some(unsafe { (*tss.0.get()).interrupt_stack_table[index as usize] })
+}
+
+/// Find the stack allocated by this module which contains `addr`, if there 
+/// is one.
+pub fn stack_bounds(addr: u64) -> Option<StackBounds> {
+    const NAMES: [&str; STACKS_PER_CPU] = 
+        ["double fault", "NMI", "machine check", "page fault", "ring 0"];
+
+    // The early boot stacks
+    //
+    // NOTE: USE OF UNSAFE
+    //  Only the address of the stacks is taken.
+    let boot_start = unsafe { BOOT_STACKS.as_ptr() } as u64;
+    for (index, name) in NAMES[..IST_COUNT].iter().enumerate() {
+        let bottom = boot_start + (index * BOOT_STACK_SIZE) as u64;
+        let top = bottom + BOOT_STACK_SIZE as u64;
+        if addr >= bottom && addr < top {
+            return Some(StackBounds { name, bottom, top });
+        }
+    }
+
+    // The stacks mapped by `map_stacks`, skipping the guard page of the slot
+    if !IST_STACKS.contains(addr) {
+        return None;
+    }
+    let slot_size = (STACK_PAGES + 1) * PAGE_SIZE;
+    let slot = (addr - IST_STACKS.start) / slot_size;
+    let bottom = IST_STACKS.start + slot * slot_size + PAGE_SIZE;
+    if addr < bottom || slot as usize >= MAX_CPUS * STACKS_PER_CPU {
+        return None;
+    }
+
+    Some(StackBounds { 
+        name: NAMES[slot as usize % STACKS_PER_CPU], 
+        bottom, 
+        top: bottom + STACK_PAGES * PAGE_SIZE 
+    })
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Store the TSS of a CPU, build its GDT, and load both on the current CPU.
+fn load(cpu: usize, tss: TaskStateSegment) -> Result<(), GdtError> {
+    TSS[cpu].try_init_once(|| TssCell(UnsafeCell::new(tss)))
+        .map_err(|_| GdtError::AlreadyInitialised(cpu))?;
+
+    // NOTE: USE OF UNSAFE
+    //  The TSS is only modified by `init_stacks`, which doesn't move it.
+    let tss: &'static TaskStateSegment = unsafe { 
+        &*TSS[cpu].get().expect("TSS was just initialised").0.get() 
+    };
+
+    GDT[cpu].try_init_once(|| {
+        let mut gdt = GlobalDescriptorTable::new();
+
+        let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
+        let tss_selector = gdt.add_entry(Descriptor::tss_segment(tss));
+
+        (gdt, Selectors { code_selector, tss_selector })
+    }).map_err(|_| GdtError::AlreadyInitialised(cpu))?;
+
+    let (gdt, selectors) = GDT[cpu].get().expect("GDT was just initialised");
 
     // Load the GDT
-    GDT.0.load();
+    gdt.load();
     
     // Register the selectors
     //
@@ -83,9 +273,94 @@ pub fn init() {
     //  The `set_cs` and `load_tss` functions are marked as unsafe so the 
     //  unsafe block is required here. This is because loading tables and
     //  setting selectors could cause some trouble if they are not valid. This
-    //  usage here is OK since it's within an init function.
+    //  usage here is OK since it's only called from the init functions.
     unsafe {
-        set_cs(GDT.1.code_selector);
-        load_tss(GDT.1.tss_selector);
+        set_cs(selectors.code_selector);
+        load_tss(selectors.tss_selector);
+    }
+
+    Ok(())
+}
+
+/// Map the stacks of a CPU, returning the top of each.
+/// 
+/// Each stack has an unmapped guard page below it, so an overflowing 
+/// handler faults rather than silently corrupting other memory.
+fn map_stacks(
+    cpu: usize,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>
+) -> Result<[VirtAddr; STACKS_PER_CPU], GdtError> {
+    let mut tops = [VirtAddr::zero(); STACKS_PER_CPU];
+
+    for (index, top) in tops.iter_mut().enumerate() {
+        // Leave the first page of each slot unmapped as the guard page
+        let slot = (cpu * STACKS_PER_CPU + index) as u64;
+        let slot_start = IST_STACKS.start 
+            + slot * (STACK_PAGES + 1) * PAGE_SIZE;
+        let stack_start = VirtAddr::new(slot_start + PAGE_SIZE);
+        let stack_end = stack_start + STACK_PAGES * PAGE_SIZE;
+
+        let page_range = Page::range(
+            Page::containing_address(stack_start), 
+            Page::containing_address(stack_end));
+        for page in page_range {
+            let frame = frame_allocator.allocate_frame()
+                .ok_or(MapToError::FrameAllocationFailed)?;
+            let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE 
+                | PageTableFlags::NO_EXECUTE;
+
+            // NOTE: USE OF UNSAFE
+            //  Each CPU's slots in the IST stacks region are reserved for its
+            //  stacks, so the pages aren't in use.
+            unsafe {
+                mapper.map_to(page, frame, flags, frame_allocator)?.flush();
+            }
+        }
+
+        *top = stack_end;
+    }
+
+    Ok(tops)
+}
+
+/// Point a TSS at stacks returned by `map_stacks`.
+fn set_stacks(tss: &mut TaskStateSegment, tops: &[VirtAddr; STACKS_PER_CPU]) {
+    tss.interrupt_stack_table[..IST_COUNT].copy_from_slice(&tops[..IST_COUNT]);
+    tss.privilege_stack_table[0] = tops[IST_COUNT];
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_ist_stacks_mapped() {
+    use crate::{serial_print, serial_println};
+    serial_print!("gdt::ist_stacks_mapped ");
+
+    for index in 0..IST_COUNT as u16 {
+        let top = ist_stack_top(BOOT_CPU, index).unwrap().as_u64();
+        assert!(IST_STACKS.contains(top - 1));
+        let bounds = stack_bounds(top - 1).unwrap();
+        assert_eq!(bounds.top, top);
+        assert_eq!(bounds.size(), STACK_PAGES * PAGE_SIZE);
+
+        // The top of each stack is mapped, the guard page isn't. Mappings can
+        // only be checked through the direct map.
+        if !crate::memory::DIRECT_MAP {
+            continue;
+        }
+        let guard = top - (STACK_PAGES + 1) * PAGE_SIZE;
+        assert_eq!(crate::memory::audit::is_user_page(
+            VirtAddr::new(top - 8)), Some(false));
+        assert_eq!(crate::memory::audit::is_user_page(
+            VirtAddr::new(guard)), None);
     }
-}
\ No newline at end of file
+
+    // Other CPUs have no tables until they are brought up
+    assert!(ist_stack_top(BOOT_CPU + 1, 0).is_none());
+    assert!(ist_stack_top(MAX_CPUS, 0).is_none());
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,131 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod soft;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::__cpuid;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+pub use soft::{adler32_update, crc32_update, fnv1a_32, fnv1a_64};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The CPUID leaf 1 ECX bit for SSE4.2, which includes the `CRC32` 
+/// instruction.
+const CPUID_SSE4_2: u32 = 1 << 20;
+
+/// Whether the CPU has the `CRC32` instruction, checked on first use.
+static HW_CRC32C: AtomicU8 = AtomicU8::new(HW_UNKNOWN);
+
+const HW_UNKNOWN: u8 = 0;
+const HW_ABSENT: u8 = 1;
+const HW_PRESENT: u8 = 2;
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// The CRC-32 of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// The CRC-32C of `data`.
+pub fn crc32c(data: &[u8]) -> u32 {
+    crc32c_update(0, data)
+}
+
+/// Continue a CRC-32C from `crc`, the CRC of the data before `data`.
+/// 
+/// This uses the SSE4.2 `CRC32` instruction when the CPU has it, and a table
+/// otherwise.
+pub fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    if has_hw_crc32c() {
+        // NOTE: USE OF UNSAFE
+        //  The CPU has just been checked for SSE4.2.
+        unsafe { hw_crc32c_update(crc, data) }
+    }
+    else {
+        soft::crc32c_update(crc, data)
+    }
+}
+
+/// The Adler-32 checksum of `data`.
+pub fn adler32(data: &[u8]) -> u32 {
+    adler32_update(1, data)
+}
+
+/// Returns `true` if CRC-32C is computed in hardware.
+pub fn has_hw_crc32c() -> bool {
+    match HW_CRC32C.load(Ordering::Relaxed) {
+        HW_UNKNOWN => {
+            // NOTE: USE OF UNSAFE
+            //  CPUID is always available in long mode.
+            let present = unsafe { __cpuid(1).ecx } & CPUID_SSE4_2 != 0;
+            HW_CRC32C.store(if present { HW_PRESENT } else { HW_ABSENT }, 
+                Ordering::Relaxed);
+            present
+        },
+        state => state == HW_PRESENT
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Continue a CRC-32C with the `CRC32` instruction, 8 bytes at a time.
+/// 
+/// The instruction is used through inline assembly on general purpose 
+/// registers, so the kernel isn't built to use any SSE registers.
+/// 
+/// NOTE: UNSAFE
+///     The caller must guarentee the CPU supports SSE4.2.
+unsafe fn hw_crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = u64::from(!crc);
+
+    let mut words = data.chunks_exact(8);
+    for word in &mut words {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(word);
+        let word = u64::from_le_bytes(bytes);
+        llvm_asm!("crc32q $1, $0" : "+r"(crc) : "r"(word));
+    }
+    let mut crc = crc as u32;
+    for &byte in words.remainder() {
+        llvm_asm!("crc32b $1, $0" : "+r"(crc) : "r"(byte));
+    }
+
+    !crc
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_crc32c_matches_soft() {
+    use crate::{serial_print, serial_println};
+    serial_print!("hash::crc32c_matches_soft ");
+
+    let mut data = [0u8; 1027];
+    crate::rng::fill_bytes(&mut data);
+
+    // Every alignment and length of the tail
+    for start in 0..8 {
+        for end in (data.len() - 8)..data.len() {
+            let slice = &data[start..end];
+            assert_eq!(crc32c(slice), soft::crc32c_update(0, slice));
+        }
+    }
+    assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+
+    serial_println!("[ok]");
+}
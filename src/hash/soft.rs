@@ -0,0 +1,140 @@
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// CRC table for each 4 bit nibble, for the reflected CRC-32 (IEEE 802.3) 
+/// polynomial 0xedb88320.
+const CRC32_NIBBLE_TABLE: [u32; 16] = [
+    0x00000000, 0x1db71064, 0x3b6e20c8, 0x26d930ac, 
+    0x76dc4190, 0x6b6b51f4, 0x4db26158, 0x5005713c, 
+    0xedb88320, 0xf00f9344, 0xd6d6a3e8, 0xcb61b38c, 
+    0x9b64c2b0, 0x86d3d2d4, 0xa00ae278, 0xbdbdf21c
+];
+
+/// CRC table for each 4 bit nibble, for the reflected CRC-32C (Castagnoli) 
+/// polynomial 0x82f63b78.
+const CRC32C_NIBBLE_TABLE: [u32; 16] = [
+    0x00000000, 0x105ec76f, 0x20bd8ede, 0x30e349b1, 
+    0x417b1dbc, 0x5125dad3, 0x61c69362, 0x7198540d, 
+    0x82f63b78, 0x92a8fc17, 0xa24bb5a6, 0xb21572c9, 
+    0xc38d26c4, 0xd3d3e1ab, 0xe330a81a, 0xf36e6f75
+];
+
+/// The Adler-32 modulus, the largest prime below 2^16.
+const ADLER_MOD: u32 = 65521;
+
+/// The most bytes which can be summed before the Adler-32 sums must be 
+/// reduced to avoid overflow.
+const ADLER_NMAX: usize = 5552;
+
+/// FNV-1a parameters.
+const FNV32_OFFSET: u32 = 0x811c_9dc5;
+const FNV32_PRIME: u32 = 0x0100_0193;
+const FNV64_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV64_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Continue a CRC-32 (as used by Ethernet, zip, and PNG) from `crc`, the CRC
+/// of the data before `data`. Start with 0.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    !nibble_crc(!crc, data, &CRC32_NIBBLE_TABLE)
+}
+
+/// Continue a CRC-32C (as used by iSCSI, ext4, and btrfs) from `crc`, the 
+/// CRC of the data before `data`. Start with 0.
+pub fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    !nibble_crc(!crc, data, &CRC32C_NIBBLE_TABLE)
+}
+
+/// Continue an Adler-32 checksum (as used by zlib) from `adler`, the 
+/// checksum of the data before `data`. Start with 1.
+pub fn adler32_update(adler: u32, data: &[u8]) -> u32 {
+    let mut a = adler & 0xffff;
+    let mut b = adler >> 16;
+
+    for chunk in data.chunks(ADLER_NMAX) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= ADLER_MOD;
+        b %= ADLER_MOD;
+    }
+
+    b << 16 | a
+}
+
+/// The 32 bit FNV-1a hash of `data`, a fast non-cryptographic hash for 
+/// tables.
+pub fn fnv1a_32(data: &[u8]) -> u32 {
+    data.iter().fold(FNV32_OFFSET, |hash, &byte| 
+        (hash ^ u32::from(byte)).wrapping_mul(FNV32_PRIME))
+}
+
+/// The 64 bit FNV-1a hash of `data`.
+pub fn fnv1a_64(data: &[u8]) -> u64 {
+    data.iter().fold(FNV64_OFFSET, |hash, &byte| 
+        (hash ^ u64::from(byte)).wrapping_mul(FNV64_PRIME))
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Run a reflected CRC over `data` a nibble at a time, without the initial 
+/// and final inversions.
+fn nibble_crc(mut crc: u32, data: &[u8], table: &[u32; 16]) -> u32 {
+    for &byte in data {
+        crc ^= u32::from(byte);
+        crc = table[(crc & 0xf) as usize] ^ (crc >> 4);
+        crc = table[(crc & 0xf) as usize] ^ (crc >> 4);
+    }
+    crc
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    /// The standard CRC check input.
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn crc_check_values() {
+        assert_eq!(crc32_update(0, CHECK), 0xcbf4_3926);
+        assert_eq!(crc32c_update(0, CHECK), 0xe306_9283);
+        assert_eq!(crc32_update(0, &[]), 0);
+    }
+
+    #[test]
+    fn crc_continues() {
+        let (head, tail) = CHECK.split_at(4);
+        assert_eq!(crc32_update(crc32_update(0, head), tail), 0xcbf4_3926);
+        assert_eq!(crc32c_update(crc32c_update(0, head), tail), 0xe306_9283);
+    }
+
+    #[test]
+    fn adler32_values() {
+        assert_eq!(adler32_update(1, b"Wikipedia"), 0x11e6_0398);
+
+        // Long input exercises the reductions
+        let data = [0xffu8; 20000];
+        let (head, tail) = data.split_at(7000);
+        assert_eq!(adler32_update(adler32_update(1, head), tail), 
+            adler32_update(1, &data));
+    }
+
+    #[test]
+    fn fnv1a_values() {
+        assert_eq!(fnv1a_32(b""), FNV32_OFFSET);
+        assert_eq!(fnv1a_32(b"a"), 0xe40c_292c);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -0,0 +1,221 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::__cpuid;
+use core::fmt;
+use bootloader::bootinfo::MemoryRegionType;
+use conquer_once::spin::OnceCell;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The first and last CPUID leaves holding the processor brand string.
+const CPUID_BRAND_FIRST: u32 = 0x8000_0002;
+const CPUID_BRAND_LAST: u32 = 0x8000_0004;
+
+/// Features reported in the summary, as (name, leaf 1 register, bit). 
+/// Register 2 is ECX and 3 is EDX.
+const LEAF1_FEATURES: [(&str, u8, u32); 10] = [
+    ("sse2", 3, 26),
+    ("sse3", 2, 0),
+    ("ssse3", 2, 9),
+    ("sse4.1", 2, 19),
+    ("sse4.2", 2, 20),
+    ("popcnt", 2, 23),
+    ("aes", 2, 25),
+    ("xsave", 2, 26),
+    ("avx", 2, 28),
+    ("rdrand", 2, 30)
+];
+
+/// The report collected by `collect`.
+static REPORT: OnceCell<HwInfo> = OnceCell::uninit();
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A summary of the hardware the kernel is running on.
+#[derive(Debug, Clone, Copy)]
+pub struct HwInfo {
+    /// The CPU vendor string.
+    pub vendor: [u8; 12],
+
+    /// The CPU brand string, if the CPU reports one.
+    pub brand: Option<[u8; 48]>,
+
+    /// The CPU family, model, and stepping.
+    pub family: u32,
+    pub model: u32,
+    pub stepping: u32,
+
+    /// A bit for each of `LEAF1_FEATURES` the CPU supports.
+    features: u32,
+
+    /// Usable physical memory in bytes.
+    pub usable_memory: u64,
+
+    /// The end of the highest region in the memory map.
+    pub phys_end: u64
+}
+
+impl HwInfo {
+
+    /// The vendor as a string.
+    pub fn vendor_str(&self) -> &str {
+        core::str::from_utf8(&self.vendor).unwrap_or("unknown")
+    }
+
+    /// The brand as a string, without the padding some CPUs add.
+    pub fn brand_str(&self) -> Option<&str> {
+        let brand = self.brand.as_ref()?;
+        let len = brand.iter().position(|&b| b == 0).unwrap_or(brand.len());
+        core::str::from_utf8(&brand[..len]).ok().map(|s| s.trim())
+    }
+
+    /// Returns `true` if the CPU supports the named feature, which must be 
+    /// one reported in the summary.
+    pub fn has_feature(&self, name: &str) -> bool {
+        LEAF1_FEATURES.iter().position(|&(n, _, _)| n == name)
+            .map_or(false, |i| self.features & (1 << i) != 0)
+    }
+}
+
+impl fmt::Display for HwInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Hardware summary:")?;
+        writeln!(f, "    CPU: {} ({})", 
+            self.brand_str().unwrap_or("unknown model"), self.vendor_str())?;
+        writeln!(f, "    family {:#x}, model {:#x}, stepping {}", 
+            self.family, self.model, self.stepping)?;
+        write!(f, "    features:")?;
+        for (i, &(name, _, _)) in LEAF1_FEATURES.iter().enumerate() {
+            if self.features & (1 << i) != 0 {
+                write!(f, " {}", name)?;
+            }
+        }
+        writeln!(f)?;
+        write!(f, "    memory: {} MiB usable, {} MiB addressed", 
+            self.usable_memory >> 20, self.phys_end >> 20)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Collect the hardware summary, which can then be retrieved with `report`.
+/// 
+/// Memory sizes are taken from the memory map recorded by `memory::init`, so 
+/// this must be called after it. There is no PCI, disk, or network support 
+/// yet so those devices aren't reported.
+pub fn collect() -> &'static HwInfo {
+    let mut info = read_cpu();
+
+    if let Some((memory_map, _)) = crate::memory::boot_memory() {
+        for region in memory_map.iter() {
+            if region.region_type == MemoryRegionType::Usable {
+                info.usable_memory += 
+                    region.range.end_addr() - region.range.start_addr();
+            }
+            info.phys_end = info.phys_end.max(region.range.end_addr());
+        }
+    }
+
+    REPORT.try_init_once(|| info)
+        .expect("hwinfo::collect must only be called once");
+    report().expect("report was just collected")
+}
+
+/// Get the hardware summary, or `None` if it hasn't been collected.
+pub fn report() -> Option<&'static HwInfo> {
+    REPORT.try_get().ok()
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read the CPU identification from CPUID.
+fn read_cpu() -> HwInfo {
+    // NOTE: USE OF UNSAFE
+    //  CPUID is always available in long mode, and leaves are only read if 
+    //  the maximum leaf says they exist.
+    unsafe {
+        let leaf0 = __cpuid(0);
+        let mut vendor = [0u8; 12];
+        vendor[0..4].copy_from_slice(&leaf0.ebx.to_le_bytes());
+        vendor[4..8].copy_from_slice(&leaf0.edx.to_le_bytes());
+        vendor[8..12].copy_from_slice(&leaf0.ecx.to_le_bytes());
+
+        // The extended family and model are only used by some base families
+        let leaf1 = __cpuid(1);
+        let base_family = (leaf1.eax >> 8) & 0xf;
+        let mut family = base_family;
+        let mut model = (leaf1.eax >> 4) & 0xf;
+        if base_family == 0xf {
+            family += (leaf1.eax >> 20) & 0xff;
+        }
+        if base_family == 0x6 || base_family == 0xf {
+            model |= ((leaf1.eax >> 16) & 0xf) << 4;
+        }
+
+        let mut features = 0;
+        for (i, &(_, reg, bit)) in LEAF1_FEATURES.iter().enumerate() {
+            let value = if reg == 2 { leaf1.ecx } else { leaf1.edx };
+            if value & (1 << bit) != 0 {
+                features |= 1 << i;
+            }
+        }
+
+        let brand = if __cpuid(0x8000_0000).eax >= CPUID_BRAND_LAST {
+            let mut brand = [0u8; 48];
+            let leaves = CPUID_BRAND_FIRST..=CPUID_BRAND_LAST;
+            for (i, regs) in leaves.map(|leaf| __cpuid(leaf)).enumerate() {
+                let chunk = &mut brand[i * 16..(i + 1) * 16];
+                chunk[0..4].copy_from_slice(&regs.eax.to_le_bytes());
+                chunk[4..8].copy_from_slice(&regs.ebx.to_le_bytes());
+                chunk[8..12].copy_from_slice(&regs.ecx.to_le_bytes());
+                chunk[12..16].copy_from_slice(&regs.edx.to_le_bytes());
+            }
+            Some(brand)
+        }
+        else {
+            None
+        };
+
+        HwInfo {
+            vendor,
+            brand,
+            family,
+            model,
+            stepping: leaf1.eax & 0xf,
+            features,
+            usable_memory: 0,
+            phys_end: 0
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_report_collected() {
+    use crate::{serial_print, serial_println};
+    serial_print!("hwinfo::report_collected ");
+
+    let info = report().expect("hwinfo not collected during init");
+    assert!(info.vendor_str().chars().all(|c| c.is_ascii_graphic()));
+
+    // Every x86_64 CPU has SSE2
+    assert!(info.has_feature("sse2"));
+    assert!(!info.has_feature("not a feature"));
+    assert!(info.usable_memory > 0);
+    assert!(info.phys_end >= info.usable_memory);
+
+    serial_println!("[ok]");
+}
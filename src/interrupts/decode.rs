@@ -0,0 +1,177 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use x86_64::structures::idt::PageFaultErrorCode;
+
+#[cfg(test)]
+use crate::{serial_print, serial_println};
+#[cfg(test)]
+use alloc::string::ToString;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The descriptor table referenced by a selector error code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorTable {
+    Gdt,
+    Idt,
+    Ldt
+}
+
+/// A decoded selector error code, as pushed by the general protection, 
+/// invalid TSS, segment not present and stack segment faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectorErrorCode {
+    /// The exception was caused by an event external to the program, such as 
+    /// a hardware interrupt.
+    pub external: bool,
+
+    /// The table the faulting selector indexes into.
+    pub table: DescriptorTable,
+
+    /// The index of the faulting descriptor in the table.
+    pub index: u16
+}
+
+impl SelectorErrorCode {
+
+    /// Decode a raw selector error code.
+    /// 
+    /// Returns `None` for a zero error code, which means the fault was not 
+    /// caused by a segment selector.
+    pub fn new(code: u64) -> Option<SelectorErrorCode> {
+        if code == 0 {
+            return None;
+        }
+
+        // Bits 1 and 2 give the table, with both 0b01 and 0b11 meaning the IDT
+        let table = match (code >> 1) & 0b11 {
+            0b00 => DescriptorTable::Gdt,
+            0b10 => DescriptorTable::Ldt,
+            _ => DescriptorTable::Idt
+        };
+
+        Some(SelectorErrorCode {
+            external: code & 1 != 0,
+            table,
+            index: ((code >> 3) & 0x1fff) as u16
+        })
+    }
+}
+
+impl fmt::Display for SelectorErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} entry {}", self.table, self.index)?;
+
+        // IDT entries are vectors so give the hex too
+        if self.table == DescriptorTable::Idt {
+            write!(f, " (vector {:#x})", self.index)?;
+        }
+
+        if self.external {
+            write!(f, ", caused by an external event")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Display wrapper which describes a raw selector error code, including the 
+/// case where it is not selector related.
+#[derive(Debug, Clone, Copy)]
+pub struct SelectorDescription(pub u64);
+
+impl fmt::Display for SelectorDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match SelectorErrorCode::new(self.0) {
+            Some(code) => write!(f, "{}", code),
+            None => write!(f, "not caused by a segment selector")
+        }
+    }
+}
+
+/// Display wrapper which describes a page fault error code in words.
+#[derive(Debug, Clone, Copy)]
+pub struct PageFaultDescription(pub PageFaultErrorCode);
+
+impl fmt::Display for PageFaultDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = self.0;
+
+        let mode = if code.contains(PageFaultErrorCode::USER_MODE) {
+            "user"
+        }
+        else {
+            "kernel"
+        };
+
+        let access = if code.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {
+            "instruction fetch from"
+        }
+        else if code.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {
+            "write to"
+        }
+        else {
+            "read from"
+        };
+
+        let page = if code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+            "a present page (protection violation)"
+        }
+        else {
+            "a non-present page"
+        };
+
+        write!(f, "{}-mode {} {}", mode, access, page)?;
+
+        if code.contains(PageFaultErrorCode::MALFORMED_TABLE) {
+            write!(f, ", reserved bit set in page table entry")?;
+        }
+
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Describe a page fault error code.
+pub fn page_fault(code: PageFaultErrorCode) -> PageFaultDescription {
+    PageFaultDescription(code)
+}
+
+/// Describe a selector error code.
+pub fn selector(code: u64) -> SelectorDescription {
+    SelectorDescription(code)
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_decode_selector() {
+    serial_print!("interrupts::decode::selector ");
+
+    // Index 5 of the GDT
+    assert_eq!(SelectorErrorCode::new(5 << 3), Some(SelectorErrorCode {
+        external: false,
+        table: DescriptorTable::Gdt,
+        index: 5
+    }));
+
+    // Vector 0x21 of the IDT, external
+    let code = SelectorErrorCode::new(0x21 << 3 | 0b011).unwrap();
+    assert_eq!(code.table, DescriptorTable::Idt);
+    assert_eq!(code.index, 0x21);
+    assert!(code.external);
+
+    assert_eq!(selector(0).to_string(), "not caused by a segment selector");
+
+    serial_println!("[ok]");
+}
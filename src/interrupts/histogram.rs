@@ -0,0 +1,194 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of buckets. Bucket `n` holds values below `2^n`, and the last 
+/// bucket holds everything larger.
+pub const BUCKETS: usize = 40;
+
+/// Initial value for the bucket array, only used to build new arrays.
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A lock-free histogram with power of two buckets, which can be updated 
+/// from interrupt handlers.
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64
+}
+
+impl Histogram {
+
+    /// Create an empty histogram.
+    pub const fn new() -> Self {
+        Histogram {
+            buckets: [ZERO; BUCKETS],
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0)
+        }
+    }
+
+    /// Record a value.
+    pub fn record(&self, value: u64) {
+        self.buckets[bucket_of(value)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+
+        let mut max = self.max.load(Ordering::Relaxed);
+        while value > max {
+            match self.max.compare_exchange_weak(max, value, 
+                Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => max = current
+            }
+        }
+    }
+
+    /// Reset every bucket to zero.
+    pub fn reset(&self) {
+        for bucket in self.buckets.iter() {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+
+    /// Take a copy of the histogram.
+    /// 
+    /// Values recorded while the copy is taken may be partially included.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut buckets = [0; BUCKETS];
+        for (copy, bucket) in buckets.iter_mut().zip(self.buckets.iter()) {
+            *copy = bucket.load(Ordering::Relaxed);
+        }
+
+        Snapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed)
+        }
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+/// A copy of a `Histogram`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    pub buckets: [u64; BUCKETS],
+    pub count: u64,
+    pub sum: u64,
+    pub max: u64
+}
+
+impl Snapshot {
+
+    /// The mean value, or 0 if nothing has been recorded.
+    pub fn mean(&self) -> u64 {
+        self.sum.checked_div(self.count).unwrap_or(0)
+    }
+
+    /// An upper bound on the given percentile, the limit of the bucket it 
+    /// falls in, capped at the maximum recorded value.
+    /// 
+    /// Returns 0 if nothing has been recorded.
+    pub fn percentile(&self, percent: u64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = (self.count * percent.min(100) / 100).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_limit(bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// The bucket a value is counted in.
+pub fn bucket_of(value: u64) -> usize {
+    ((64 - value.leading_zeros()) as usize).min(BUCKETS - 1)
+}
+
+/// The largest value counted in a bucket.
+pub fn bucket_limit(bucket: usize) -> u64 {
+    if bucket >= BUCKETS - 1 {
+        u64::MAX
+    }
+    else {
+        (1u64 << bucket) - 1
+    }
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets() {
+        assert_eq!(bucket_of(0), 0);
+        assert_eq!(bucket_of(1), 1);
+        assert_eq!(bucket_of(2), 2);
+        assert_eq!(bucket_of(3), 2);
+        assert_eq!(bucket_of(1000), 10);
+        assert_eq!(bucket_of(u64::MAX), BUCKETS - 1);
+
+        for bucket in 1..BUCKETS - 1 {
+            assert_eq!(bucket_of(bucket_limit(bucket)), bucket);
+            assert_eq!(bucket_of(bucket_limit(bucket) + 1), bucket + 1);
+        }
+    }
+
+    #[test]
+    fn record_and_percentiles() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.snapshot().percentile(50), 0);
+
+        for value in 1..=100 {
+            histogram.record(value);
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 100);
+        assert_eq!(snapshot.max, 100);
+        assert_eq!(snapshot.mean(), 50);
+
+        // 50 falls in the 32..=63 bucket, 99 and 100 in 64..=127
+        assert_eq!(snapshot.percentile(50), 63);
+        assert_eq!(snapshot.percentile(99), 100);
+        assert_eq!(snapshot.percentile(100), 100);
+
+        histogram.reset();
+        assert_eq!(histogram.snapshot().count, 0);
+    }
+}
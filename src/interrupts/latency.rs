@@ -0,0 +1,195 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::_rdtsc;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::log::clock;
+use super::histogram::{Histogram, Snapshot};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of instrumented interrupts.
+const IRQ_COUNT: usize = 3;
+
+/// Handler durations in TSC cycles, indexed by `Irq`.
+static DURATIONS: [Histogram; IRQ_COUNT] = 
+    [Histogram::new(), Histogram::new(), Histogram::new()];
+
+/// How far each timer interrupt arrived from one tick period after the 
+/// previous one, in TSC cycles.
+static TIMER_JITTER: Histogram = Histogram::new();
+
+/// The TSC value at entry to the previous timer interrupt.
+static LAST_TIMER_ENTRY: AtomicU64 = AtomicU64::new(0);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The instrumented interrupts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Irq {
+    Timer = 0,
+    Keyboard = 1,
+    Serial1 = 2
+}
+
+impl Irq {
+    /// All instrumented interrupts, in index order.
+    pub const ALL: [Irq; IRQ_COUNT] = [Irq::Timer, Irq::Keyboard, Irq::Serial1];
+
+    /// The name of the interrupt.
+    pub fn name(self) -> &'static str {
+        match self {
+            Irq::Timer => "timer",
+            Irq::Keyboard => "keyboard",
+            Irq::Serial1 => "serial1"
+        }
+    }
+}
+
+/// Records the duration of an interrupt handler when dropped, created by 
+/// `enter`.
+pub(crate) struct HandlerGuard {
+    irq: Irq,
+    entry: u64
+}
+
+impl Drop for HandlerGuard {
+    fn drop(&mut self) {
+        DURATIONS[self.irq as usize].record(rdtsc().saturating_sub(self.entry));
+    }
+}
+
+/// A snapshot of every latency histogram, printed in nanoseconds once the 
+/// TSC is calibrated and in cycles before that.
+pub struct Report {
+    durations: [Snapshot; IRQ_COUNT],
+    timer_jitter: Snapshot
+}
+
+impl Report {
+    /// The handler duration histogram of an interrupt, in TSC cycles.
+    pub fn duration(&self, irq: Irq) -> &Snapshot {
+        &self.durations[irq as usize]
+    }
+
+    /// The timer arrival jitter histogram, in TSC cycles.
+    pub fn timer_jitter(&self) -> &Snapshot {
+        &self.timer_jitter
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = if clock::tsc_hz().is_some() { "ns" } else { "cycles" };
+        writeln!(f, "IRQ latency ({}):", unit)?;
+        for irq in Irq::ALL.iter() {
+            write_row(f, irq.name(), "handler", self.duration(*irq))?;
+            writeln!(f)?;
+        }
+        write_row(f, Irq::Timer.name(), "jitter", &self.timer_jitter)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Start timing an interrupt handler, which should hold the returned guard
+/// until it returns.
+/// 
+/// The timer's arrival time is also compared against the previous tick, 
+/// which shows how long interrupts were held off by code with interrupts 
+/// disabled. This needs the TSC to be calibrated.
+pub(crate) fn enter(irq: Irq) -> HandlerGuard {
+    let entry = rdtsc();
+
+    if irq == Irq::Timer {
+        let last = LAST_TIMER_ENTRY.swap(entry, Ordering::Relaxed);
+        if let (Some(period), true) = (clock::tick_period_cycles(), last != 0) {
+            let interval = entry.saturating_sub(last);
+            TIMER_JITTER.record(if interval > period { 
+                interval - period 
+            } 
+            else { 
+                period - interval 
+            });
+        }
+    }
+
+    HandlerGuard { irq, entry }
+}
+
+/// Take a snapshot of the latency histograms.
+pub fn report() -> Report {
+    Report {
+        durations: [
+            DURATIONS[0].snapshot(), 
+            DURATIONS[1].snapshot(), 
+            DURATIONS[2].snapshot()
+        ],
+        timer_jitter: TIMER_JITTER.snapshot()
+    }
+}
+
+/// Reset every histogram, for example before measuring a new driver.
+pub fn reset() {
+    for histogram in DURATIONS.iter() {
+        histogram.reset();
+    }
+    TIMER_JITTER.reset();
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read the TSC.
+fn rdtsc() -> u64 {
+    // NOTE: USE OF UNSAFE
+    //  RDTSC is always available on x86_64.
+    unsafe { _rdtsc() }
+}
+
+/// Write one line of the report, converting cycles into nanoseconds if the
+/// TSC is calibrated.
+fn write_row(
+    f: &mut fmt::Formatter, 
+    name: &str, 
+    kind: &str, 
+    snapshot: &Snapshot
+) -> fmt::Result {
+    let convert = |cycles| clock::cycles_to_ns(cycles).unwrap_or(cycles);
+    write!(f, "    {:8} {:7} {:8} samples, mean {}, p50 {}, p99 {}, max {}",
+        name, kind, snapshot.count, convert(snapshot.mean()), 
+        convert(snapshot.percentile(50)), convert(snapshot.percentile(99)), 
+        convert(snapshot.max))
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_handler_duration() {
+    use crate::{serial_print, serial_println};
+    serial_print!("interrupts::latency::handler_duration ");
+
+    let before = report().duration(Irq::Keyboard).count;
+    {
+        let _guard = enter(Irq::Keyboard);
+        for _ in 0..1000 {
+            core::sync::atomic::spin_loop_hint();
+        }
+    }
+    let after = report();
+    assert!(after.duration(Irq::Keyboard).count > before);
+    assert!(after.duration(Irq::Keyboard).max > 0);
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,409 @@
+
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod decode;
+pub mod histogram;
+pub mod latency;
+pub mod nmi;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use lazy_static::lazy_static;
+use x86_64::structures::idt::{
+    InterruptDescriptorTable, 
+    InterruptStackFrame, 
+    PageFaultErrorCode
+};
+use x86_64::registers::control::Cr2;
+use pic8259_simple::ChainedPics;
+use spin::Mutex;
+use conquer_once::spin::OnceCell;
+use crate::ioport::PortRange;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::{exception_println, println, gdt};
+use crate::symbols::SymbolisedAddr;
+
+#[cfg(test)]
+use crate::{serial_print, serial_println};
+
+// ---------------------------------------------------------------------------
+// STATIC INITIALISATIONS
+// ---------------------------------------------------------------------------
+
+/// The frequency of the timer interrupt, the PIT's default of roughly 18.2Hz.
+pub const TIMER_FREQUENCY_HZ: u64 = 18;
+
+/// The number of timer interrupts since the PICs were initialised.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+pub const PIC_1_OFFSET: u8 = 32;
+pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+
+lazy_static! {
+    /// The interrupt descriptor table.
+    /// 
+    /// Initialisation is done using lazy_static to allow a statically 
+    /// allocated mutable reference.
+    static ref IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+
+        // ---- CPU EXCEPTIONS ----
+        idt.breakpoint.set_handler_fn(breakpoint_hander);
+        idt.debug.set_handler_fn(debug_handler);
+        idt.invalid_tss.set_handler_fn(invalid_tss_handler);
+        idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+        idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
+        idt.general_protection_fault
+            .set_handler_fn(general_protection_fault_handler);
+
+        // NOTE: USE OF UNSAFE
+        //  This code is unsafe since the argument to `set_stack_index` must
+        //  be a valid stack index not used by any other interrupts. This is
+        //  enforced by using the global constant index from the GDT module.
+        unsafe {
+            idt.double_fault.set_handler_fn(double_fault_handler)
+                .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+            idt.non_maskable_interrupt.set_handler_fn(nmi_handler)
+                .set_stack_index(gdt::NMI_IST_INDEX);
+            idt.machine_check.set_handler_fn(machine_check_handler)
+                .set_stack_index(gdt::MACHINE_CHECK_IST_INDEX);
+
+            let page_fault = idt.page_fault.set_handler_fn(page_fault_handler);
+            if gdt::PAGE_FAULT_IST {
+                page_fault.set_stack_index(gdt::PAGE_FAULT_IST_INDEX);
+            }
+        }
+
+        // ---- HARDWARE INTERRUPTS ----
+        idt[InterruptIndex::Timer.as_usize()]
+            .set_handler_fn(timer_interrupt_handler);
+        idt[InterruptIndex::Keyboard.as_usize()]
+            .set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::Serial1.as_usize()]
+            .set_handler_fn(serial1_interrupt_handler);
+
+        idt
+    };
+
+    /// A minimal IDT containing only a halting double fault handler, loaded 
+    /// by `load_emergency_idt`.
+    /// 
+    /// This is built by `init_idt` so the panic path only has to load it.
+    static ref EMERGENCY_IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+
+        // NOTE: USE OF UNSAFE
+        //  See `IDT`. The double fault stack is known good even if the stack
+        //  which panicked has overflowed.
+        unsafe {
+            idt.double_fault.set_handler_fn(emergency_double_fault_handler)
+                .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+        }
+
+        idt
+    };
+}
+
+/// Chained PIC static for dealing with hardware interrupts.
+/// 
+/// NOTE: USE OF UNSAFE
+///     The use of unsafe here required since invalid offsets can cause 
+///     undefined behaviour. Safety is enforced through the use of constants.
+pub static PICS: Mutex<ChainedPics> = Mutex::new(
+    unsafe{ ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+
+/// The PIC command and data ports. These are driven by `ChainedPics`, the 
+/// claims only stop other drivers using them.
+static PIC_PORTS: OnceCell<(PortRange, PortRange)> = OnceCell::uninit();
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURE DEFINITIONS
+// ---------------------------------------------------------------------------
+
+/// Interrupt index enum.
+/// 
+/// Contains the indexes of all hardware interrupts in the PICs.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum InterruptIndex {
+    Timer = PIC_1_OFFSET,
+    Keyboard,
+    Serial1 = PIC_1_OFFSET + 4,
+}
+
+impl InterruptIndex {
+    fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    fn as_usize(self) -> usize {
+        usize::from(self.as_u8())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the number of timer ticks since the PICs were initialised.
+/// 
+/// Ticks arrive at `TIMER_FREQUENCY_HZ`.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Claim the I/O ports of the PICs.
+pub fn init_ports() {
+    let claims = PortRange::claim(0x20, 2, "pic1")
+        .and_then(|pic1| Ok((pic1, PortRange::claim(0xa0, 2, "pic2")?)));
+
+    match claims {
+        Ok(ports) => {
+            PIC_PORTS.try_init_once(|| ports)
+                .expect("interrupts::init_ports must only be called once");
+        },
+        Err(e) => println!("[INT-ERROR] Cannot claim PIC ports: {}", e)
+    }
+}
+
+/// Initialise the interrupt descriptor table, and build the emergency one.
+pub fn init_idt() {
+    IDT.load();
+    lazy_static::initialize(&EMERGENCY_IDT);
+}
+
+/// Disable interrupts and load a minimal IDT containing only a halting double
+/// fault handler.
+/// 
+/// This is used at the start of the panic path, where the main IDT (or the 
+/// structures its handlers rely on) may be corrupted. Any fault while printing
+/// the panic then ends in a halt rather than recursing or triple faulting.
+pub fn load_emergency_idt() {
+    x86_64::instructions::interrupts::disable();
+    EMERGENCY_IDT.load();
+}
+
+// ---------------------------------------------------------------------------
+// CPU EXCEPTION HANDLER FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Handle the breakpoint exception.
+extern "x86-interrupt" fn breakpoint_hander(
+    stack_frame: &mut InterruptStackFrame
+) {
+    exception_println!("[CPU-EXCEPTION] BREAKPOINT\n{:#?}", stack_frame);
+}
+
+/// Handle debug exceptions, raised by hardware breakpoints and single 
+/// stepping.
+extern "x86-interrupt" fn debug_handler(
+    stack_frame: &mut InterruptStackFrame
+) {
+    crate::debug::handle_exception(stack_frame);
+}
+
+/// Handle double fault exception.
+/// 
+/// Note that unlike most handlers this one is diverging.
+extern "x86-interrupt" fn double_fault_handler(
+    stack_frame: &mut InterruptStackFrame, 
+    _error_code: u64
+) -> ! {
+    panic!("[CPU-EXCEPTION] DOUBLE FAULT at {}\n{:#?}", 
+        SymbolisedAddr(stack_frame.instruction_pointer.as_u64()), stack_frame);
+}
+
+/// Double fault handler for the emergency IDT.
+/// 
+/// This doesn't print with the normal macros as the fault may have come from
+/// within them.
+extern "x86-interrupt" fn emergency_double_fault_handler(
+    _stack_frame: &mut InterruptStackFrame, 
+    _error_code: u64
+) -> ! {
+    crate::emergency_println!("[CPU-EXCEPTION] DOUBLE FAULT IN PANIC HANDLER");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Handle non-maskable interrupts.
+/// 
+/// NMIs signal hardware failures (or a watchdog) and can arrive while locks 
+/// are held, so the state is captured and printed without taking any locks 
+/// before halting.
+extern "x86-interrupt" fn nmi_handler(
+    stack_frame: &mut InterruptStackFrame
+) {
+    nmi::handle(stack_frame);
+    crate::halt_loop();
+}
+
+/// Handle machine check exceptions.
+/// 
+/// A machine check is an uncorrectable hardware error, so like an NMI it is 
+/// reported without taking any locks before halting.
+extern "x86-interrupt" fn machine_check_handler(
+    stack_frame: &mut InterruptStackFrame
+) -> ! {
+    crate::emergency_println!("[CPU-EXCEPTION] MACHINE CHECK at {}\n{:#?}", 
+        SymbolisedAddr(stack_frame.instruction_pointer.as_u64()), stack_frame);
+    crate::halt_loop();
+}
+
+/// Handle page faults.
+extern "x86-interrupt" fn page_fault_handler(
+    stack_frame: &mut InterruptStackFrame,
+    error_code: PageFaultErrorCode
+) {
+    // Page faults can be raised from inside the allocator or a print, so 
+    // these prints neither allocate nor wait on the print locks
+    let addr = Cr2::read();
+    match crate::protection::classify_fault(
+        error_code, addr, stack_frame.cpu_flags) {
+        Some(violation) => exception_println!(
+            "[CPU-EXCEPTION] PAGE FAULT ({:?} violation)", violation),
+        None => exception_println!("[CPU-EXCEPTION] PAGE FAULT")
+    }
+    exception_println!("Address accessed: {:?}", addr);
+    exception_println!("Faulting instruction: {}", 
+        SymbolisedAddr(stack_frame.instruction_pointer.as_u64()));
+    exception_println!("Error code: {:?} ({})", 
+        error_code, decode::page_fault(error_code));
+    exception_println!("{:#?}", stack_frame);
+    crate::halt_loop();
+}
+
+/// Handle invalid TSS exceptions.
+extern "x86-interrupt" fn invalid_tss_handler(
+    stack_frame: &mut InterruptStackFrame,
+    error_code: u64
+) {
+    selector_fault("INVALID TSS", stack_frame, error_code);
+}
+
+/// Handle segment not present exceptions.
+extern "x86-interrupt" fn segment_not_present_handler(
+    stack_frame: &mut InterruptStackFrame,
+    error_code: u64
+) {
+    selector_fault("SEGMENT NOT PRESENT", stack_frame, error_code);
+}
+
+/// Handle stack segment fault exceptions.
+extern "x86-interrupt" fn stack_segment_fault_handler(
+    stack_frame: &mut InterruptStackFrame,
+    error_code: u64
+) {
+    selector_fault("STACK SEGMENT FAULT", stack_frame, error_code);
+}
+
+/// Handle general protection fault exceptions.
+extern "x86-interrupt" fn general_protection_fault_handler(
+    stack_frame: &mut InterruptStackFrame,
+    error_code: u64
+) {
+    selector_fault("GENERAL PROTECTION FAULT", stack_frame, error_code);
+}
+
+/// Report an exception which pushes a selector error code and panic.
+/// 
+/// None of these exceptions can be recovered from yet so all of them panic.
+fn selector_fault(
+    name: &str, 
+    stack_frame: &InterruptStackFrame, 
+    error_code: u64
+) -> ! {
+    panic!("[CPU-EXCEPTION] {} at {}\nError code: {:#x} ({})\n{:#?}", 
+        name,
+        SymbolisedAddr(stack_frame.instruction_pointer.as_u64()),
+        error_code,
+        decode::selector(error_code),
+        stack_frame);
+}
+
+// ---------------------------------------------------------------------------
+// HARDWARE INTERRUPT HANDLER FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Handle the hardware timer interrupt.
+extern "x86-interrupt" fn timer_interrupt_handler(
+    _stack_frame: &mut InterruptStackFrame
+) {
+    let _latency = latency::enter(latency::Irq::Timer);
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    crate::kassert::timer_tick(ticks);
+    crate::log::clock::timer_tick(ticks);
+    crate::sensors::timer_tick(ticks);
+    crate::rng::add_interrupt_timing(crate::rng::Source::Timer);
+
+    // NOTE: USE OF UNSAFE
+    //  Notify end of interrupt can be unsafe if the index is not valid. Safety
+    //  is enforced by use of the `InterruptIndex` enum.
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+    }
+}
+
+/// Handle keyboard interrupts by adding the scancode into the keyboard task 
+/// queue.
+extern "x86-interrupt" fn keyboard_interrupt_handler(
+    stack_frame: &mut InterruptStackFrame
+) {
+    let _latency = latency::enter(latency::Irq::Keyboard);
+
+    // Read the scancode and add it to the keyboard proc queue.
+    //
+    // SysRq combinations are handled here so they work even if the executor
+    // is stuck
+    if let Some(scancode) = crate::task::keyboard::read_scancode() {
+        crate::rng::add_interrupt_timing(crate::rng::Source::Keyboard);
+        crate::trace::event_value("kbd::irq", scancode as u64);
+        if !crate::sysrq::filter_scancode(scancode, stack_frame) {
+            crate::task::keyboard::push_scancode(scancode);
+        }
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  Notify end of interrupt can be unsafe if the index is not valid. Safety
+    //  is enforced by use of the `InterruptIndex` enum.
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+    }
+
+}
+
+/// Handle serial port 1 interrupts by passing received bytes to the escape 
+/// processor.
+extern "x86-interrupt" fn serial1_interrupt_handler(
+    stack_frame: &mut InterruptStackFrame
+) {
+    let _latency = latency::enter(latency::Irq::Serial1);
+    crate::rng::add_interrupt_timing(crate::rng::Source::Serial);
+    crate::serial::handle_interrupt(stack_frame);
+
+    // NOTE: USE OF UNSAFE
+    //  Notify end of interrupt can be unsafe if the index is not valid. Safety
+    //  is enforced by use of the `InterruptIndex` enum.
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::Serial1.as_u8());
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_breakpoint() {
+    serial_print!("interrupts::breakpoint ");
+
+    // Invoke the breakpoint exception
+    x86_64::instructions::interrupts::int3();
+
+    serial_println!("[ok]");
+}
\ No newline at end of file
@@ -0,0 +1,168 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::structures::idt::InterruptStackFrame;
+use x86_64::registers::control::{Cr2, Cr3};
+use conquer_once::spin::OnceCell;
+use crate::ioport::PortRange;
+use crate::vga_buffer::{self, BUFFER_WIDTH};
+use crate::symbols::SymbolisedAddr;
+use crate::emergency_println;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of console lines captured into the crash area.
+pub const CRASH_LINES: usize = 8;
+
+/// System control port B, which reports the source of an NMI.
+const SYSTEM_CONTROL_PORT_B: u16 = 0x61;
+
+/// The claimed system control port B.
+static SYSTEM_CONTROL: OnceCell<PortRange> = OnceCell::uninit();
+
+/// The crash area written by the NMI handler.
+///
+/// NOTE: This is a mutable static since the NMI handler cannot take locks,
+/// writes are guarded by `CRASH_AREA_TAKEN` instead.
+static mut CRASH_AREA: CrashArea = CrashArea::empty();
+
+/// Set once the crash area has been claimed by an NMI.
+static CRASH_AREA_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Set once the crash area has been completely written.
+static CRASH_AREA_WRITTEN: AtomicBool = AtomicBool::new(false);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The state captured when an NMI is received.
+#[derive(Clone)]
+pub struct CrashArea {
+    pub instruction_pointer: u64,
+    pub code_segment: u64,
+    pub cpu_flags: u64,
+    pub stack_pointer: u64,
+    pub stack_segment: u64,
+    pub cr2: u64,
+    pub cr3: u64,
+
+    /// The value of system control port B, giving the NMI source.
+    pub reason: u8,
+
+    /// The last lines written to the console, oldest first.
+    pub lines: [[u8; BUFFER_WIDTH]; CRASH_LINES]
+}
+
+impl CrashArea {
+
+    /// Create an empty crash area.
+    const fn empty() -> CrashArea {
+        CrashArea {
+            instruction_pointer: 0,
+            code_segment: 0,
+            cpu_flags: 0,
+            stack_pointer: 0,
+            stack_segment: 0,
+            cr2: 0,
+            cr3: 0,
+            reason: 0,
+            lines: [[b' '; BUFFER_WIDTH]; CRASH_LINES]
+        }
+    }
+
+    /// Get a description of the NMI source from the system control port.
+    pub fn reason_str(&self) -> &'static str {
+        if self.reason & 0x80 != 0 {
+            "memory parity/system error"
+        }
+        else if self.reason & 0x40 != 0 {
+            "I/O channel check"
+        }
+        else {
+            "unknown (possibly watchdog)"
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Claim system control port B, so the NMI handler can read the NMI source.
+pub fn init_ports() {
+    match PortRange::claim(SYSTEM_CONTROL_PORT_B, 1, "system control b") {
+        Ok(port) => {
+            SYSTEM_CONTROL.try_init_once(|| port)
+                .expect("nmi::init_ports must only be called once");
+        },
+        Err(e) => crate::println!("[NMI-ERROR] Cannot claim port: {}", e)
+    }
+}
+
+/// Get the crash area written by the last NMI, if there was one.
+pub fn crash_area() -> Option<&'static CrashArea> {
+    if CRASH_AREA_WRITTEN.load(Ordering::Acquire) {
+        // NOTE: USE OF UNSAFE
+        //  The area is never written again once `CRASH_AREA_WRITTEN` is set.
+        Some(unsafe { &CRASH_AREA })
+    }
+    else {
+        None
+    }
+}
+
+/// Capture the state of the CPU into the crash area and print it.
+///
+/// Only the first NMI is captured, any later ones are just reported.
+pub(crate) fn handle(stack_frame: &InterruptStackFrame) {
+    if CRASH_AREA_TAKEN.swap(true, Ordering::AcqRel) {
+        emergency_println!("[NMI] Further NMI at {}",
+            SymbolisedAddr(stack_frame.instruction_pointer.as_u64()));
+        return;
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  The crash area is claimed above so nothing else can be writing it.
+    let area = unsafe { &mut CRASH_AREA };
+    area.instruction_pointer = stack_frame.instruction_pointer.as_u64();
+    area.code_segment = stack_frame.code_segment;
+    area.cpu_flags = stack_frame.cpu_flags;
+    area.stack_pointer = stack_frame.stack_pointer.as_u64();
+    area.stack_segment = stack_frame.stack_segment;
+    area.cr2 = Cr2::read().as_u64();
+    area.cr3 = Cr3::read().0.start_address().as_u64();
+    area.reason = SYSTEM_CONTROL.try_get()
+        .map(|port| port.read_u8(0))
+        .unwrap_or(0);
+    vga_buffer::recent_lines(&mut area.lines);
+
+    CRASH_AREA_WRITTEN.store(true, Ordering::Release);
+
+    report(area);
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Print the crash area to the serial port without taking any locks.
+fn report(area: &CrashArea) {
+    emergency_println!("\n[NMI] NON-MASKABLE INTERRUPT: {}", area.reason_str());
+    emergency_println!("RIP: {}", SymbolisedAddr(area.instruction_pointer));
+    emergency_println!("CS: {:#x}  RFLAGS: {:#x}",
+        area.code_segment, area.cpu_flags);
+    emergency_println!("RSP: {:#x}  SS: {:#x}",
+        area.stack_pointer, area.stack_segment);
+    emergency_println!("CR2: {:#x}  CR3: {:#x}", area.cr2, area.cr3);
+    emergency_println!("Last console lines:");
+    for line in area.lines.iter() {
+        // Placeholder characters aren't valid UTF-8, so fall back if needed
+        let line = core::str::from_utf8(line).unwrap_or("<invalid>");
+        emergency_println!("  | {}", line.trim_end());
+    }
+}
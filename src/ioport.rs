@@ -0,0 +1,196 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The maximum number of port ranges which can be claimed at once.
+const MAX_CLAIMS: usize = 16;
+
+/// The claimed port ranges, as `(base, len, owner)`.
+static CLAIMS: Mutex<[Option<(u16, u16, &'static str)>; MAX_CLAIMS]> = 
+    Mutex::new([None; MAX_CLAIMS]);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Errors which can occur when claiming a port range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPortError {
+    /// Part of the range is already claimed by the given owner.
+    Conflict(&'static str),
+
+    /// `MAX_CLAIMS` ranges are already claimed.
+    TooManyClaims,
+
+    /// The range is empty or runs past the end of the port space.
+    InvalidRange
+}
+
+impl fmt::Display for IoPortError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IoPortError::Conflict(owner) => 
+                write!(f, "ports already claimed by {}", owner),
+            IoPortError::TooManyClaims => write!(f, "too many port claims"),
+            IoPortError::InvalidRange => write!(f, "invalid port range")
+        }
+    }
+}
+
+/// An owned range of I/O ports.
+/// 
+/// Only one `PortRange` can cover a given port at a time, so a driver 
+/// holding one knows no other driver is using its ports. The claim is 
+/// released when the range is dropped.
+/// 
+/// Port accesses take `&self` since each is a single instruction, so a range 
+/// can be shared with interrupt handlers through a static.
+#[derive(Debug)]
+pub struct PortRange {
+    base: u16,
+    len: u16
+}
+
+impl PortRange {
+
+    /// Claim the ports `base..base + len` for `owner`.
+    pub fn claim(base: u16, len: u16, owner: &'static str) 
+        -> Result<PortRange, IoPortError> {
+
+        if len == 0 || base.checked_add(len - 1).is_none() {
+            return Err(IoPortError::InvalidRange);
+        }
+
+        let end = base as u32 + len as u32;
+        let mut claims = CLAIMS.lock();
+
+        // Check for overlap with any existing claim
+        for &(claim_base, claim_len, claim_owner) in claims.iter().flatten() {
+            let claim_end = claim_base as u32 + claim_len as u32;
+            if (base as u32) < claim_end && (claim_base as u32) < end {
+                return Err(IoPortError::Conflict(claim_owner));
+            }
+        }
+
+        let slot = claims.iter_mut().find(|c| c.is_none())
+            .ok_or(IoPortError::TooManyClaims)?;
+        *slot = Some((base, len, owner));
+
+        Ok(PortRange { base, len })
+    }
+
+    /// The first port in the range.
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    /// The number of ports in the range.
+    pub fn len(&self) -> u16 {
+        self.len
+    }
+
+    /// Read a byte from the port at `offset` in the range.
+    pub fn read_u8(&self, offset: u16) -> u8 {
+        // NOTE: USE OF UNSAFE
+        //  The port is owned by this range, so reading it can only affect 
+        //  the device belonging to the owner.
+        unsafe { Port::<u8>::new(self.port(offset, 1)).read() }
+    }
+
+    /// Write a byte to the port at `offset` in the range.
+    pub fn write_u8(&self, offset: u16, value: u8) {
+        // NOTE: USE OF UNSAFE
+        //  See `read_u8`.
+        unsafe { Port::<u8>::new(self.port(offset, 1)).write(value) }
+    }
+
+    /// Read a word from the port at `offset` in the range.
+    pub fn read_u16(&self, offset: u16) -> u16 {
+        // NOTE: USE OF UNSAFE
+        //  See `read_u8`.
+        unsafe { Port::<u16>::new(self.port(offset, 2)).read() }
+    }
+
+    /// Write a word to the port at `offset` in the range.
+    pub fn write_u16(&self, offset: u16, value: u16) {
+        // NOTE: USE OF UNSAFE
+        //  See `read_u8`.
+        unsafe { Port::<u16>::new(self.port(offset, 2)).write(value) }
+    }
+
+    /// Read a double word from the port at `offset` in the range.
+    pub fn read_u32(&self, offset: u16) -> u32 {
+        // NOTE: USE OF UNSAFE
+        //  See `read_u8`.
+        unsafe { Port::<u32>::new(self.port(offset, 4)).read() }
+    }
+
+    /// Write a double word to the port at `offset` in the range.
+    pub fn write_u32(&self, offset: u16, value: u32) {
+        // NOTE: USE OF UNSAFE
+        //  See `read_u8`.
+        unsafe { Port::<u32>::new(self.port(offset, 4)).write(value) }
+    }
+
+    /// Get the port number at `offset`, panicking if an access of `size` 
+    /// bytes would go outside the range.
+    fn port(&self, offset: u16, size: u16) -> u16 {
+        assert!(offset as u32 + size as u32 <= self.len as u32, 
+            "[IOPORT-ERROR] Access at offset {} outside range {:#x}+{}", 
+            offset, self.base, self.len);
+        self.base + offset
+    }
+}
+
+impl Drop for PortRange {
+    fn drop(&mut self) {
+        let mut claims = CLAIMS.lock();
+        if let Some(slot) = claims.iter_mut()
+            .find(|c| c.map(|(base, _, _)| base) == Some(self.base)) {
+            *slot = None;
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the owner of the given port, if it is claimed.
+pub fn owner(port: u16) -> Option<&'static str> {
+    CLAIMS.lock().iter().flatten()
+        .find(|&&(base, len, _)| 
+            port >= base && (port as u32) < base as u32 + len as u32)
+        .map(|&(_, _, owner)| owner)
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_claim_conflicts() {
+    use crate::{serial_print, serial_println};
+    serial_print!("ioport::claim_conflicts ");
+
+    // Use ports nothing in the kernel drives
+    let range = PortRange::claim(0x2e0, 8, "test").unwrap();
+    assert_eq!(owner(0x2e7), Some("test"));
+    assert_eq!(PortRange::claim(0x2e4, 8, "other").unwrap_err(), 
+        IoPortError::Conflict("test"));
+
+    // Released on drop
+    drop(range);
+    assert_eq!(owner(0x2e0), None);
+    assert!(PortRange::claim(0x2e4, 8, "other").is_ok());
+
+    serial_println!("[ok]");
+}
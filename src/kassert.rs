@@ -0,0 +1,194 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+use futures_util::task::AtomicWaker;
+use crate::interrupts::TIMER_FREQUENCY_HZ;
+use crate::{allocator, log, log_error, task};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// Whether `kdebug_assert!` and the periodic invariant checks are compiled 
+/// in, which they are in debug builds.
+pub const DEBUG_CHECKS: bool = cfg!(debug_assertions);
+
+/// The number of timer ticks between invariant checks.
+const CHECK_INTERVAL_TICKS: u64 = 10 * TIMER_FREQUENCY_HZ;
+
+/// The `Action` taken on a failed assertion.
+static ACTION: AtomicU8 = AtomicU8::new(Action::Panic as u8);
+
+/// The number of failed assertions.
+static FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+/// Set by the timer when the invariant checks are due.
+static CHECK_DUE: AtomicBool = AtomicBool::new(false);
+static CHECK_WAKER: AtomicWaker = AtomicWaker::new();
+
+// ---------------------------------------------------------------------------
+// MACRO DEFINITIONS
+// ---------------------------------------------------------------------------
+
+/// Assert that a condition holds, taking the configured `Action` if not.
+/// 
+/// Unlike `assert!` a failure doesn't have to panic, so this can be used for 
+/// checks the kernel could survive failing.
+#[macro_export]
+macro_rules! kassert {
+    ($cond:expr) => ($crate::kassert!($cond, "{}", stringify!($cond)));
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::kassert::_failed(file!(), line!(), 
+                format_args!($($arg)+));
+        }
+    };
+}
+
+/// Equivalent of `kassert!` which is only checked in debug builds.
+#[macro_export]
+macro_rules! kdebug_assert {
+    ($($arg:tt)*) => {
+        if $crate::kassert::DEBUG_CHECKS {
+            $crate::kassert!($($arg)*);
+        }
+    };
+}
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// What happens when an assertion fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Action {
+    /// Panic, which writes the crash dump.
+    Panic = 0,
+
+    /// Log a rate limited error and continue.
+    Log = 1,
+
+    /// Only count the failure.
+    Count = 2
+}
+
+/// Future resolved when the invariant checks are due.
+struct CheckDue;
+
+impl Future for CheckDue {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if CHECK_DUE.swap(false, Ordering::AcqRel) {
+            return Poll::Ready(());
+        }
+
+        CHECK_WAKER.register(cx.waker());
+
+        if CHECK_DUE.swap(false, Ordering::AcqRel) {
+            Poll::Ready(())
+        }
+        else {
+            Poll::Pending
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Set the action taken when an assertion fails.
+pub fn set_action(action: Action) {
+    ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+/// Get the action taken when an assertion fails.
+pub fn action() -> Action {
+    match ACTION.load(Ordering::Relaxed) {
+        1 => Action::Log,
+        2 => Action::Count,
+        _ => Action::Panic
+    }
+}
+
+/// The number of assertions which have failed.
+pub fn failures() -> usize {
+    FAILURES.load(Ordering::Relaxed)
+}
+
+#[doc(hidden)]
+pub fn _failed(file: &str, line: u32, args: fmt::Arguments) {
+    FAILURES.fetch_add(1, Ordering::Relaxed);
+
+    match action() {
+        Action::Panic => panic!("[ASSERT] {}:{}: {}", file, line, args),
+        Action::Log => log_error!(log::ASSERT, "{}:{}: {}", file, line, args),
+        Action::Count => ()
+    }
+}
+
+/// Check the invariants of the core kernel structures.
+pub fn check_invariants() {
+    let counts = task::counts();
+    kassert!(counts.finished <= counts.created, 
+        "more tasks finished than created: {}", counts);
+
+    if let Err(e) = allocator::check_free_lists() {
+        kassert!(false, "block allocator free list corrupt: {}", e);
+    }
+}
+
+/// Background task which checks the invariants periodically, in debug 
+/// builds only.
+pub async fn checker() {
+    if !DEBUG_CHECKS {
+        return;
+    }
+
+    loop {
+        CheckDue.await;
+        check_invariants();
+    }
+}
+
+/// Schedule the invariant checks if they are due.
+/// 
+/// Should be called from the timer interrupt handler.
+pub(crate) fn timer_tick(ticks: u64) {
+    if DEBUG_CHECKS && ticks % CHECK_INTERVAL_TICKS == 0 {
+        CHECK_DUE.store(true, Ordering::Release);
+        CHECK_WAKER.wake();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_kassert_actions() {
+    use crate::{serial_print, serial_println};
+    serial_print!("kassert::actions ");
+
+    let before = failures();
+    kassert!(1 + 1 == 2);
+    assert_eq!(failures(), before);
+
+    set_action(Action::Count);
+    kassert!(1 + 1 == 3);
+    kassert!(false, "counted {}", "failure");
+    assert_eq!(failures(), before + 2);
+    set_action(Action::Panic);
+
+    check_invariants();
+
+    serial_println!("[ok]");
+}
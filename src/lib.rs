@@ -6,6 +6,7 @@
 #![feature(alloc_error_handler)]
 #![feature(const_in_array_repeat_expressions)]
 #![feature(wake_trait)]
+#![feature(llvm_asm)]
 #![test_runner(crate::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 
@@ -17,8 +18,8 @@
 // ---------------------------------------------------------------------------
 
 use core::panic::PanicInfo;
-use x86_64::VirtAddr;
 use bootloader::BootInfo;
+use conquer_once::spin::OnceCell;
 
 #[cfg(test)]
 use bootloader::entry_point;
@@ -36,12 +37,48 @@ pub mod gdt;
 pub mod memory;
 pub mod allocator;
 pub mod task;
+pub mod clipboard;
+pub mod boot_ui;
+pub mod symbols;
+pub mod crashdump;
+pub mod fs;
+pub mod event;
+pub mod power;
+pub mod sensors;
+pub mod sysrq;
+pub mod ioport;
+pub mod debug;
+pub mod nvram;
+pub mod perf;
+pub mod protection;
+pub mod rng;
+pub mod selftest;
+pub mod sync;
+pub mod hwinfo;
+pub mod log;
+pub mod kassert;
+pub mod hash;
+pub mod compress;
+pub mod trace;
+pub mod stack;
 
 // ---------------------------------------------------------------------------
 // MODULE USE STATEMENTS
 // ---------------------------------------------------------------------------
 
 use memory::BootInfoFrameAllocator;
+use boot_ui::BootProgress;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The number of stages run by `init`, used to scale the boot progress bar.
+const INIT_STAGES: usize = 11 + memory::DIRECT_MAP as usize 
+    + memory::memtest::ENABLED as usize;
+
+/// The QEMU `isa-debug-exit` port, claimed once by `init_exit_port`.
+static QEMU_EXIT_PORT: OnceCell<ioport::PortRange> = OnceCell::uninit();
 
 // ---------------------------------------------------------------------------
 // PUBLIC FUNCTION DEFINITIONS
@@ -67,14 +104,25 @@ pub fn init(boot_info: &'static BootInfo) {
     vga_buffer::divider(b'-');
     println!("Initialising kernel:\n");
 
-    // Initialise GDT and IDT
-    print!("GDT... ");
-    gdt::init();
-    println!("complete");
+    boot_ui::log_boot_info(boot_info);
+
+    let mut progress = BootProgress::new(INIT_STAGES);
 
-    print!("IDT... ");
-    interrupts::init_idt();
-    println!("complete");
+    // Initialise GDT and IDT
+    progress.stage("GDT", || gdt::init());
+    progress.stage("IDT", || interrupts::init_idt());
+    progress.stage("SMEP/SMAP", || protection::init());
+
+    // Claim the I/O ports of the built in devices, before any of them are 
+    // used by interrupt handlers
+    progress.stage("I/O ports", || {
+        interrupts::init_ports();
+        interrupts::nmi::init_ports();
+        serial::init_ports();
+        task::keyboard::init_ports();
+        vga_buffer::init_ports();
+        init_exit_port();
+    });
 
     // Initialise the PICs and enable interrupts
     //
@@ -82,31 +130,95 @@ pub fn init(boot_info: &'static BootInfo) {
     //  The initialisation of a misconfigured ChainedPic object can cause 
     //  undefined behaviour. Safety is enforced through use only in the init 
     //  function.
-    print!("PICs... ");
-    unsafe { interrupts::PICS.lock().initialize() };
-    x86_64::instructions::interrupts::enable();
-    println!("complete, interrupts enabled");
+    progress.stage("PICs", || {
+        unsafe { interrupts::PICS.lock().initialize() };
+        x86_64::instructions::interrupts::enable();
+    });
 
     // ---- HEAP INITIALISATION ----
 
+    // Check the kernel is running in the expected virtual layout
+    progress.stage("Memory layout", || {
+        if let Err(e) = memory::layout::validate(boot_info) {
+            panic!("[MEM-ERROR] Invalid memory layout: {}", e);
+        }
+    });
+
     // Initialise the memory mapper
-    print!("Memory mapper... ");
-    let phys_offset = VirtAddr::new(boot_info.physical_memory_offset);
-    let mut mapper = unsafe { memory::init(phys_offset) };
-    println!("complete");
+    let mut mapper = progress.stage("Memory mapper", || 
+        unsafe { memory::init(boot_info) });
 
     // Initialise the frame allocator
-    print!("Frame allocator... ");
-    let mut frame_allocator = unsafe {
-        BootInfoFrameAllocator::init(&boot_info.memory_map)
+    let mut frame_allocator = progress.stage("Frame allocator", || 
+        unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) });
+
+    // The crash dump and memory test access physical memory through the 
+    // bootloader's direct map, so only run if there is one
+    let phys_offset = memory::physical_memory_offset(boot_info);
+
+    // Reserve the crash dump region before any frames are allocated
+    if let Some(phys_offset) = phys_offset {
+        progress.stage("Crash dump", || crashdump::init(
+            &boot_info.memory_map, phys_offset, &mut frame_allocator));
+    }
+
+    // Test memory and exclude bad frames, again before any are allocated
+    let memtest_report = match phys_offset {
+        Some(phys_offset) if memory::memtest::ENABLED => {
+            Some(progress.stage("Memory test", || memory::memtest::run(
+                &boot_info.memory_map, phys_offset, &mut frame_allocator)))
+        },
+        _ => None
     };
-    println!("complete");
 
-    print!("Kernel heap... ");
-    let heap_info = allocator::init_heap(
-        &mut mapper, &mut frame_allocator).expect("failed");
-    println!("complete");
-    println!("Kernel heap information: \n{:#?}", heap_info);
+    let heap_info = progress.stage("Kernel heap", || 
+        allocator::init_heap(&mut mapper, &mut frame_allocator)
+            .expect("failed"));
+
+    // Move the interrupt stacks off the small boot stacks
+    progress.stage("IST stacks", || 
+        gdt::init_stacks(&mut mapper, &mut frame_allocator)
+            .expect("failed"));
+
+    // Summarise the hardware now the memory map has been recorded
+    let hw_info = progress.stage("Hardware info", || hwinfo::collect());
+
+    progress.finish();
+
+    // Buffer serial input now the heap is up, enabling the escape commands
+    serial::escape::init();
+
+    // Allow interrupt handlers to defer work
+    task::workqueue::SYSTEM.init();
+
+    crashdump::print_previous();
+
+    println!("{}", hw_info);
+
+    if let Some(report) = memtest_report {
+        println!("{}", report);
+    }
+
+    if boot_ui::VERBOSE {
+        println!("{}", heap_info);
+        for &zone in memory::Zone::ALL.iter() {
+            println!("{:?} zone: {:?}", 
+                zone, frame_allocator.zone_stats(zone));
+        }
+        println!("{}", power::cpufreq::capabilities());
+        println!("{}", perf::capabilities());
+        println!("SMEP: {}, SMAP: {}", 
+            protection::smep_enabled(), protection::smap_enabled());
+    }
+
+    // Check the page tables now every boot time mapping has been made
+    let audit = memory::audit();
+    if !audit.is_clean() {
+        println!("[MEM-ERROR] {}", audit);
+    }
+    else if boot_ui::VERBOSE {
+        println!("{}", audit);
+    }
 
     // End of initialisations
     println!("\nInitialisation complete");
@@ -138,6 +250,12 @@ pub fn test_runner(tests: &[&dyn Fn()]) {
     serial::divider(b'-');
     serial_println!("\nTests complete\n");
 
+    // Dump the allocation sizes seen during the tests, to help tune the 
+    // block allocator.
+    if allocator::stats::total_allocations() > 0 {
+        serial_println!("{}", allocator::stats::report());
+    }
+
     // Exit from the tests (assuming QEMU)
     exit_qemu(QemuExitCode::Success);
 }
@@ -151,21 +269,31 @@ pub fn test_panic_handler(info: &PanicInfo) -> ! {
     serial::divider(b'-');
     serial_println!("PANIC DURING TEST!\n");
     serial_println!("{}", info);
+    symbols::print_backtrace();
     exit_qemu(QemuExitCode::Failed);
     
     halt_loop()
 }
 
+/// Claim the QEMU exit port, which must be done before `exit_qemu` can exit.
+/// 
+/// Called by `init`, test binaries which don't run `init` must call this 
+/// themselves.
+pub fn init_exit_port() {
+    match ioport::PortRange::claim(0xf4, 4, "qemu exit") {
+        Ok(port) => {
+            QEMU_EXIT_PORT.try_init_once(|| port)
+                .expect("init_exit_port must only be called once");
+        },
+        Err(e) => serial_println!("[QEMU-ERROR] Cannot claim exit port: {}", e)
+    }
+}
+
 /// Exit from a QEMU session by writing to the exit port.
 pub fn exit_qemu(exit_code: QemuExitCode) {
-    use x86_64::instructions::port::Port;
-    
-    // NOTE: USE OF UNSAFE
-    //  The write function is declared as unsafe because I/O port writing is
-    //  not guarenteed to avoid memory kerfuffles.
-    unsafe {
-        let mut port = Port::new(0xf4);
-        port.write(exit_code as u32);
+    match QEMU_EXIT_PORT.try_get() {
+        Ok(port) => port.write_u32(0, exit_code as u32),
+        Err(_) => serial_println!("[QEMU-ERROR] Exit port not claimed")
     }
 }
 
@@ -0,0 +1,164 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::_rdtsc;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use crate::interrupts;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The PIT input clock and the default divisor, giving the exact timer 
+/// interrupt frequency of `PIT_HZ / PIT_DIVISOR` (about 18.2 Hz).
+const PIT_HZ: u64 = 1_193_182;
+const PIT_DIVISOR: u64 = 65536;
+
+/// The tick the TSC calibration starts on, skipping the first tick which may
+/// be partial.
+const CALIBRATION_START_TICK: u64 = 1;
+
+/// The number of ticks the TSC is calibrated over, about a second.
+const CALIBRATION_TICKS: u64 = 18;
+
+/// The TSC value at the start of calibration.
+static TSC_START: AtomicU64 = AtomicU64::new(0);
+
+/// The calibrated TSC frequency, or 0 if it isn't calibrated yet.
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// The selected `ClockSource`.
+static SOURCE: AtomicU8 = AtomicU8::new(ClockSource::Best as u8);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Where timestamps come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ClockSource {
+    /// The TSC once it is calibrated, and the timer ticks before that.
+    Best = 0,
+
+    /// The timer ticks, with a resolution of about 55 ms.
+    Ticks = 1
+}
+
+/// A monotonic time since boot, in microseconds.
+/// 
+/// Time is counted from the first timer interrupt, so everything before the 
+/// PICs are initialised is at 0. There is no wall clock until an RTC driver 
+/// exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(pub u64);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:5}.{:06}", self.0 / 1_000_000, self.0 % 1_000_000)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the current time from the selected clock source.
+pub fn now() -> Timestamp {
+    let hz = TSC_HZ.load(Ordering::Relaxed);
+    if source() == ClockSource::Ticks || hz == 0 {
+        return ticks_to_timestamp(interrupts::ticks());
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  RDTSC is always available on x86_64.
+    let elapsed = unsafe { _rdtsc() }
+        .saturating_sub(TSC_START.load(Ordering::Relaxed));
+    let start = ticks_to_timestamp(CALIBRATION_START_TICK).0;
+    Timestamp(start + (elapsed as u128 * 1_000_000 / hz as u128) as u64)
+}
+
+/// Select the clock source used for timestamps.
+pub fn set_source(source: ClockSource) {
+    SOURCE.store(source as u8, Ordering::Relaxed);
+}
+
+/// Get the selected clock source.
+pub fn source() -> ClockSource {
+    match SOURCE.load(Ordering::Relaxed) {
+        1 => ClockSource::Ticks,
+        _ => ClockSource::Best
+    }
+}
+
+/// The calibrated TSC frequency, if calibration has finished.
+pub fn tsc_hz() -> Option<u64> {
+    match TSC_HZ.load(Ordering::Relaxed) {
+        0 => None,
+        hz => Some(hz)
+    }
+}
+
+/// The number of TSC cycles between timer ticks, once calibrated.
+pub fn tick_period_cycles() -> Option<u64> {
+    tsc_hz().map(|hz| hz * PIT_DIVISOR / PIT_HZ)
+}
+
+/// Convert a number of TSC cycles into nanoseconds, once calibrated.
+pub fn cycles_to_ns(cycles: u64) -> Option<u64> {
+    tsc_hz().map(|hz| (cycles as u128 * 1_000_000_000 / hz as u128) as u64)
+}
+
+/// Calibrate the TSC against the timer.
+/// 
+/// Should be called from the timer interrupt handler.
+pub(crate) fn timer_tick(ticks: u64) {
+    if ticks == CALIBRATION_START_TICK {
+        // NOTE: USE OF UNSAFE
+        //  RDTSC is always available on x86_64.
+        TSC_START.store(unsafe { _rdtsc() }, Ordering::Relaxed);
+    }
+    else if ticks == CALIBRATION_START_TICK + CALIBRATION_TICKS {
+        // NOTE: USE OF UNSAFE
+        //  RDTSC is always available on x86_64.
+        let elapsed = unsafe { _rdtsc() } 
+            - TSC_START.load(Ordering::Relaxed);
+        TSC_HZ.store(elapsed * PIT_HZ / (PIT_DIVISOR * CALIBRATION_TICKS), 
+            Ordering::Relaxed);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Convert a number of timer ticks into a timestamp.
+fn ticks_to_timestamp(ticks: u64) -> Timestamp {
+    Timestamp(ticks * PIT_DIVISOR * 1_000_000 / PIT_HZ)
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_timestamps() {
+    use crate::{serial_print, serial_println};
+    use alloc::format;
+    serial_print!("log::clock::timestamps ");
+
+    assert_eq!(format!("{}", Timestamp(1_234_567)), "    1.234567");
+    assert_eq!(ticks_to_timestamp(18).0 / 1000, 988);
+
+    // Both sources are monotonic
+    let first = now();
+    set_source(ClockSource::Ticks);
+    let ticks = now();
+    set_source(ClockSource::Best);
+    assert!(ticks <= now());
+    assert!(first <= now());
+
+    serial_println!("[ok]");
+}
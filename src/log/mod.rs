@@ -0,0 +1,143 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod clock;
+pub mod ratelimit;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use crate::interrupts::{self, TIMER_FREQUENCY_HZ};
+use crate::println;
+
+pub use ratelimit::{RateLimit, Verdict};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The default number of messages each call site can print per interval.
+const DEFAULT_BURST: u32 = 5;
+
+/// The default rate limit interval, in timer ticks.
+const DEFAULT_INTERVAL_TICKS: u64 = 5 * TIMER_FREQUENCY_HZ;
+
+/// Keyboard errors.
+pub static KBD: Target = Target::new("KBD");
+
+/// Serial port errors.
+pub static SERIAL: Target = Target::new("SERIAL");
+
+/// Memory management errors.
+pub static MEM: Target = Target::new("MEM");
+
+/// Failed `kassert!`s which don't panic.
+pub static ASSERT: Target = Target::new("ASSERT");
+
+/// Stack space checks.
+pub static STACK: Target = Target::new("STACK");
+
+// ---------------------------------------------------------------------------
+// MACRO DEFINITIONS
+// ---------------------------------------------------------------------------
+
+/// Print a rate limited error for a log target, stamped with the time since
+/// boot.
+/// 
+/// Each use of the macro is limited separately, to its target's burst per 
+/// interval. Suppressed messages are reported as repeats of the previous 
+/// message the next time one from the same use is printed.
+#[macro_export]
+macro_rules! log_error {
+    ($target:expr, $($arg:tt)*) => {{
+        static LIMIT: $crate::log::RateLimit = $crate::log::RateLimit::new();
+        $crate::log::_log_error(&$target, &LIMIT, format_args!($($arg)*));
+    }};
+}
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A source of log messages, named in the message prefix, with its own rate 
+/// limit configuration.
+pub struct Target {
+    name: &'static str,
+    burst: AtomicU32,
+    interval_ticks: AtomicU64
+}
+
+impl Target {
+
+    /// Create a new target with the default rate limit.
+    pub const fn new(name: &'static str) -> Target {
+        Target {
+            name,
+            burst: AtomicU32::new(DEFAULT_BURST),
+            interval_ticks: AtomicU64::new(DEFAULT_INTERVAL_TICKS)
+        }
+    }
+
+    /// The name of the target.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Allow `burst` messages from each call site every `interval_ticks` 
+    /// timer ticks. An interval of 0 disables rate limiting.
+    pub fn configure(&self, burst: u32, interval_ticks: u64) {
+        self.burst.store(burst, Ordering::Relaxed);
+        self.interval_ticks.store(interval_ticks, Ordering::Relaxed);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+#[doc(hidden)]
+pub fn _log_error(target: &Target, limit: &RateLimit, args: fmt::Arguments) {
+    let verdict = limit.check(
+        target.burst.load(Ordering::Relaxed),
+        target.interval_ticks.load(Ordering::Relaxed),
+        interrupts::ticks());
+
+    if let Verdict::Print { suppressed } = verdict {
+        let timestamp = clock::now();
+        if suppressed > 0 {
+            println!("[{}] [{}-ERROR] Previous message repeated {} times", 
+                timestamp, target.name, suppressed);
+        }
+        println!("[{}] [{}-ERROR] {}", timestamp, target.name, args);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_log_error_limited() {
+    use crate::{serial_print, serial_println};
+    serial_print!("log::log_error_limited ");
+
+    static TEST: Target = Target::new("TEST");
+    static LIMIT: RateLimit = RateLimit::new();
+
+    // Only the first message of a burst of 1 is printed, whatever the time
+    TEST.configure(1, u64::MAX);
+    for i in 0..10 {
+        _log_error(&TEST, &LIMIT, format_args!("test message {}", i));
+    }
+    assert_eq!(LIMIT.check(1, u64::MAX, interrupts::ticks()), 
+        Verdict::Suppress);
+
+    // The macro can be used with any target
+    crate::log_error!(TEST, "macro message");
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,114 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Whether a rate limited message should be printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Print the message, after reporting the number of messages suppressed 
+    /// since the last one printed.
+    Print { suppressed: u32 },
+
+    /// Drop the message.
+    Suppress
+}
+
+/// A limit of `burst` messages in each window of time, which can be checked 
+/// from interrupt handlers.
+/// 
+/// Messages over the limit are counted, and the count is reported with the 
+/// first message printed in a later window.
+pub struct RateLimit {
+    window_start: AtomicU64,
+    printed: AtomicU32,
+    suppressed: AtomicU32
+}
+
+impl RateLimit {
+
+    /// Create a new rate limit, with no messages printed.
+    pub const fn new() -> RateLimit {
+        RateLimit {
+            window_start: AtomicU64::new(0),
+            printed: AtomicU32::new(0),
+            suppressed: AtomicU32::new(0)
+        }
+    }
+
+    /// Check whether a message at time `now` should be printed, when at most 
+    /// `burst` messages are allowed every `interval`.
+    /// 
+    /// An `interval` of 0 disables the limit.
+    pub fn check(&self, burst: u32, interval: u64, now: u64) -> Verdict {
+        if interval == 0 {
+            return Verdict::Print { 
+                suppressed: self.suppressed.swap(0, Ordering::Relaxed) 
+            };
+        }
+
+        // Start a new window if the current one has ended. If another caller
+        // starts it first this message just counts towards it.
+        let start = self.window_start.load(Ordering::Relaxed);
+        if now.wrapping_sub(start) >= interval 
+            && self.window_start.compare_exchange(
+                start, now, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            self.printed.store(1, Ordering::Relaxed);
+            return Verdict::Print { 
+                suppressed: self.suppressed.swap(0, Ordering::Relaxed) 
+            };
+        }
+
+        if self.printed.fetch_add(1, Ordering::Relaxed) < burst {
+            Verdict::Print { suppressed: 0 }
+        }
+        else {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            Verdict::Suppress
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_suppress() {
+        let limit = RateLimit::new();
+        for _ in 0..3 {
+            assert_eq!(limit.check(3, 10, 20), 
+                Verdict::Print { suppressed: 0 });
+        }
+        assert_eq!(limit.check(3, 10, 21), Verdict::Suppress);
+        assert_eq!(limit.check(3, 10, 29), Verdict::Suppress);
+
+        // The next window reports what was dropped
+        assert_eq!(limit.check(3, 10, 30), Verdict::Print { suppressed: 2 });
+        assert_eq!(limit.check(3, 10, 31), Verdict::Print { suppressed: 0 });
+    }
+
+    #[test]
+    fn zero_interval_is_unlimited() {
+        let limit = RateLimit::new();
+        for now in 0..100 {
+            assert_eq!(limit.check(1, 0, now), 
+                Verdict::Print { suppressed: 0 });
+        }
+    }
+}
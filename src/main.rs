@@ -9,8 +9,9 @@
 // ---------------------------------------------------------------------------
 
 use core::panic::PanicInfo;
-use scos::println;
-use scos::task::{executor::Executor, Task, keyboard};
+use scos::{println, allocator, kassert, selftest};
+use scos::memory::pressure;
+use scos::task::{executor::Executor, Task, Priority, keyboard, workqueue};
 use bootloader::{BootInfo, entry_point};
 
 #[cfg(not(test))]
@@ -37,7 +38,18 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 
     // Create and run task executor
     let mut executor = Executor::new();
+    executor.spawn(Task::with_priority(
+        workqueue::SYSTEM.worker(), Priority::High));
     executor.spawn(Task::new(keyboard::print_keypresses()));
+    executor.spawn(Task::new(allocator::zero_free_blocks()));
+    executor.spawn(Task::new(pressure::monitor()));
+    executor.spawn(Task::new(selftest::runner()));
+    if kassert::DEBUG_CHECKS {
+        executor.spawn(Task::new(kassert::checker()));
+    }
+    if allocator::redzone::ENABLED {
+        executor.spawn(Task::new(allocator::redzone::scrubber()));
+    }
     executor.run();
 }
 
@@ -48,11 +60,23 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    // Swap to the minimal IDT so faults while reporting the panic can't 
+    // recurse through possibly broken handlers.
+    scos::interrupts::load_emergency_idt();
+
+    // Save the panic so it can be reported on the next boot. This is done 
+    // first since it takes no locks, so it works even if printing deadlocks.
+    scos::crashdump::write(info);
+
     // Print a divider to clearly separate this from anything else
     vga_buffer::divider(b'-');
     println!("PANIC!\n");
     println!("{}", info);
 
+    // The backtrace goes to serial only, since it can be longer than the 
+    // screen
+    scos::symbols::print_backtrace();
+
     scos::halt_loop()
 }
 
@@ -0,0 +1,375 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use x86_64::{
+    VirtAddr, PhysAddr,
+    structures::paging::{PageTable, PageTableFlags},
+    registers::control::Cr3
+};
+use crate::allocator::{HEAP_START, HEAP_SIZE};
+use super::layout;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The maximum number of violations recorded in a report, any more are only 
+/// counted.
+pub const MAX_RECORDED: usize = 8;
+
+/// The number of page table levels.
+const LEVELS: u8 = 4;
+
+/// The stack needed by each level of `walk`.
+const WALK_STACK: u64 = 1024;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A kind of page table invariant violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// A page is accessible from user mode.
+    UserAccessible,
+
+    /// A page is both writable and executable.
+    WritableExecutable,
+
+    /// A physical frame in the memory map isn't mapped by the direct map, or
+    /// is mapped to the wrong frame.
+    DirectMapMissing,
+
+    /// A page of the heap isn't mapped.
+    HeapUnmapped,
+
+    /// A page in the heap window but outside the heap is mapped.
+    OutsideHeap
+}
+
+/// A single violation found by the audit.
+#[derive(Debug, Clone, Copy)]
+pub struct Violation {
+    pub kind: ViolationKind,
+
+    /// The virtual address of the page.
+    pub addr: VirtAddr
+}
+
+/// The results of a page table audit.
+pub struct AuditReport {
+    /// `false` if the page tables couldn't be walked, because there is no 
+    /// direct map to read them through.
+    pub walked: bool,
+
+    /// The number of mapped pages (of any size) checked.
+    pub pages: usize,
+
+    /// The total number of violations found.
+    pub violations: usize,
+
+    /// `true` if parts of the tables weren't walked because the stack was 
+    /// running out, so there may be violations which weren't found.
+    pub incomplete: bool,
+
+    /// The first violations found.
+    pub recorded: [Option<Violation>; MAX_RECORDED]
+}
+
+impl AuditReport {
+
+    /// Returns `true` if the whole audit ran and no violations were found.
+    pub fn is_clean(&self) -> bool {
+        self.violations == 0 && !self.incomplete
+    }
+
+    /// Record a violation.
+    fn record(&mut self, kind: ViolationKind, addr: VirtAddr) {
+        if let Some(slot) = self.recorded.iter_mut().find(|v| v.is_none()) {
+            *slot = Some(Violation { kind, addr });
+        }
+        self.violations += 1;
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.walked {
+            return write!(f, 
+                "Page table audit skipped, no direct map to read tables");
+        }
+
+        write!(f, "Page table audit: {} pages, {} violations", 
+            self.pages, self.violations)?;
+        if self.incomplete {
+            write!(f, " (incomplete, stack ran low)")?;
+        }
+        for violation in self.recorded.iter().flatten() {
+            write!(f, "\n    {:?} at {:#x}", 
+                violation.kind, violation.addr.as_u64())?;
+        }
+        if self.violations > MAX_RECORDED {
+            write!(f, "\n    ... and {} more", 
+                self.violations - MAX_RECORDED)?;
+        }
+        Ok(())
+    }
+}
+
+/// The access allowed to a page, combined from every level of the tables.
+#[derive(Clone, Copy)]
+struct Access {
+    user: bool,
+    writable: bool,
+    executable: bool
+}
+
+impl Access {
+
+    /// Restrict the access by the flags of a table entry.
+    fn restrict(self, flags: PageTableFlags) -> Access {
+        Access {
+            user: self.user && flags.contains(PageTableFlags::USER_ACCESSIBLE),
+            writable: self.writable 
+                && flags.contains(PageTableFlags::WRITABLE),
+            executable: self.executable 
+                && !flags.contains(PageTableFlags::NO_EXECUTE)
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Audit the active page tables, checking that:
+/// 
+/// - no page is accessible from user mode,
+/// - no page is both writable and executable,
+/// - the direct map covers the memory map,
+/// - the heap is mapped, and nothing else is mapped in its window.
+/// 
+/// The bootloader maps the direct map without `NO_EXECUTE`, and it can't be 
+/// changed without remapping all of physical memory, so the direct map is 
+/// excluded from the writable and executable check. The direct map is only 
+/// checked at the first and last frame of each memory map region.
+/// 
+/// Must be called after `memory::init`.
+pub fn audit() -> AuditReport {
+    audit_with_stack(WALK_STACK)
+}
+
+/// Returns whether the page containing `addr` is accessible from user mode,
+/// or `None` if it isn't mapped or the tables can't be read.
+/// 
+/// This is used by the page fault handler to tell SMEP and SMAP violations 
+/// from other protection faults.
+pub fn is_user_page(addr: VirtAddr) -> Option<bool> {
+    let phys_offset = super::boot_memory()?.1?;
+    let (l4_frame, _) = Cr3::read();
+    let mut table_addr = l4_frame.start_address();
+    let mut user = true;
+
+    for level in (1..=LEVELS).rev() {
+        let index = (addr.as_u64() >> entry_shift(level)) as usize & 0x1ff;
+        let entry = &table_at(phys_offset, table_addr)[index];
+        let flags = entry.flags();
+
+        if !flags.contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        user &= flags.contains(PageTableFlags::USER_ACCESSIBLE);
+        if level == 1 || flags.contains(PageTableFlags::HUGE_PAGE) {
+            return Some(user);
+        }
+        table_addr = entry.addr();
+    }
+
+    None
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Run the audit, giving up on parts of the tables if less than `walk_stack`
+/// bytes of stack are left for each level of the walk.
+fn audit_with_stack(walk_stack: u64) -> AuditReport {
+    let mut report = AuditReport {
+        walked: false,
+        pages: 0,
+        violations: 0,
+        incomplete: false,
+        recorded: [None; MAX_RECORDED]
+    };
+
+    let (memory_map, phys_offset) = match super::boot_memory() {
+        Some((memory_map, Some(phys_offset))) => (memory_map, phys_offset),
+        _ => return report
+    };
+    report.walked = true;
+
+    // Check every mapped page
+    let (l4_frame, _) = Cr3::read();
+    let access = Access { user: true, writable: true, executable: true };
+    walk(&mut report, phys_offset, l4_frame.start_address(), LEVELS, 0, 
+        access, walk_stack);
+
+    // Check the direct map covers the memory map
+    for region in memory_map.iter() {
+        let first = region.range.start_addr();
+        let last = region.range.end_addr().saturating_sub(4096).max(first);
+        for &phys in [first, last].iter() {
+            let virt = phys_offset + phys;
+            if translate(phys_offset, virt) != Some(PhysAddr::new(phys)) {
+                report.record(ViolationKind::DirectMapMissing, virt);
+            }
+        }
+    }
+
+    // Check every page of the heap is mapped
+    let mut addr = HEAP_START as u64;
+    while addr < (HEAP_START + HEAP_SIZE) as u64 {
+        let virt = VirtAddr::new(addr);
+        if translate(phys_offset, virt).is_none() {
+            report.record(ViolationKind::HeapUnmapped, virt);
+        }
+        addr += 4096;
+    }
+
+    report
+}
+
+/// Check every page mapped by the table at `table_addr`, which maps the 
+/// addresses from `base` at the given `level`.
+fn walk(
+    report: &mut AuditReport, 
+    phys_offset: VirtAddr,
+    table_addr: PhysAddr,
+    level: u8,
+    base: u64,
+    access: Access,
+    walk_stack: u64
+) {
+    // The audit can run from deep in a failing path, so give up on the 
+    // subtree rather than overflow the stack
+    if crate::stack::check(walk_stack).is_err() {
+        report.incomplete = true;
+        return;
+    }
+
+    let table = table_at(phys_offset, table_addr);
+
+    for (i, entry) in table.iter().enumerate() {
+        let flags = entry.flags();
+        if !flags.contains(PageTableFlags::PRESENT) {
+            continue;
+        }
+
+        let addr = sign_extend(base | (i as u64) << entry_shift(level));
+        let access = access.restrict(flags);
+
+        if level == 1 || flags.contains(PageTableFlags::HUGE_PAGE) {
+            report.pages += 1;
+            check_page(report, VirtAddr::new(addr), access);
+        }
+        else {
+            walk(report, phys_offset, entry.addr(), level - 1, addr, access, 
+                walk_stack);
+        }
+    }
+}
+
+/// Check a single mapped page.
+fn check_page(report: &mut AuditReport, addr: VirtAddr, access: Access) {
+    let virt = addr.as_u64();
+
+    if access.user {
+        report.record(ViolationKind::UserAccessible, addr);
+    }
+    if access.writable && access.executable 
+        && !layout::DIRECT_MAP.contains(virt) {
+        report.record(ViolationKind::WritableExecutable, addr);
+    }
+
+    let heap_end = ((HEAP_START + HEAP_SIZE + 4095) & !4095) as u64;
+    if layout::HEAP.contains(virt) && virt >= heap_end {
+        report.record(ViolationKind::OutsideHeap, addr);
+    }
+}
+
+/// Translate a virtual address by reading the tables through the direct map.
+fn translate(phys_offset: VirtAddr, addr: VirtAddr) -> Option<PhysAddr> {
+    let (l4_frame, _) = Cr3::read();
+    let mut table_addr = l4_frame.start_address();
+
+    for level in (1..=LEVELS).rev() {
+        let index = (addr.as_u64() >> entry_shift(level)) as usize & 0x1ff;
+        let entry = &table_at(phys_offset, table_addr)[index];
+        let flags = entry.flags();
+
+        if !flags.contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        if level == 1 || flags.contains(PageTableFlags::HUGE_PAGE) {
+            let page_mask = (1u64 << entry_shift(level)) - 1;
+            return Some(entry.addr() + (addr.as_u64() & page_mask));
+        }
+        table_addr = entry.addr();
+    }
+
+    None
+}
+
+/// Get a reference to the page table at the given physical address.
+fn table_at(phys_offset: VirtAddr, addr: PhysAddr) -> &'static PageTable {
+    let ptr: *const PageTable = (phys_offset + addr.as_u64()).as_ptr();
+
+    // NOTE: USE OF UNSAFE
+    //  The address comes from CR3 or a present table entry, so is a page 
+    //  table, and all of physical memory is mapped at `phys_offset`. The 
+    //  table is only read.
+    unsafe { &*ptr }
+}
+
+/// The shift of the address bits indexing a table at the given level.
+fn entry_shift(level: u8) -> u64 {
+    12 + 9 * (level as u64 - 1)
+}
+
+/// Sign extend a 48 bit address to make it canonical.
+fn sign_extend(addr: u64) -> u64 {
+    ((addr << 16) as i64 >> 16) as u64
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_boot_tables_pass_audit() {
+    use crate::{serial_print, serial_println};
+    serial_print!("memory::audit::boot_tables_pass_audit ");
+    let report = audit();
+    assert!(report.walked || !super::DIRECT_MAP);
+    assert!(report.is_clean(), "{}", report);
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn test_low_stack_marks_incomplete() {
+    use crate::{serial_print, serial_println};
+    serial_print!("memory::audit::low_stack_marks_incomplete ");
+
+    // Require more stack than any stack has so the walk gives up at once
+    let report = audit_with_stack(u64::MAX / 2);
+    if report.walked {
+        assert!(report.incomplete);
+        assert_eq!(report.pages, 0);
+        assert!(!report.is_clean());
+    }
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,208 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use bootloader::BootInfo;
+use crate::allocator::{HEAP_START, HEAP_SIZE};
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A window of the kernel's virtual address space.
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    /// The name of the region, used in errors.
+    pub name: &'static str,
+
+    /// The first address of the region.
+    pub start: u64,
+
+    /// The address after the end of the region.
+    pub end: u64
+}
+
+impl Region {
+
+    /// Returns `true` if `addr` is inside the region.
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+
+    /// The size of the region in bytes.
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Errors found when validating the layout.
+#[derive(Debug, Clone, Copy)]
+pub enum LayoutError {
+    /// A region isn't page aligned, or isn't in the higher half.
+    Invalid(&'static str),
+
+    /// Two regions overlap, or aren't in address order.
+    Overlap(&'static str, &'static str),
+
+    /// Something is outside the region which should contain it.
+    Outside { what: &'static str, addr: u64, region: &'static str },
+
+    /// Physical memory extends beyond the end of the direct map.
+    DirectMapTooSmall(u64)
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Invalid(name) => 
+                write!(f, "{} region is invalid", name),
+            LayoutError::Overlap(a, b) => 
+                write!(f, "{} region overlaps {} region", a, b),
+            LayoutError::Outside { what, addr, region } => 
+                write!(f, "{} at {:#x} is outside the {} region", 
+                    what, addr, region),
+            LayoutError::DirectMapTooSmall(end) => 
+                write!(f, "physical memory up to {:#x} can't be direct mapped",
+                    end)
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The start of the higher half, all kernel regions lie above this.
+pub const HIGHER_HALF_START: u64 = 0xffff_8000_0000_0000;
+
+/// All of physical memory, mapped by the bootloader at this offset.
+/// 
+/// This must match `physical-memory-offset` in `Cargo.toml`. It is unused 
+/// with the `recursive-page-table` feature.
+pub const DIRECT_MAP: Region = Region {
+    name: "direct map",
+    start: 0xffff_8000_0000_0000,
+    end: 0xffff_c000_0000_0000
+};
+
+/// The kernel heap.
+pub const HEAP: Region = Region {
+    name: "heap",
+    start: 0xffff_c000_0000_0000,
+    end: 0xffff_c000_4000_0000
+};
+
+/// Per-CPU data.
+pub const PER_CPU: Region = Region {
+    name: "per-cpu",
+    start: 0xffff_c800_0000_0000,
+    end: 0xffff_c800_4000_0000
+};
+
+/// Interrupt stacks, each with an unmapped guard page below it.
+pub const IST_STACKS: Region = Region {
+    name: "ist stacks",
+    start: 0xffff_c900_0000_0000,
+    end: 0xffff_c900_0100_0000
+};
+
+/// Mappings of device memory.
+pub const MMIO: Region = Region {
+    name: "mmio",
+    start: 0xffff_d000_0000_0000,
+    end: 0xffff_d100_0000_0000
+};
+
+/// The kernel stack, set up by the bootloader.
+/// 
+/// This must match `kernel-stack-address` in `Cargo.toml`.
+pub const KERNEL_STACK: Region = Region {
+    name: "kernel stack",
+    start: 0xffff_ff00_0000_0000,
+    end: 0xffff_ff00_4000_0000
+};
+
+/// The number of pages in the kernel stack, including the unmapped guard page
+/// at the bottom.
+/// 
+/// This must match `kernel-stack-size` in `Cargo.toml`.
+pub const KERNEL_STACK_PAGES: u64 = 512;
+
+/// The kernel text and data, in the top 2 GiB for the `kernel` code model.
+/// 
+/// This must match the image base in the target definition. The last page is 
+/// excluded so the end doesn't overflow.
+pub const KERNEL_IMAGE: Region = Region {
+    name: "kernel image",
+    start: 0xffff_ffff_8000_0000,
+    end: 0xffff_ffff_ffff_f000
+};
+
+/// All regions, in address order.
+pub const REGIONS: [Region; 7] = [
+    DIRECT_MAP, HEAP, PER_CPU, IST_STACKS, MMIO, KERNEL_STACK, KERNEL_IMAGE
+];
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Check the regions are consistent and that the kernel is running in the 
+/// layout they describe.
+pub fn validate(boot_info: &BootInfo) -> Result<(), LayoutError> {
+
+    // Check the regions themselves
+    for region in REGIONS.iter() {
+        if region.start % 4096 != 0 || region.end % 4096 != 0 
+            || region.start < HIGHER_HALF_START || region.end <= region.start {
+            return Err(LayoutError::Invalid(region.name));
+        }
+    }
+    for pair in REGIONS.windows(2) {
+        if pair[0].end > pair[1].start {
+            return Err(LayoutError::Overlap(pair[0].name, pair[1].name));
+        }
+    }
+
+    // Check the kernel was loaded where it was linked, and is running on the
+    // stack the bootloader was configured to create
+    let stack_marker = 0u8;
+    check_within("kernel code", validate as *const () as u64, &KERNEL_IMAGE)?;
+    check_within("stack", &stack_marker as *const u8 as u64, &KERNEL_STACK)?;
+
+    // Check the heap window can hold the heap
+    check_within("heap start", HEAP_START as u64, &HEAP)?;
+    check_within("heap end", (HEAP_START + HEAP_SIZE - 1) as u64, &HEAP)?;
+
+    // Check all of physical memory fits in the direct map
+    if let Some(offset) = super::physical_memory_offset(boot_info) {
+        check_within("physical memory offset", offset.as_u64(), &DIRECT_MAP)?;
+
+        let phys_end = boot_info.memory_map.iter()
+            .map(|r| r.range.end_addr())
+            .max()
+            .unwrap_or(0);
+        if offset.as_u64() - DIRECT_MAP.start + phys_end > DIRECT_MAP.size() {
+            return Err(LayoutError::DirectMapTooSmall(phys_end));
+        }
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Check that `addr` is inside `region`.
+fn check_within(what: &'static str, addr: u64, region: &Region) 
+    -> Result<(), LayoutError> {
+
+    if region.contains(addr) {
+        Ok(())
+    }
+    else {
+        Err(LayoutError::Outside { what, addr, region: region.name })
+    }
+}
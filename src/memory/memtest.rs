@@ -0,0 +1,180 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use x86_64::{PhysAddr, VirtAddr};
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use super::BootInfoFrameAllocator;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// Whether the boot-time memory test runs, enabled by the `memtest` feature.
+pub const ENABLED: bool = cfg!(feature = "memtest");
+
+// The test reads every frame through the bootloader's physical memory map
+#[cfg(all(feature = "memtest", feature = "recursive-page-table"))]
+compile_error!("the `memtest` feature requires the physical memory map, so \
+    can't be used with `recursive-page-table`");
+
+/// The maximum number of bad ranges recorded by a test.
+pub const MAX_BAD_RANGES: usize = 4;
+
+/// The size of a physical frame.
+const FRAME_SIZE: u64 = 4096;
+
+/// The fixed patterns written to every word of each frame. An address 
+/// pattern is tested after these to catch address line faults.
+const PATTERNS: [u64; 2] = [0x5555_5555_5555_5555, 0xaaaa_aaaa_aaaa_aaaa];
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The results of a memory test.
+pub struct MemtestReport {
+    /// The number of frames tested.
+    pub tested_frames: usize,
+
+    /// The number of frames which failed.
+    pub bad_frames: usize,
+
+    /// The bad physical ranges, merged where they're contiguous.
+    pub bad_ranges: [Option<(PhysAddr, PhysAddr)>; MAX_BAD_RANGES],
+
+    /// Set if more bad ranges were found than could be recorded. Frames in 
+    /// the unrecorded ranges are still handed out by the frame allocator.
+    pub overflowed: bool
+}
+
+impl MemtestReport {
+
+    /// Record a bad frame, merging it into the previous range if they're 
+    /// contiguous.
+    fn add_bad_frame(&mut self, start: u64) {
+        self.bad_frames += 1;
+        let end = start + FRAME_SIZE;
+
+        for range in self.bad_ranges.iter_mut() {
+            match range {
+                Some((_, range_end)) if range_end.as_u64() == start => {
+                    *range_end = PhysAddr::new(end);
+                    return;
+                },
+                Some(_) => continue,
+                None => {
+                    *range = Some((PhysAddr::new(start), PhysAddr::new(end)));
+                    return;
+                }
+            }
+        }
+
+        self.overflowed = true;
+    }
+}
+
+impl fmt::Display for MemtestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Memory test: {} frames tested, {} bad", 
+            self.tested_frames, self.bad_frames)?;
+        for (start, end) in self.bad_ranges.iter().flatten() {
+            writeln!(f, "    bad: {:#012x} - {:#012x}", 
+                start.as_u64(), end.as_u64())?;
+        }
+        if self.overflowed {
+            writeln!(f, "[MEM-ERROR] Too many bad ranges, some are still \
+                in use")?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Test every usable frame with pattern writes and reads, reserving any bad 
+/// ranges from the frame allocator.
+/// 
+/// This destroys the contents of every usable frame so must be run before 
+/// any frames are allocated. Reserved frames, such as the crash dump region,
+/// are skipped.
+pub fn run(
+    memory_map: &MemoryMap,
+    phys_offset: VirtAddr,
+    frame_allocator: &mut BootInfoFrameAllocator
+) -> MemtestReport {
+    let mut report = MemtestReport {
+        tested_frames: 0,
+        bad_frames: 0,
+        bad_ranges: [None; MAX_BAD_RANGES],
+        overflowed: false
+    };
+
+    let frames = memory_map.iter()
+        .filter(|r| r.region_type == MemoryRegionType::Usable)
+        .flat_map(|r| (r.range.start_addr()..r.range.end_addr())
+            .step_by(FRAME_SIZE as usize));
+
+    for addr in frames {
+        if frame_allocator.is_reserved(PhysAddr::new(addr)) {
+            continue;
+        }
+
+        // NOTE: USE OF UNSAFE
+        //  The frame is usable, unreserved and not yet allocated, so nothing
+        //  else is using it.
+        let ok = unsafe { test_frame(phys_offset + addr, addr) };
+        report.tested_frames += 1;
+        if !ok {
+            report.add_bad_frame(addr);
+        }
+    }
+
+    for (start, end) in report.bad_ranges.iter().flatten() {
+        if !frame_allocator.try_reserve(*start, *end) {
+            report.overflowed = true;
+        }
+    }
+
+    report
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Test a single frame mapped at `virt`, returning `false` if any word 
+/// doesn't read back as written.
+/// 
+/// NOTE: UNSAFE
+///     The caller must guarentee the frame is mapped at `virt` and unused.
+unsafe fn test_frame(virt: VirtAddr, phys: u64) -> bool {
+    let words = (FRAME_SIZE / 8) as usize;
+    let ptr = virt.as_mut_ptr::<u64>();
+
+    for &pattern in PATTERNS.iter() {
+        for i in 0..words {
+            ptr.add(i).write_volatile(pattern);
+        }
+        for i in 0..words {
+            if ptr.add(i).read_volatile() != pattern {
+                return false;
+            }
+        }
+    }
+
+    // Write each word's own physical address, so aliased addresses are found
+    for i in 0..words {
+        ptr.add(i).write_volatile(phys + 8 * i as u64);
+    }
+    for i in 0..words {
+        if ptr.add(i).read_volatile() != phys + 8 * i as u64 {
+            return false;
+        }
+    }
+
+    true
+}
@@ -0,0 +1,390 @@
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use x86_64::{
+    VirtAddr, PhysAddr,
+    structures::paging::{
+        PageTable, 
+        Size4KiB,
+        PhysFrame, 
+        UnusedPhysFrame,
+        FrameAllocator,
+        MapperAllSizes},
+};
+use bootloader::BootInfo;
+use conquer_once::spin::OnceCell;
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+
+#[cfg(not(feature = "recursive-page-table"))]
+use x86_64::{
+    structures::paging::OffsetPageTable,
+    registers::control::Cr3
+};
+
+#[cfg(feature = "recursive-page-table")]
+use x86_64::structures::paging::RecursivePageTable;
+
+#[cfg(not(any(
+    feature = "physical-memory-map", feature = "recursive-page-table")))]
+compile_error!("one of the `physical-memory-map` or `recursive-page-table` \
+    features must be enabled");
+
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod audit;
+pub mod layout;
+pub mod memtest;
+pub mod pressure;
+
+pub use audit::audit;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Whether the bootloader maps all of physical memory into the kernel's 
+/// address space (at `layout::DIRECT_MAP`), which is the case unless the 
+/// `recursive-page-table` feature is enabled.
+/// 
+/// Without this map physical memory can only be accessed by mapping it 
+/// through the page tables.
+pub const DIRECT_MAP: bool = cfg!(not(feature = "recursive-page-table"));
+
+/// The page table mapper used by the kernel.
+/// 
+/// By default the bootloader maps all physical memory at an offset, through 
+/// which the page tables are accessed. With the `recursive-page-table` 
+/// feature the bootloader instead maps the level 4 table recursively.
+#[cfg(not(feature = "recursive-page-table"))]
+pub type KernelMapper = OffsetPageTable<'static>;
+
+/// The page table mapper used by the kernel.
+/// 
+/// By default the bootloader maps all physical memory at an offset, through 
+/// which the page tables are accessed. With the `recursive-page-table` 
+/// feature the bootloader instead maps the level 4 table recursively.
+#[cfg(feature = "recursive-page-table")]
+pub type KernelMapper = RecursivePageTable<'static>;
+
+/// The memory map and physical memory offset from the boot info, recorded by 
+/// `init`.
+static BOOT_MEMORY: OnceCell<(&'static MemoryMap, Option<VirtAddr>)> = 
+    OnceCell::uninit();
+
+/// The maximum number of physical ranges which can be reserved from the frame
+/// allocator.
+const MAX_RESERVED_RANGES: usize = 8;
+
+/// The end of the ISA DMA zone, devices using ISA DMA can only address the 
+/// first 16 MiB of physical memory.
+const DMA_ZONE_END: u64 = 16 * 1024 * 1024;
+
+/// The end of the 32-bit DMA zone.
+const DMA32_ZONE_END: u64 = 4 * 1024 * 1024 * 1024;
+
+/// A physical memory zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// Frames below 16 MiB, for ISA DMA.
+    Dma,
+
+    /// Frames between 16 MiB and 4 GiB, for 32-bit devices.
+    Dma32,
+
+    /// Frames above 4 GiB.
+    Normal
+}
+
+impl Zone {
+
+    /// All zones, from lowest to highest.
+    pub const ALL: [Zone; 3] = [Zone::Dma, Zone::Dma32, Zone::Normal];
+
+    /// The physical address range covered by the zone.
+    pub fn range(self) -> (u64, u64) {
+        match self {
+            Zone::Dma => (0, DMA_ZONE_END),
+            Zone::Dma32 => (DMA_ZONE_END, DMA32_ZONE_END),
+            Zone::Normal => (DMA32_ZONE_END, u64::max_value())
+        }
+    }
+
+    /// The index of the zone into per-zone arrays.
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Statistics for a single physical memory zone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZoneStats {
+    /// The number of usable frames in the zone.
+    pub total: usize,
+
+    /// The number of frames allocated from the zone.
+    pub allocated: usize
+}
+
+impl ZoneStats {
+
+    /// The number of frames still free in the zone.
+    pub fn free(&self) -> usize {
+        self.total - self.allocated
+    }
+}
+
+/// A `FrameAllocator` that returns usable frames from the bootloader's memory
+/// map.
+/// 
+/// Frames are split into zones by physical address. Drivers which need low 
+/// frames request them with `allocate_frame_in`, while `allocate_frame` 
+/// prefers the highest zone to keep the low zones free for them.
+pub struct BootInfoFrameAllocator {
+    memory_map: &'static MemoryMap,
+    counted: bool,
+    stats: [ZoneStats; 3],
+    reserved: [Option<(u64, u64)>; MAX_RESERVED_RANGES]
+}
+
+impl BootInfoFrameAllocator {
+
+    /// Initialise the allocator.
+    /// 
+    /// NOTE: UNSAFE
+    ///     This function is unsafe since the caller must ensure the memory map
+    ///     is valid.
+    pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
+        BootInfoFrameAllocator {
+            memory_map,
+            counted: false,
+            stats: [ZoneStats::default(); 3],
+            reserved: [None; MAX_RESERVED_RANGES]
+        }
+    }
+
+    /// Reserve the physical range `start..end` so that none of its frames are
+    /// handed out by the allocator.
+    /// 
+    /// This must be called before any frames are allocated, since reserving a
+    /// range changes which frame each allocation index refers to. Panics if 
+    /// frames have already been allocated or too many ranges are reserved.
+    pub fn reserve(&mut self, start: PhysAddr, end: PhysAddr) {
+        assert!(self.try_reserve(start, end), 
+            "[MEM-ERROR] Too many reserved physical ranges");
+    }
+
+    /// Reserve the physical range `start..end`, returning `false` if there 
+    /// are no free reservation slots.
+    /// 
+    /// As with `reserve` this panics if frames have already been allocated.
+    pub fn try_reserve(&mut self, start: PhysAddr, end: PhysAddr) -> bool {
+        assert!(!self.counted, 
+            "[MEM-ERROR] Cannot reserve frames after allocation has started");
+
+        match self.reserved.iter_mut().find(|r| r.is_none()) {
+            Some(slot) => {
+                *slot = Some((start.as_u64(), end.as_u64()));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Returns `true` if the frame starting at `addr` has been reserved.
+    pub fn is_reserved(&self, addr: PhysAddr) -> bool {
+        is_reserved(&self.reserved, addr.as_u64())
+    }
+
+    /// Allocate a frame from the given zone only.
+    pub fn allocate_frame_in(&mut self, zone: Zone) 
+        -> Option<UnusedPhysFrame> {
+
+        self.count_frames();
+
+        let stats = self.stats[zone.index()];
+        if stats.free() == 0 {
+            return None;
+        }
+        let next = stats.allocated;
+
+        let frame = self.useable_frames(zone).nth(next);
+        if frame.is_some() {
+            self.stats[zone.index()].allocated += 1;
+            pressure::set_free_frames(self.free_frames());
+        }
+
+        frame
+    }
+
+    /// Get the statistics for the given zone.
+    pub fn zone_stats(&mut self, zone: Zone) -> ZoneStats {
+        self.count_frames();
+        self.stats[zone.index()]
+    }
+
+    /// The number of free frames across all zones.
+    pub fn free_frames(&self) -> usize {
+        self.stats.iter().map(ZoneStats::free).sum()
+    }
+
+    /// Count the frames in each zone on the first allocation, once all 
+    /// reservations have been made.
+    fn count_frames(&mut self) {
+        if !self.counted {
+            for &zone in Zone::ALL.iter() {
+                self.stats[zone.index()].total = 
+                    self.useable_frames(zone).count();
+            }
+            self.counted = true;
+        }
+    }
+
+    /// Returns an iterator over the unused physical frames in the zone.
+    fn useable_frames(&self, zone: Zone) 
+        -> impl Iterator<Item = UnusedPhysFrame> {
+
+        // Get usable regions from the map
+        let regions = self.memory_map.iter();
+        let useable_regions = regions.filter(
+            |r| r.region_type == MemoryRegionType::Usable);
+
+        // Map each usable region to its address range, clipped to the zone
+        let (zone_start, zone_end) = zone.range();
+        let addr_ranges = useable_regions.map(
+            move |r| r.range.start_addr().max(zone_start)
+                ..r.range.end_addr().min(zone_end));
+
+        // Transform into an iterator, skipping any reserved frames
+        let reserved = self.reserved;
+        let frame_addresses = addr_ranges.flat_map(|r| r.step_by(4096))
+            .filter(move |&addr| !is_reserved(&reserved, addr));
+
+        // Create physical frame types from the start addresses
+        let frames = frame_addresses.map(
+            |addr| PhysFrame::containing_address(PhysAddr::new(addr)));
+
+        frames.map(|f| unsafe {UnusedPhysFrame::new(f)})
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
+
+    /// Allocate a frame, preferring the highest zone with free frames.
+    fn allocate_frame(&mut self) -> Option<UnusedPhysFrame> {
+        Zone::ALL.iter().rev()
+            .find_map(|&zone| self.allocate_frame_in(zone))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Initialise the kernel's page table mapper from the mapping set up by the 
+/// bootloader.
+/// 
+/// NOTE: UNSAFE
+///     This function is unsafe because the caller must guarentee that the 
+///     boot info describes the active page tables.
+/// 
+///     This function must only be called once to avoid aliasing &mut 
+///     references which is undefined behaviour.
+#[cfg(not(feature = "recursive-page-table"))]
+pub unsafe fn init(boot_info: &'static BootInfo) -> KernelMapper {
+    record_boot_memory(boot_info);
+    let phys_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let l4_table = active_l4_table(phys_offset);
+    OffsetPageTable::new(l4_table, phys_offset)
+}
+
+/// Initialise the kernel's page table mapper from the mapping set up by the 
+/// bootloader.
+/// 
+/// NOTE: UNSAFE
+///     This function is unsafe because the caller must guarentee that the 
+///     boot info describes the active page tables.
+/// 
+///     This function must only be called once to avoid aliasing &mut 
+///     references which is undefined behaviour.
+#[cfg(feature = "recursive-page-table")]
+pub unsafe fn init(boot_info: &'static BootInfo) -> KernelMapper {
+    record_boot_memory(boot_info);
+    let l4_table_ptr = boot_info.recursive_page_table_addr as *mut PageTable;
+    RecursivePageTable::new(&mut *l4_table_ptr)
+        .expect("[MEM-ERROR] Level 4 table is not recursively mapped")
+}
+
+/// Get the virtual address at which the bootloader mapped all of physical 
+/// memory, or `None` if it didn't (see `DIRECT_MAP`).
+#[cfg(not(feature = "recursive-page-table"))]
+pub fn physical_memory_offset(boot_info: &BootInfo) -> Option<VirtAddr> {
+    Some(VirtAddr::new(boot_info.physical_memory_offset))
+}
+
+/// Get the virtual address at which the bootloader mapped all of physical 
+/// memory, or `None` if it didn't (see `DIRECT_MAP`).
+#[cfg(feature = "recursive-page-table")]
+pub fn physical_memory_offset(_boot_info: &BootInfo) -> Option<VirtAddr> {
+    None
+}
+
+/// Get the memory map and physical memory offset recorded by `init`.
+pub fn boot_memory() -> Option<(&'static MemoryMap, Option<VirtAddr>)> {
+    BOOT_MEMORY.try_get().ok().copied()
+}
+
+/// Translate a virtual address into its mapped physical address, or `None` if
+/// the address is not mapped.
+pub fn translate_addr(mapper: &KernelMapper, addr: VirtAddr) 
+    -> Option<PhysAddr> {
+        
+    mapper.translate_addr(addr)
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Record the boot info's memory description for `boot_memory`.
+fn record_boot_memory(boot_info: &'static BootInfo) {
+    BOOT_MEMORY.try_init_once(|| 
+        (&boot_info.memory_map, physical_memory_offset(boot_info)))
+        .expect("memory::init must only be called once");
+}
+
+/// Returns `true` if the frame starting at `addr` overlaps one of the reserved 
+/// ranges.
+fn is_reserved(reserved: &[Option<(u64, u64)>], addr: u64) -> bool {
+    reserved.iter().flatten()
+        .any(|&(start, end)| addr < end && addr + 4096 > start)
+}
+
+/// Get a mutable reference to the current active level 4 page table.
+/// 
+/// NOTE: UNSAFE
+///     This function is unsafe because the caller must guarentee that the 
+///     entire physical memory is mapped at the given `physical_mem_offset`.
+/// 
+///     This function must only be called once to avoid aliasing &mut 
+///     references which is undefined behaviour.
+#[cfg(not(feature = "recursive-page-table"))]
+unsafe fn active_l4_table(physical_mem_offset: VirtAddr) 
+    -> &'static mut PageTable {
+
+    // Get the frame physical address from the CR3 register.
+    let (l4_table_frame, _) = Cr3::read();
+
+    // Offset the physical address into the virtual space
+    let phys = l4_table_frame.start_address();
+    let virt = physical_mem_offset + phys.as_u64();
+
+    // Get a mutable pointer to the table.
+    let page_table_ptr: *mut PageTable = virt.as_mut_ptr();
+
+    &mut *page_table_ptr
+}
+
@@ -0,0 +1,170 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use spin::Mutex;
+use crate::{allocator, event};
+use crate::sync::WaitQueue;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The maximum number of subsystems which can register a shrinker.
+pub const MAX_SHRINKERS: usize = 8;
+
+/// Pressure is raised once fewer than this many physical frames are free.
+const LOW_FREE_FRAMES: usize = 64;
+
+/// Pressure is critical once fewer than this many physical frames are free.
+const CRITICAL_FREE_FRAMES: usize = 16;
+
+/// The registered shrinkers.
+static SHRINKERS: Mutex<[Option<Shrinker>; MAX_SHRINKERS]> = 
+    Mutex::new([None; MAX_SHRINKERS]);
+
+/// The number of free physical frames, as last reported by the frame 
+/// allocator.
+static FREE_FRAMES: AtomicUsize = AtomicUsize::new(usize::max_value());
+
+/// Set when pressure has been raised and the monitor hasn't yet relieved it.
+static RAISED: AtomicBool = AtomicBool::new(false);
+
+/// Woken when pressure is raised.
+static PRESSURE_RAISED: WaitQueue = WaitQueue::new();
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The level of memory pressure on the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Pressure {
+    /// There is plenty of free memory.
+    None,
+
+    /// Free memory is running low, subsystems should release anything which
+    /// is cheap to recreate.
+    Low,
+
+    /// Memory is nearly or completely exhausted, subsystems should release 
+    /// everything they can.
+    Critical
+}
+
+/// Errors which can occur when registering a shrinker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureError {
+    /// `MAX_SHRINKERS` shrinkers are already registered.
+    TooManyShrinkers
+}
+
+/// A function called to release memory under pressure, returning the number
+/// of bytes released.
+pub type ShrinkFn = fn(Pressure) -> usize;
+
+/// A shrinker registered by a subsystem.
+#[derive(Clone, Copy)]
+struct Shrinker {
+    name: &'static str,
+    shrink: ShrinkFn
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Register a shrinker for a subsystem.
+/// 
+/// The shrinker is called whenever memory pressure is raised, including from
+/// the allocation path when the heap is exhausted, so it must not allocate.
+pub fn register(name: &'static str, shrink: ShrinkFn) 
+    -> Result<(), PressureError> {
+
+    let mut shrinkers = SHRINKERS.lock();
+    let slot = shrinkers.iter_mut().find(|s| s.is_none())
+        .ok_or(PressureError::TooManyShrinkers)?;
+    *slot = Some(Shrinker { name, shrink });
+    Ok(())
+}
+
+/// Unregister the shrinker registered under `name`.
+/// 
+/// Returns `false` if no shrinker has that name.
+pub fn unregister(name: &'static str) -> bool {
+    let mut shrinkers = SHRINKERS.lock();
+    match shrinkers.iter_mut().find(|s| s.map_or(false, |s| s.name == name)) {
+        Some(slot) => {
+            *slot = None;
+            true
+        },
+        None => false
+    }
+}
+
+/// Get the current memory pressure, the worse of the heap and physical frame
+/// pressures.
+pub fn level() -> Pressure {
+    let free_frames = FREE_FRAMES.load(Ordering::Relaxed);
+    let frames = if free_frames < CRITICAL_FREE_FRAMES {
+        Pressure::Critical
+    }
+    else if free_frames < LOW_FREE_FRAMES {
+        Pressure::Low
+    }
+    else {
+        Pressure::None
+    };
+
+    frames.max(allocator::pressure())
+}
+
+/// Call every registered shrinker with the given pressure level.
+/// 
+/// Returns the total number of bytes released.
+pub fn relieve(level: Pressure) -> usize {
+    // Copy the shrinkers out so the lock isn't held while they run
+    let shrinkers = *SHRINKERS.lock();
+
+    shrinkers.iter().flatten()
+        .map(|shrinker| {
+            let released = (shrinker.shrink)(level);
+            if crate::boot_ui::VERBOSE && released > 0 {
+                crate::serial_println!("[MEM] {} released {} bytes", 
+                    shrinker.name, released);
+            }
+            released
+        })
+        .sum()
+}
+
+/// Background task which relieves memory pressure whenever it is raised.
+pub async fn monitor() {
+    loop {
+        PRESSURE_RAISED.wait_until(|| RAISED.swap(false, Ordering::AcqRel))
+            .await;
+
+        let level = level();
+        if level != Pressure::None {
+            event::LOW_MEMORY.publish(level);
+            relieve(level);
+        }
+    }
+}
+
+/// Raise memory pressure, waking the `monitor` task.
+pub(crate) fn notify() {
+    if !RAISED.swap(true, Ordering::AcqRel) {
+        PRESSURE_RAISED.wake_all();
+    }
+}
+
+/// Update the number of free physical frames, raising pressure if it is 
+/// running low.
+pub(crate) fn set_free_frames(count: usize) {
+    FREE_FRAMES.store(count, Ordering::Relaxed);
+    if count < LOW_FREE_FRAMES {
+        notify();
+    }
+}
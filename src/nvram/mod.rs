@@ -0,0 +1,152 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod store;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use crate::ioport::{IoPortError, PortRange};
+use store::{Store, StoreError, STORE_SIZE};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The CMOS index and data ports.
+const CMOS_PORT_BASE: u16 = 0x70;
+const CMOS_INDEX_OFFSET: u16 = 0;
+const CMOS_DATA_OFFSET: u16 = 1;
+
+/// The first CMOS byte used for the store.
+/// 
+/// The RTC uses the bytes below 0x0E and the BIOS the bytes up to 0x3F 
+/// (checksummed at 0x2E), so the store lives in the upper 64 bytes. Some 
+/// firmware uses these too, so the store's own magic and checksum are 
+/// relied on to detect that.
+const NVRAM_START: u8 = 0x40;
+
+/// Well known keys.
+pub mod keys {
+    /// The default console, as a name such as `b"vga"` or `b"serial"`.
+    pub const DEFAULT_CONSOLE: u8 = 1;
+
+    /// The log level, as a single byte.
+    pub const LOG_LEVEL: u8 = 2;
+
+    /// The status of the last boot, as a single byte.
+    pub const LAST_BOOT_STATUS: u8 = 3;
+}
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Errors which can occur when using the NVRAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NvramError {
+    /// The CMOS ports couldn't be claimed.
+    Ports(IoPortError),
+
+    /// The store couldn't be updated.
+    Store(StoreError)
+}
+
+impl fmt::Display for NvramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NvramError::Ports(e) => write!(f, "{}", e),
+            NvramError::Store(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+/// The key/value store held in the spare CMOS NVRAM bytes.
+/// 
+/// Changes are made to a copy in memory and only written back to the CMOS 
+/// by `commit`.
+pub struct Nvram {
+    ports: PortRange,
+    store: Store,
+    loaded: Result<(), StoreError>
+}
+
+impl Nvram {
+
+    /// Claim the CMOS ports and read the store.
+    /// 
+    /// If the CMOS doesn't hold a valid store an empty one is used, the 
+    /// reason is available from `load_error`.
+    pub fn open() -> Result<Nvram, NvramError> {
+        let ports = PortRange::claim(CMOS_PORT_BASE, 2, "cmos")
+            .map_err(NvramError::Ports)?;
+
+        let mut bytes = [0u8; STORE_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = read_cmos(&ports, NVRAM_START + i as u8);
+        }
+
+        let (store, loaded) = match Store::from_bytes(bytes) {
+            Ok(store) => (store, Ok(())),
+            Err(e) => (Store::empty(), Err(e))
+        };
+
+        Ok(Nvram { ports, store, loaded })
+    }
+
+    /// Get the reason the store in the CMOS wasn't valid when opened, if it 
+    /// wasn't.
+    pub fn load_error(&self) -> Option<StoreError> {
+        self.loaded.err()
+    }
+
+    /// Get the value stored under `key`.
+    pub fn get(&self, key: u8) -> Option<&[u8]> {
+        self.store.get(key)
+    }
+
+    /// Store `value` under `key`.
+    pub fn set(&mut self, key: u8, value: &[u8]) -> Result<(), NvramError> {
+        self.store.set(key, value).map_err(NvramError::Store)
+    }
+
+    /// Remove the value stored under `key`, returning `true` if there was 
+    /// one.
+    pub fn remove(&mut self, key: u8) -> bool {
+        self.store.remove(key)
+    }
+
+    /// Write the store back to the CMOS.
+    pub fn commit(&mut self) {
+        for (i, &byte) in self.store.to_bytes().iter().enumerate() {
+            write_cmos(&self.ports, NVRAM_START + i as u8, byte);
+        }
+        self.loaded = Ok(());
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read a CMOS register.
+/// 
+/// Interrupts are disabled so nothing can change the index between the two 
+/// accesses.
+fn read_cmos(ports: &PortRange, register: u8) -> u8 {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        ports.write_u8(CMOS_INDEX_OFFSET, register);
+        ports.read_u8(CMOS_DATA_OFFSET)
+    })
+}
+
+/// Write a CMOS register.
+fn write_cmos(ports: &PortRange, register: u8, value: u8) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        ports.write_u8(CMOS_INDEX_OFFSET, register);
+        ports.write_u8(CMOS_DATA_OFFSET, value);
+    })
+}
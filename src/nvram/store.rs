@@ -0,0 +1,269 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+// NOTE: This module only depends on `core` so that it can be built and tested
+// on the host by the `host-tests` crate.
+
+use core::fmt;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The size of the store in bytes.
+pub const STORE_SIZE: usize = 64;
+
+/// Magic bytes marking an initialised store.
+const MAGIC: [u8; 2] = *b"SN";
+
+/// Offset of the byte giving the length of the entry area in use.
+const USED_OFFSET: usize = 2;
+
+/// Offset of the first entry.
+const ENTRIES_OFFSET: usize = 3;
+
+/// Offset of the checksum byte, the last byte of the store.
+const CHECKSUM_OFFSET: usize = STORE_SIZE - 1;
+
+/// The space available for entries.
+const ENTRIES_SIZE: usize = CHECKSUM_OFFSET - ENTRIES_OFFSET;
+
+/// The size of an entry header, the key and the value length.
+const ENTRY_HEADER_SIZE: usize = 2;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Errors which can occur when using a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreError {
+    /// The bytes don't start with the store magic, so were never written.
+    BadMagic,
+
+    /// The checksum doesn't match the contents.
+    BadChecksum,
+
+    /// The entries run past the end of the store.
+    Corrupt,
+
+    /// Key 0 is reserved.
+    InvalidKey,
+
+    /// There isn't enough space left for the value.
+    NoSpace
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            StoreError::BadMagic => "store not initialised",
+            StoreError::BadChecksum => "checksum mismatch",
+            StoreError::Corrupt => "corrupt entries",
+            StoreError::InvalidKey => "invalid key",
+            StoreError::NoSpace => "not enough space"
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+/// A small checksummed key/value store in a fixed size byte array.
+/// 
+/// The layout is the magic bytes, one byte giving the length of the entry 
+/// area in use, the entries and a final checksum byte. Each entry is a key 
+/// byte, a length byte and the value.
+#[derive(Clone)]
+pub struct Store {
+    bytes: [u8; STORE_SIZE]
+}
+
+impl Store {
+
+    /// Create an empty store.
+    pub fn empty() -> Store {
+        let mut bytes = [0u8; STORE_SIZE];
+        bytes[..MAGIC.len()].copy_from_slice(&MAGIC);
+        Store { bytes }
+    }
+
+    /// Load a store from its bytes, checking they are valid.
+    pub fn from_bytes(bytes: [u8; STORE_SIZE]) -> Result<Store, StoreError> {
+        if bytes[..MAGIC.len()] != MAGIC {
+            return Err(StoreError::BadMagic);
+        }
+        if checksum(&bytes[..CHECKSUM_OFFSET]) != bytes[CHECKSUM_OFFSET] {
+            return Err(StoreError::BadChecksum);
+        }
+
+        let store = Store { bytes };
+        if store.used() > ENTRIES_SIZE {
+            return Err(StoreError::Corrupt);
+        }
+
+        // Every entry must lie within the used area
+        let mut offset = 0;
+        while offset < store.used() {
+            if offset + ENTRY_HEADER_SIZE > store.used() {
+                return Err(StoreError::Corrupt);
+            }
+            offset += ENTRY_HEADER_SIZE + store.entry_len(offset);
+        }
+        if offset != store.used() {
+            return Err(StoreError::Corrupt);
+        }
+
+        Ok(store)
+    }
+
+    /// Get the bytes of the store, with the checksum updated.
+    pub fn to_bytes(&self) -> [u8; STORE_SIZE] {
+        let mut bytes = self.bytes;
+        bytes[CHECKSUM_OFFSET] = checksum(&bytes[..CHECKSUM_OFFSET]);
+        bytes
+    }
+
+    /// Get the value stored under `key`.
+    pub fn get(&self, key: u8) -> Option<&[u8]> {
+        let offset = self.find(key)?;
+        let start = ENTRIES_OFFSET + offset + ENTRY_HEADER_SIZE;
+        Some(&self.bytes[start..start + self.entry_len(offset)])
+    }
+
+    /// Store `value` under `key`, replacing any existing value.
+    pub fn set(&mut self, key: u8, value: &[u8]) -> Result<(), StoreError> {
+        if key == 0 {
+            return Err(StoreError::InvalidKey);
+        }
+
+        // Check the space with the old value removed before changing anything
+        let existing = self.find(key)
+            .map_or(0, |offset| ENTRY_HEADER_SIZE + self.entry_len(offset));
+        // The entry area is smaller than 255 bytes, so this also checks the 
+        // length fits in its byte
+        let needed = ENTRY_HEADER_SIZE + value.len();
+        if self.used() - existing + needed > ENTRIES_SIZE {
+            return Err(StoreError::NoSpace);
+        }
+
+        self.remove(key);
+
+        let start = ENTRIES_OFFSET + self.used();
+        self.bytes[start] = key;
+        self.bytes[start + 1] = value.len() as u8;
+        self.bytes[start + ENTRY_HEADER_SIZE..start + needed]
+            .copy_from_slice(value);
+        self.bytes[USED_OFFSET] += needed as u8;
+
+        Ok(())
+    }
+
+    /// Remove the value stored under `key`, returning `true` if there was 
+    /// one.
+    pub fn remove(&mut self, key: u8) -> bool {
+        let offset = match self.find(key) {
+            Some(offset) => offset,
+            None => return false
+        };
+
+        // Shift the following entries down over the removed one
+        let len = ENTRY_HEADER_SIZE + self.entry_len(offset);
+        let start = ENTRIES_OFFSET + offset;
+        let end = ENTRIES_OFFSET + self.used();
+        self.bytes.copy_within(start + len..end, start);
+        for byte in self.bytes[end - len..end].iter_mut() {
+            *byte = 0;
+        }
+        self.bytes[USED_OFFSET] -= len as u8;
+
+        true
+    }
+
+    /// The number of bytes of the entry area in use.
+    fn used(&self) -> usize {
+        self.bytes[USED_OFFSET] as usize
+    }
+
+    /// The length of the value of the entry at `offset` in the entry area.
+    fn entry_len(&self, offset: usize) -> usize {
+        self.bytes[ENTRIES_OFFSET + offset + 1] as usize
+    }
+
+    /// Find the offset of the entry for `key` in the entry area.
+    fn find(&self, key: u8) -> Option<usize> {
+        let mut offset = 0;
+        while offset < self.used() {
+            if self.bytes[ENTRIES_OFFSET + offset] == key {
+                return Some(offset);
+            }
+            offset += ENTRY_HEADER_SIZE + self.entry_len(offset);
+        }
+        None
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Checksum the bytes, such that an all zero store doesn't pass.
+fn checksum(bytes: &[u8]) -> u8 {
+    !bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_get_roundtrip() {
+        let mut store = Store::empty();
+        store.set(1, b"vga").unwrap();
+        store.set(2, &[3]).unwrap();
+
+        let loaded = Store::from_bytes(store.to_bytes()).unwrap();
+        assert_eq!(loaded.get(1), Some(&b"vga"[..]));
+        assert_eq!(loaded.get(2), Some(&[3u8][..]));
+        assert_eq!(loaded.get(3), None);
+    }
+
+    #[test]
+    fn replace_and_remove() {
+        let mut store = Store::empty();
+        store.set(1, b"vga").unwrap();
+        store.set(2, b"x").unwrap();
+        store.set(1, b"serial").unwrap();
+        assert_eq!(store.get(1), Some(&b"serial"[..]));
+        assert_eq!(store.get(2), Some(&b"x"[..]));
+
+        assert!(store.remove(1));
+        assert!(!store.remove(1));
+        assert_eq!(store.get(1), None);
+        assert_eq!(store.get(2), Some(&b"x"[..]));
+    }
+
+    #[test]
+    fn rejects_invalid_bytes() {
+        assert_eq!(Store::from_bytes([0; STORE_SIZE]).err(), 
+            Some(StoreError::BadMagic));
+
+        let mut bytes = Store::empty().to_bytes();
+        bytes[10] ^= 1;
+        assert_eq!(Store::from_bytes(bytes).err(), 
+            Some(StoreError::BadChecksum));
+    }
+
+    #[test]
+    fn full_store_is_unchanged() {
+        let mut store = Store::empty();
+        store.set(1, &[0; ENTRIES_SIZE - ENTRY_HEADER_SIZE]).unwrap();
+        assert_eq!(store.set(2, b"a"), Err(StoreError::NoSpace));
+        assert_eq!(store.set(0, b"a"), Err(StoreError::InvalidKey));
+        assert_eq!(store.get(1).map(|v| v.len()), 
+            Some(ENTRIES_SIZE - ENTRY_HEADER_SIZE));
+    }
+}
@@ -0,0 +1,309 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::__cpuid;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::registers::model_specific::Msr;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// CPUID leaf describing architectural performance monitoring.
+const CPUID_PERFMON: u32 = 0xa;
+
+/// The first programmable counter and its event select MSR.
+const IA32_PMC0: u32 = 0xc1;
+const IA32_PERFEVTSEL0: u32 = 0x186;
+
+/// The first fixed counter, counting instructions retired. The next counts 
+/// core cycles.
+const IA32_FIXED_CTR0: u32 = 0x309;
+
+/// MSR enabling the fixed counters.
+const IA32_FIXED_CTR_CTRL: u32 = 0x38d;
+
+/// MSR globally enabling counters, from perfmon version 2.
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38f;
+
+/// Event select bits counting in ring 0 and enabling the counter.
+const EVTSEL_OS: u64 = 1 << 17;
+const EVTSEL_ENABLE: u64 = 1 << 22;
+
+/// Fixed counter control bits counting in ring 0, for each counter.
+const FIXED_CTRL_OS: u64 = 0b01;
+
+/// Set while the counters are in use by `measure`.
+static BUSY: AtomicBool = AtomicBool::new(false);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The performance monitoring capabilities of the CPU, read from CPUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The architectural perfmon version, 0 if unsupported.
+    pub version: u8,
+
+    /// The number of programmable counters.
+    pub counters: u8,
+
+    /// The number of fixed counters.
+    pub fixed_counters: u8,
+
+    /// The width of the programmable counters in bits.
+    pub counter_width: u8,
+
+    /// The width of the fixed counters in bits.
+    pub fixed_counter_width: u8,
+
+    /// CPUID bits flagging architectural events which are *not* available.
+    unavailable_events: u32
+}
+
+impl Capabilities {
+
+    /// Returns `true` if `measure` can use the counters, which needs the 
+    /// global control MSR from version 2.
+    pub fn usable(&self) -> bool {
+        self.version >= 2
+    }
+
+    /// Returns `true` if `event` can be counted.
+    pub fn supports(&self, event: Event) -> bool {
+        if !self.usable() {
+            return false;
+        }
+
+        match event.counter() {
+            Counter::Fixed(i) => i < self.fixed_counters,
+            Counter::Programmable(i, _) => i < self.counters 
+                && self.unavailable_events & (1 << event.cpuid_bit()) == 0
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Perfmon v{}: {} x {}-bit counters, {} x {}-bit fixed", 
+            self.version, self.counters, self.counter_width, 
+            self.fixed_counters, self.fixed_counter_width)
+    }
+}
+
+/// An event counted by `measure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    InstructionsRetired,
+    CoreCycles,
+    CacheMisses,
+    BranchMispredicts
+}
+
+impl Event {
+
+    /// All events, in the order of `Sample`'s fields.
+    pub const ALL: [Event; 4] = [
+        Event::InstructionsRetired, Event::CoreCycles, 
+        Event::CacheMisses, Event::BranchMispredicts
+    ];
+
+    /// The counter used for the event.
+    fn counter(self) -> Counter {
+        match self {
+            Event::InstructionsRetired => Counter::Fixed(0),
+            Event::CoreCycles => Counter::Fixed(1),
+
+            // Last level cache misses, event 0x2e umask 0x41
+            Event::CacheMisses => Counter::Programmable(0, 0x412e),
+
+            // Branch instructions mispredicted at retirement, event 0xc5
+            Event::BranchMispredicts => Counter::Programmable(1, 0x00c5)
+        }
+    }
+
+    /// The bit in CPUID leaf 0xa EBX which is set if the event is 
+    /// unavailable.
+    fn cpuid_bit(self) -> u32 {
+        match self {
+            Event::CoreCycles => 0,
+            Event::InstructionsRetired => 1,
+            Event::CacheMisses => 4,
+            Event::BranchMispredicts => 6
+        }
+    }
+}
+
+/// The hardware counter used for an event.
+#[derive(Debug, Clone, Copy)]
+enum Counter {
+    /// A fixed counter, by index.
+    Fixed(u8),
+
+    /// A programmable counter, by index, with its event select value.
+    Programmable(u8, u64)
+}
+
+/// The counts of each event over a measurement, `None` where the event 
+/// couldn't be counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub instructions: Option<u64>,
+    pub cycles: Option<u64>,
+    pub cache_misses: Option<u64>,
+    pub branch_mispredicts: Option<u64>
+}
+
+impl Sample {
+
+    /// Get the count of `event`.
+    pub fn get(&self, event: Event) -> Option<u64> {
+        match event {
+            Event::InstructionsRetired => self.instructions,
+            Event::CoreCycles => self.cycles,
+            Event::CacheMisses => self.cache_misses,
+            Event::BranchMispredicts => self.branch_mispredicts
+        }
+    }
+
+    /// Set the count of `event`.
+    fn set(&mut self, event: Event, count: Option<u64>) {
+        match event {
+            Event::InstructionsRetired => self.instructions = count,
+            Event::CoreCycles => self.cycles = count,
+            Event::CacheMisses => self.cache_misses = count,
+            Event::BranchMispredicts => self.branch_mispredicts = count
+        }
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &event) in Event::ALL.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match self.get(event) {
+                Some(count) => write!(f, "{:?}: {}", event, count)?,
+                None => write!(f, "{:?}: n/a", event)?
+            }
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read the CPU's performance monitoring capabilities.
+pub fn capabilities() -> Capabilities {
+    // NOTE: USE OF UNSAFE
+    //  CPUID is always available in long mode, and the perfmon leaf is only 
+    //  read if the maximum leaf says it exists.
+    let (eax, ebx, edx) = unsafe {
+        if __cpuid(0).eax >= CPUID_PERFMON {
+            let leaf = __cpuid(CPUID_PERFMON);
+            (leaf.eax, leaf.ebx, leaf.edx)
+        }
+        else {
+            (0, 0, 0)
+        }
+    };
+
+    Capabilities {
+        version: eax as u8,
+        counters: (eax >> 8) as u8,
+        counter_width: (eax >> 16) as u8,
+        fixed_counters: (edx & 0x1f) as u8,
+        fixed_counter_width: (edx >> 5) as u8,
+        unavailable_events: ebx
+    }
+}
+
+/// Run `f`, counting the events it causes.
+/// 
+/// Events the CPU can't count are `None`, as is every event if the counters 
+/// are unsupported or already in use by another `measure`. Interrupts which 
+/// arrive while `f` runs are counted too.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Sample) {
+    let caps = capabilities();
+    if !caps.usable() || BUSY.swap(true, Ordering::AcqRel) {
+        return (f(), Sample::default());
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  Only counters which CPUID reports are programmed, and they're only 
+    //  used here, guarded by `BUSY`.
+    unsafe {
+        let mut global = 0;
+        let mut fixed_ctrl = 0;
+        for &event in Event::ALL.iter().filter(|&&e| caps.supports(e)) {
+            match event.counter() {
+                Counter::Fixed(i) => {
+                    Msr::new(IA32_FIXED_CTR0 + i as u32).write(0);
+                    fixed_ctrl |= FIXED_CTRL_OS << (4 * i);
+                    global |= 1 << (32 + i);
+                },
+                Counter::Programmable(i, select) => {
+                    Msr::new(IA32_PMC0 + i as u32).write(0);
+                    Msr::new(IA32_PERFEVTSEL0 + i as u32)
+                        .write(select | EVTSEL_OS | EVTSEL_ENABLE);
+                    global |= 1 << i;
+                }
+            }
+        }
+        Msr::new(IA32_FIXED_CTR_CTRL).write(fixed_ctrl);
+        Msr::new(IA32_PERF_GLOBAL_CTRL).write(global);
+    }
+
+    let result = f();
+
+    let mut sample = Sample::default();
+
+    // NOTE: USE OF UNSAFE
+    //  As above, only the counters programmed above are read and disabled.
+    unsafe {
+        Msr::new(IA32_PERF_GLOBAL_CTRL).write(0);
+        for &event in Event::ALL.iter().filter(|&&e| caps.supports(e)) {
+            let count = match event.counter() {
+                Counter::Fixed(i) => 
+                    Msr::new(IA32_FIXED_CTR0 + i as u32).read(),
+                Counter::Programmable(i, _) => {
+                    Msr::new(IA32_PERFEVTSEL0 + i as u32).write(0);
+                    Msr::new(IA32_PMC0 + i as u32).read()
+                }
+            };
+            sample.set(event, Some(count));
+        }
+        Msr::new(IA32_FIXED_CTR_CTRL).write(0);
+    }
+
+    BUSY.store(false, Ordering::Release);
+    (result, sample)
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_measure() {
+    use crate::{serial_print, serial_println};
+    serial_print!("perf::measure ");
+
+    let (value, sample) = measure(|| 41 + 1);
+    assert_eq!(value, 42);
+
+    // Counters may not be emulated, but should be counted if they are 
+    // reported as supported
+    let caps = capabilities();
+    for &event in Event::ALL.iter() {
+        assert_eq!(sample.get(event).is_some(), caps.supports(event));
+    }
+
+    serial_println!("[ok]");
+}
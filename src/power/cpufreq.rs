@@ -0,0 +1,320 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::__cpuid;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::registers::model_specific::Msr;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// MSR giving the platform's minimum and maximum non-turbo ratios.
+const MSR_PLATFORM_INFO: u32 = 0xce;
+
+/// MSR giving the current performance state.
+const IA32_PERF_STATUS: u32 = 0x198;
+
+/// MSR used to request a performance state.
+const IA32_PERF_CTL: u32 = 0x199;
+
+/// The bus clock used to convert ratios into frequencies, in MHz.
+const BUS_CLOCK_MHZ: u64 = 100;
+
+/// The first family 6 model (Nehalem) with `MSR_PLATFORM_INFO`.
+const FIRST_PLATFORM_INFO_MODEL: u32 = 0x1a;
+
+/// Later family 6 models without `MSR_PLATFORM_INFO`, the Bonnell and 
+/// Saltwell Atoms.
+const NO_PLATFORM_INFO_MODELS: [u32; 5] = [0x1c, 0x26, 0x27, 0x35, 0x36];
+
+/// The number of times the executor has halted the CPU while idle.
+static IDLE_HALTS: AtomicU64 = AtomicU64::new(0);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The frequency scaling and idle capabilities of the CPU, read from CPUID.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    /// The CPU vendor string.
+    pub vendor: [u8; 12],
+
+    /// Enhanced SpeedStep, P-states can be requested through `IA32_PERF_CTL`.
+    pub eist: bool,
+
+    /// Hardware controlled P-states.
+    pub hwp: bool,
+
+    /// Turbo boost.
+    pub turbo: bool,
+
+    /// `MONITOR`/`MWAIT` for entering deeper C-states.
+    pub mwait: bool,
+
+    /// Running under a hypervisor, which may not emulate the P-state MSRs.
+    pub hypervisor: bool,
+
+    /// The CPU family and model, including the extended fields.
+    pub family: u32,
+    pub model: u32,
+
+    /// The number of `MWAIT` sub-states supported for C0 to C7, from CPUID 
+    /// leaf 5.
+    pub cstate_substates: [u8; 8],
+
+    /// The base and maximum frequencies in MHz, from CPUID leaf 0x16.
+    pub base_mhz: Option<u32>,
+    pub max_mhz: Option<u32>
+}
+
+impl Capabilities {
+
+    /// The vendor as a string.
+    pub fn vendor_str(&self) -> &str {
+        core::str::from_utf8(&self.vendor).unwrap_or("unknown")
+    }
+
+    /// Returns `true` if P-state MSRs can be used.
+    fn has_pstate_msrs(&self) -> bool {
+        self.eist && &self.vendor == b"GenuineIntel"
+    }
+
+    /// Returns `true` if `MSR_PLATFORM_INFO` is known to exist.
+    /// 
+    /// The MSR is model specific, and reading it where it doesn't exist 
+    /// raises a general protection fault, so it's only used on the models 
+    /// documented to have it and never under a hypervisor.
+    fn has_platform_info(&self) -> bool {
+        self.has_pstate_msrs() 
+            && !self.hypervisor 
+            && self.family == 6 
+            && self.model >= FIRST_PLATFORM_INFO_MODEL 
+            && !NO_PLATFORM_INFO_MODELS.contains(&self.model)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "CPU: {}", self.vendor_str())?;
+        writeln!(f, "    EIST: {}, HWP: {}, turbo: {}, MWAIT: {}", 
+            self.eist, self.hwp, self.turbo, self.mwait)?;
+        if let (Some(base), Some(max)) = (self.base_mhz, self.max_mhz) {
+            writeln!(f, "    base: {} MHz, max: {} MHz", base, max)?;
+        }
+        write!(f, "    C-states:")?;
+        for (state, &substates) in self.cstate_substates.iter().enumerate() {
+            if substates > 0 {
+                write!(f, " C{}({})", state, substates)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A snapshot of frequency and idle statistics.
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    /// The current P-state ratio, if it can be read.
+    pub current_ratio: Option<u8>,
+
+    /// The lowest and highest non-turbo ratios, if they can be read.
+    pub min_ratio: Option<u8>,
+    pub max_ratio: Option<u8>,
+
+    /// The number of times the CPU has been halted while idle.
+    pub idle_halts: u64
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.current_ratio {
+            Some(ratio) => write!(f, "P-state ratio {} (~{} MHz)", 
+                ratio, ratio as u64 * BUS_CLOCK_MHZ)?,
+            None => write!(f, "P-state unavailable")?
+        }
+        if let (Some(min), Some(max)) = (self.min_ratio, self.max_ratio) {
+            write!(f, ", range {}-{}", min, max)?;
+        }
+        write!(f, ", {} idle halts", self.idle_halts)
+    }
+}
+
+/// Errors which can occur when requesting a P-state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuFreqError {
+    /// The CPU doesn't support software controlled P-states.
+    Unsupported,
+
+    /// The requested ratio is outside the supported range.
+    OutOfRange
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read the CPU's frequency scaling and idle capabilities.
+pub fn capabilities() -> Capabilities {
+    // NOTE: USE OF UNSAFE
+    //  CPUID is always available in long mode, and leaves are only read if 
+    //  the maximum leaf says they exist.
+    unsafe {
+        let leaf0 = __cpuid(0);
+        let max_leaf = leaf0.eax;
+
+        let mut vendor = [0u8; 12];
+        vendor[0..4].copy_from_slice(&leaf0.ebx.to_le_bytes());
+        vendor[4..8].copy_from_slice(&leaf0.edx.to_le_bytes());
+        vendor[8..12].copy_from_slice(&leaf0.ecx.to_le_bytes());
+
+        // The extended family and model are only used by some base families
+        let leaf1 = __cpuid(1);
+        let base_family = (leaf1.eax >> 8) & 0xf;
+        let mut family = base_family;
+        let mut model = (leaf1.eax >> 4) & 0xf;
+        if base_family == 0xf {
+            family += (leaf1.eax >> 20) & 0xff;
+        }
+        if base_family == 0x6 || base_family == 0xf {
+            model |= ((leaf1.eax >> 16) & 0xf) << 4;
+        }
+
+        let mut caps = Capabilities {
+            vendor,
+            eist: leaf1.ecx & (1 << 7) != 0,
+            hwp: false,
+            turbo: false,
+            mwait: leaf1.ecx & (1 << 3) != 0,
+            hypervisor: leaf1.ecx & (1 << 31) != 0,
+            family,
+            model,
+            cstate_substates: [0; 8],
+            base_mhz: None,
+            max_mhz: None
+        };
+
+        if caps.mwait && max_leaf >= 5 {
+            let edx = __cpuid(5).edx;
+            for (state, substates) in caps.cstate_substates.iter_mut()
+                .enumerate() {
+                *substates = ((edx >> (state * 4)) & 0xf) as u8;
+            }
+        }
+
+        if max_leaf >= 6 {
+            let eax = __cpuid(6).eax;
+            caps.turbo = eax & (1 << 1) != 0;
+            caps.hwp = eax & (1 << 7) != 0;
+        }
+
+        if max_leaf >= 0x16 {
+            let leaf16 = __cpuid(0x16);
+            if leaf16.eax != 0 {
+                caps.base_mhz = Some(leaf16.eax & 0xffff);
+                caps.max_mhz = Some(leaf16.ebx & 0xffff);
+            }
+        }
+
+        caps
+    }
+}
+
+/// Get the current frequency statistics.
+pub fn stats() -> Stats {
+    let caps = capabilities();
+    let (min_ratio, max_ratio) = ratio_range(&caps)
+        .map_or((None, None), |(min, max)| (Some(min), Some(max)));
+
+    // Hypervisors don't always emulate the status MSR, and reading a missing
+    // MSR faults
+    let current_ratio = if caps.has_pstate_msrs() && !caps.hypervisor {
+        // NOTE: USE OF UNSAFE
+        //  The MSR exists on Intel CPUs supporting EIST.
+        Some((unsafe { Msr::new(IA32_PERF_STATUS).read() } >> 8) as u8)
+    }
+    else {
+        None
+    };
+
+    Stats {
+        current_ratio,
+        min_ratio,
+        max_ratio,
+        idle_halts: IDLE_HALTS.load(Ordering::Relaxed)
+    }
+}
+
+/// Request the given P-state ratio.
+/// 
+/// The CPU treats this as a hint, and may run at a different frequency.
+pub fn request_ratio(ratio: u8) -> Result<(), CpuFreqError> {
+    let caps = capabilities();
+    let (min, max) = ratio_range(&caps).ok_or(CpuFreqError::Unsupported)?;
+    if ratio < min || ratio > max {
+        return Err(CpuFreqError::OutOfRange);
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  The MSR exists on Intel CPUs supporting EIST, and only the target 
+    //  ratio field is changed.
+    unsafe {
+        let mut msr = Msr::new(IA32_PERF_CTL);
+        let value = msr.read() & !0xff00;
+        msr.write(value | (ratio as u64) << 8);
+    }
+
+    Ok(())
+}
+
+/// Record that the CPU has been halted while idle.
+pub(crate) fn record_idle_halt() {
+    IDLE_HALTS.fetch_add(1, Ordering::Relaxed);
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the minimum and maximum non-turbo ratios from the platform info MSR.
+/// 
+/// Returns `None` if the MSR can't be confirmed to exist.
+fn ratio_range(caps: &Capabilities) -> Option<(u8, u8)> {
+    if !caps.has_platform_info() {
+        return None;
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  The MSR exists on the models checked by `has_platform_info`.
+    let info = unsafe { Msr::new(MSR_PLATFORM_INFO).read() };
+    let max = (info >> 8) as u8;
+    let min = (info >> 40) as u8;
+
+    if min == 0 || max < min {
+        None
+    }
+    else {
+        Some((min, max))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_idle_halt_stats() {
+    use crate::{serial_print, serial_println};
+    serial_print!("power::cpufreq::idle_halt_stats ");
+
+    // The executor isn't running in tests, so nothing else records halts
+    let before = stats().idle_halts;
+    record_idle_halt();
+    record_idle_halt();
+    assert_eq!(stats().idle_halts, before + 2);
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,26 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod cpufreq;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// Keyboard controller command which pulses the CPU reset line.
+const KBC_PULSE_RESET: u8 = 0xfe;
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Reboot the machine through the keyboard controller.
+/// 
+/// If the reset doesn't happen (or the controller ports haven't been claimed)
+/// the CPU is halted instead.
+pub fn reboot() -> ! {
+    x86_64::instructions::interrupts::disable();
+    crate::task::keyboard::send_controller_command(KBC_PULSE_RESET);
+    crate::halt_loop()
+}
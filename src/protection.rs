@@ -0,0 +1,166 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::VirtAddr;
+use x86_64::registers::control::Cr4;
+use x86_64::registers::rflags::{self, RFlags};
+use x86_64::structures::idt::PageFaultErrorCode;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// CPUID leaf 7 EBX bit reporting SMEP support.
+const CPUID_SMEP: u32 = 1 << 7;
+
+/// CPUID leaf 7 EBX bit reporting SMAP support.
+const CPUID_SMAP: u32 = 1 << 20;
+
+/// CR4 bit enabling SMEP.
+const CR4_SMEP: u64 = 1 << 20;
+
+/// CR4 bit enabling SMAP.
+const CR4_SMAP: u64 = 1 << 21;
+
+// ---------------------------------------------------------------------------
+// STATICS
+// ---------------------------------------------------------------------------
+
+static SMEP_ENABLED: AtomicBool = AtomicBool::new(false);
+static SMAP_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A page fault caused by supervisor mode protection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The kernel tried to execute a user page.
+    Smep,
+
+    /// The kernel tried to access a user page outside of a `UserAccess` 
+    /// guard.
+    Smap
+}
+
+/// Guard allowing the kernel to access user memory while SMAP is enabled.
+/// 
+/// The guard sets the AC flag, as `stac` does, and restores the previous 
+/// flag when dropped. Keep it alive only around the copy to or from user 
+/// memory, so stray accesses through user pointers elsewhere still fault.
+pub struct UserAccess {
+    was_set: bool
+}
+
+impl Drop for UserAccess {
+    fn drop(&mut self) {
+        if !self.was_set {
+            set_access_flag(false);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Enable SMEP and SMAP if the CPU supports them.
+pub fn init() {
+    // NOTE: USE OF UNSAFE
+    //  CPUID is always available in long mode, and leaf 7 is only read if the
+    //  maximum leaf says it exists.
+    let features = unsafe {
+        if __cpuid(0).eax >= 7 {
+            __cpuid_count(7, 0).ebx
+        }
+        else {
+            0
+        }
+    };
+
+    let mut cr4 = Cr4::read_raw();
+    if features & CPUID_SMEP != 0 {
+        cr4 |= CR4_SMEP;
+        SMEP_ENABLED.store(true, Ordering::Relaxed);
+    }
+    if features & CPUID_SMAP != 0 {
+        cr4 |= CR4_SMAP;
+        SMAP_ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  Only bits the CPU reports support for are set. The kernel has no user 
+    //  accessible pages, so neither can break existing mappings.
+    unsafe { Cr4::write_raw(cr4) };
+}
+
+/// Returns `true` if SMEP has been enabled.
+pub fn smep_enabled() -> bool {
+    SMEP_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns `true` if SMAP has been enabled.
+pub fn smap_enabled() -> bool {
+    SMAP_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Allow access to user memory until the returned guard is dropped.
+/// 
+/// Does nothing if SMAP isn't enabled.
+pub fn user_access() -> UserAccess {
+    let was_set = rflags::read().contains(RFlags::ALIGNMENT_CHECK);
+    if smap_enabled() && !was_set {
+        set_access_flag(true);
+    }
+    UserAccess { was_set: was_set || !smap_enabled() }
+}
+
+/// Run `f` with access to user memory allowed.
+pub fn with_user_access<T>(f: impl FnOnce() -> T) -> T {
+    let _guard = user_access();
+    f()
+}
+
+/// Work out whether a page fault was caused by SMEP or SMAP.
+/// 
+/// Both cause kernel mode protection violations on user accessible pages, 
+/// SMAP only when the AC flag in the faulting `flags` was clear.
+pub fn classify_fault(code: PageFaultErrorCode, addr: VirtAddr, flags: u64)
+    -> Option<Violation> {
+
+    if code.contains(PageFaultErrorCode::USER_MODE) 
+        || !code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) 
+        || crate::memory::audit::is_user_page(addr) != Some(true) {
+        return None;
+    }
+
+    if code.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {
+        if smep_enabled() {
+            return Some(Violation::Smep);
+        }
+    }
+    else if smap_enabled() 
+        && flags & RFlags::ALIGNMENT_CHECK.bits() == 0 {
+        return Some(Violation::Smap);
+    }
+
+    None
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Set or clear the AC flag, which is what `stac` and `clac` do.
+fn set_access_flag(set: bool) {
+    let mut flags = rflags::read();
+    flags.set(RFlags::ALIGNMENT_CHECK, set);
+
+    // NOTE: USE OF UNSAFE
+    //  Only the AC flag is changed, which in ring 0 only affects SMAP.
+    unsafe { rflags::write(flags) };
+}
@@ -0,0 +1,181 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod pool;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::{__cpuid, _rdtsc, _rdrand64_step};
+use spin::Mutex;
+use lazy_static::lazy_static;
+use pool::{Pool, Generator, KEY_WORDS};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The credit, in bits, the pool must reach before the generator is 
+/// reseeded from it.
+const RESEED_BITS: u32 = 64;
+
+/// CPUID leaf 1 ECX bit reporting RDRAND support.
+const CPUID_RDRAND: u32 = 1 << 30;
+
+/// The number of times RDRAND is retried when it has no random data ready, 
+/// as recommended by Intel.
+const RDRAND_RETRIES: usize = 10;
+
+// ---------------------------------------------------------------------------
+// STATICS
+// ---------------------------------------------------------------------------
+
+/// The entropy pool, which interrupt handlers mix their timestamps into.
+static POOL: Mutex<Pool> = Mutex::new(Pool::new());
+
+/// The generator, created on first use.
+static GENERATOR: Mutex<Option<Generator>> = Mutex::new(None);
+
+lazy_static! {
+    /// Whether the CPU supports RDRAND.
+    static ref HAS_RDRAND: bool = {
+        // NOTE: USE OF UNSAFE
+        //  CPUID is always available on x86_64.
+        unsafe { __cpuid(1).ecx & CPUID_RDRAND != 0 }
+    };
+}
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The interrupt sources which are mixed into the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Timer,
+    Keyboard,
+    Serial
+}
+
+impl Source {
+
+    /// Whether events from the source are credited with entropy.
+    /// 
+    /// The timer fires at a fixed rate so its timing is mixed in but never 
+    /// credited.
+    fn credited(self) -> bool {
+        self != Source::Timer
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Mix the current timestamp of an interrupt from `source` into the pool.
+/// 
+/// This is called from interrupt handlers so never waits on the pool lock, 
+/// if the pool is in use the sample is dropped.
+pub fn add_interrupt_timing(source: Source) {
+    // NOTE: USE OF UNSAFE
+    //  RDTSC is always available on x86_64.
+    let timestamp = unsafe { _rdtsc() };
+
+    if let Some(mut pool) = POOL.try_lock() {
+        let sample = timestamp ^ ((source as u64) << 56);
+        pool.add_timing(sample, source.credited());
+    }
+}
+
+/// Fill `buf` with random bytes.
+/// 
+/// The generator is seeded from the pool on first use, and reseeded whenever
+/// enough entropy has been credited. If the CPU supports RDRAND its output 
+/// is mixed in at each reseed. Until interrupts have been credited this 
+/// output is only as good as RDRAND, or the timestamps mixed in so far 
+/// without it.
+pub fn fill_bytes(buf: &mut [u8]) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut generator = GENERATOR.lock();
+        let mut pool = POOL.lock();
+
+        match generator.as_mut() {
+            None => *generator = Some(Generator::new(seed(&mut pool))),
+            Some(generator) if pool.credit() >= RESEED_BITS => 
+                generator.reseed(seed(&mut pool)),
+            Some(_) => ()
+        }
+        drop(pool);
+
+        if let Some(generator) = generator.as_mut() {
+            generator.fill(buf);
+        }
+    })
+}
+
+/// Get a random `u64`.
+pub fn next_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    fill_bytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+/// The entropy credited to the pool since the generator was last seeded, in 
+/// bits.
+pub fn entropy_bits() -> u32 {
+    x86_64::instructions::interrupts::without_interrupts(|| 
+        POOL.lock().credit())
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Extract a seed from the pool, mixing in RDRAND output first if available.
+fn seed(pool: &mut Pool) -> [u32; KEY_WORDS] {
+    // NOTE: USE OF UNSAFE
+    //  RDTSC is always available on x86_64.
+    pool.mix(unsafe { _rdtsc() });
+
+    if *HAS_RDRAND {
+        for _ in 0..KEY_WORDS / 2 {
+            // NOTE: USE OF UNSAFE
+            //  RDRAND support has been checked through CPUID.
+            if let Some(value) = unsafe { rdrand() } {
+                pool.mix(value);
+            }
+        }
+    }
+
+    pool.extract()
+}
+
+/// Read a value from RDRAND, or `None` if it has none ready.
+/// 
+/// NOTE: UNSAFE
+///     This function is unsafe because the CPU must support RDRAND.
+#[target_feature(enable = "rdrand")]
+unsafe fn rdrand() -> Option<u64> {
+    let mut value = 0;
+    for _ in 0..RDRAND_RETRIES {
+        if _rdrand64_step(&mut value) == 1 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_output_differs() {
+    use crate::{serial_print, serial_println};
+    serial_print!("rng::output_differs ");
+    add_interrupt_timing(Source::Keyboard);
+    assert_ne!(next_u64(), next_u64());
+    serial_println!("[ok]");
+}
@@ -0,0 +1,301 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+// NOTE: This module only depends on `core` so that it can be built and tested
+// on the host by the `host-tests` crate.
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The number of 32-bit words in the pool, the size of a ChaCha state.
+pub const POOL_WORDS: usize = 16;
+
+/// The number of words in a generator key.
+pub const KEY_WORDS: usize = 8;
+
+/// The ChaCha constant words, "expand 32-byte k".
+const CHACHA_CONSTANTS: [u32; 4] = 
+    [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// The number of ChaCha double rounds, giving ChaCha20.
+const DOUBLE_ROUNDS: usize = 10;
+
+/// The maximum credit the pool holds, in bits.
+const MAX_CREDIT: u32 = (POOL_WORDS * 32) as u32;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// An entropy pool which samples are mixed into, and from which generator 
+/// keys are extracted.
+/// 
+/// Samples are folded into the pool words in turn, and the whole pool is 
+/// stirred with the ChaCha permutation each time every word has been mixed, 
+/// so every sample affects every word. Extraction stirs the pool again and 
+/// takes a key from it, then mixes the key back so earlier output can't be 
+/// recovered from the pool.
+pub struct Pool {
+    words: [u32; POOL_WORDS],
+    pos: usize,
+    credit: u32,
+    last_timestamp: u64,
+    last_delta: u64
+}
+
+impl Pool {
+
+    /// Create an empty pool.
+    pub const fn new() -> Pool {
+        Pool {
+            words: [0; POOL_WORDS],
+            pos: 0,
+            credit: 0,
+            last_timestamp: 0,
+            last_delta: 0
+        }
+    }
+
+    /// Mix a sample into the pool without crediting any entropy.
+    pub fn mix(&mut self, sample: u64) {
+        self.mix_word(sample as u32);
+        self.mix_word((sample >> 32) as u32);
+    }
+
+    /// Mix the timestamp of an event into the pool, crediting one bit of 
+    /// entropy if `credit` is set and the timing was irregular.
+    /// 
+    /// The timing is irregular if the time since the last event differs from
+    /// the time between the two before, so a steady source such as a timer 
+    /// isn't credited.
+    pub fn add_timing(&mut self, timestamp: u64, credit: bool) {
+        let delta = timestamp.wrapping_sub(self.last_timestamp);
+        let irregular = delta != self.last_delta && delta != 0;
+        self.last_timestamp = timestamp;
+        self.last_delta = delta;
+
+        self.mix(timestamp);
+        if credit && irregular {
+            self.credit = (self.credit + 1).min(MAX_CREDIT);
+        }
+    }
+
+    /// The entropy credited to the pool since the last extraction, in bits.
+    pub fn credit(&self) -> u32 {
+        self.credit
+    }
+
+    /// Extract a generator key from the pool, clearing its credit.
+    pub fn extract(&mut self) -> [u32; KEY_WORDS] {
+        self.stir();
+
+        let mut key = [0u32; KEY_WORDS];
+        key.copy_from_slice(&self.words[..KEY_WORDS]);
+
+        // Overwrite the words the key came from, so the key can't be 
+        // recovered from the pool if it is later exposed
+        for word in key.iter() {
+            self.mix_word(*word);
+        }
+        self.stir();
+
+        self.credit = 0;
+        key
+    }
+
+    /// Fold a word into the pool.
+    fn mix_word(&mut self, word: u32) {
+        let i = self.pos;
+        let prev = self.words[(i + POOL_WORDS - 1) % POOL_WORDS];
+        self.words[i] = (self.words[i] ^ word)
+            .wrapping_add(prev.rotate_left(7));
+
+        self.pos = (i + 1) % POOL_WORDS;
+        if self.pos == 0 {
+            self.stir();
+        }
+    }
+
+    /// Apply the ChaCha permutation to the pool.
+    fn stir(&mut self) {
+        let input = self.words;
+        permute(&mut self.words);
+        for (word, input) in self.words.iter_mut().zip(input.iter()) {
+            *word = word.wrapping_add(*input);
+        }
+    }
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Pool::new()
+    }
+}
+
+/// A ChaCha20 random number generator.
+/// 
+/// The key is replaced by generator output after every request, so a later 
+/// compromise of the generator doesn't reveal earlier output.
+pub struct Generator {
+    key: [u32; KEY_WORDS],
+    counter: u64
+}
+
+impl Generator {
+
+    /// Create a generator with the given key.
+    pub fn new(key: [u32; KEY_WORDS]) -> Generator {
+        Generator { key, counter: 0 }
+    }
+
+    /// Mix a new key into the generator's key.
+    pub fn reseed(&mut self, key: [u32; KEY_WORDS]) {
+        for (word, new) in self.key.iter_mut().zip(key.iter()) {
+            *word ^= new;
+        }
+        self.rekey();
+    }
+
+    /// Fill `buf` with random bytes.
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(POOL_WORDS * 4) {
+            let block = self.next_block();
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = block[i / 4].to_le_bytes()[i % 4];
+            }
+        }
+        self.rekey();
+    }
+
+    /// Replace the key with the next block of output.
+    fn rekey(&mut self) {
+        let block = self.next_block();
+        self.key.copy_from_slice(&block[..KEY_WORDS]);
+    }
+
+    /// Generate the next block of output.
+    fn next_block(&mut self) -> [u32; POOL_WORDS] {
+        let counter = self.counter;
+        self.counter = self.counter.wrapping_add(1);
+        block(&self.key, counter as u32, [(counter >> 32) as u32, 0, 0])
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Compute a ChaCha20 block, as defined in RFC 7539.
+pub fn block(key: &[u32; KEY_WORDS], counter: u32, nonce: [u32; 3]) 
+    -> [u32; POOL_WORDS] {
+
+    let mut state = [0u32; POOL_WORDS];
+    state[..4].copy_from_slice(&CHACHA_CONSTANTS);
+    state[4..12].copy_from_slice(key);
+    state[12] = counter;
+    state[13..].copy_from_slice(&nonce);
+
+    let mut output = state;
+    permute(&mut output);
+    for (word, input) in output.iter_mut().zip(state.iter()) {
+        *word = word.wrapping_add(*input);
+    }
+    output
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Apply the ChaCha20 rounds to the state, without the final addition.
+fn permute(state: &mut [u32; POOL_WORDS]) {
+    for _ in 0..DOUBLE_ROUNDS {
+        quarter_round(state, 0, 4, 8, 12);
+        quarter_round(state, 1, 5, 9, 13);
+        quarter_round(state, 2, 6, 10, 14);
+        quarter_round(state, 3, 7, 11, 15);
+        quarter_round(state, 0, 5, 10, 15);
+        quarter_round(state, 1, 6, 11, 12);
+        quarter_round(state, 2, 7, 8, 13);
+        quarter_round(state, 3, 4, 9, 14);
+    }
+}
+
+/// The ChaCha quarter round.
+fn quarter_round(s: &mut [u32; POOL_WORDS], a: usize, b: usize, c: usize, 
+    d: usize) {
+
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chacha_block_test_vector() {
+        // RFC 7539 section 2.3.2
+        let mut key = [0u32; KEY_WORDS];
+        for (i, word) in key.iter_mut().enumerate() {
+            let b = 4 * i as u8;
+            *word = u32::from_le_bytes([b, b + 1, b + 2, b + 3]);
+        }
+        let output = block(&key, 1, [0x0900_0000, 0x4a00_0000, 0]);
+
+        assert_eq!(output, [
+            0xe4e7_f110, 0x1559_3bd1, 0x1fdd_0f50, 0xc471_20a3,
+            0xc7f4_d1c7, 0x0368_c033, 0x9aaa_2204, 0x4e6c_d4c3,
+            0x4664_82d2, 0x09aa_9f07, 0x05d7_c214, 0xa202_8bd9,
+            0xd19c_12b5, 0xb94e_16de, 0xe883_d0cb, 0x4e3c_50a2
+        ]);
+    }
+
+    #[test]
+    fn only_irregular_timing_is_credited() {
+        let mut pool = Pool::new();
+        for i in 1..10 {
+            pool.add_timing(i * 100, true);
+        }
+        // Only the first event has a changing delta
+        assert_eq!(pool.credit(), 1);
+
+        pool.add_timing(1234, true);
+        pool.add_timing(1300, false);
+        assert_eq!(pool.credit(), 2);
+    }
+
+    #[test]
+    fn extraction_depends_on_samples() {
+        let mut a = Pool::new();
+        let mut b = Pool::new();
+        a.mix(1);
+        b.mix(2);
+        assert_ne!(a.extract(), b.extract());
+
+        // Extracting changes the pool, and clears the credit
+        a.add_timing(5, true);
+        let first = a.extract();
+        assert_eq!(a.credit(), 0);
+        assert_ne!(first, a.extract());
+    }
+
+    #[test]
+    fn generator_output_changes() {
+        let mut generator = Generator::new([7; KEY_WORDS]);
+        let mut first = [0u8; 100];
+        let mut second = [0u8; 100];
+        generator.fill(&mut first);
+        generator.fill(&mut second);
+        assert_ne!(first[..], second[..]);
+        assert!(first.iter().any(|&b| b != 0));
+    }
+}
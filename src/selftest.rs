@@ -0,0 +1,190 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use alloc::{boxed::Box, vec::Vec};
+use crate::{println, allocator, interrupts, rng, vga_buffer};
+use crate::sync::WaitQueue;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of `hlt`s to wait for the timer to tick before failing.
+const TIMER_WAIT_HALTS: usize = 64;
+
+/// The self tests, a curated set of the `#[test_case]` checks which are safe 
+/// to run on a live system.
+pub const TESTS: [SelfTest; 6] = [
+    SelfTest { name: "allocator::box", run: test_box },
+    SelfTest { name: "allocator::vec", run: test_vec },
+    SelfTest { name: "allocator::reuse", run: test_reuse },
+    SelfTest { name: "vga_buffer::println", run: test_println },
+    SelfTest { name: "interrupts::timer", run: test_timer },
+    SelfTest { name: "rng::output", run: test_rng }
+];
+
+/// Set when a run has been requested and the runner hasn't started it.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Woken when a run is requested.
+static RUN_REQUESTED: WaitQueue = WaitQueue::new();
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A check which can be run on a live system.
+#[derive(Clone, Copy)]
+pub struct SelfTest {
+    pub name: &'static str,
+
+    /// Run the test, returning why it failed.
+    pub run: fn() -> Result<(), &'static str>
+}
+
+/// The results of running the self tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfTestReport {
+    pub passed: usize,
+    pub failed: usize
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Self test: {} passed, {} failed", self.passed, self.failed)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Run every self test, printing the result of each.
+pub fn run() -> SelfTestReport {
+    let mut report = SelfTestReport { passed: 0, failed: 0 };
+
+    for test in TESTS.iter() {
+        match (test.run)() {
+            Ok(()) => {
+                println!("[SELFTEST] {} ... ok", test.name);
+                report.passed += 1;
+            },
+            Err(reason) => {
+                println!("[SELFTEST] {} ... FAILED: {}", test.name, reason);
+                report.failed += 1;
+            }
+        }
+    }
+
+    println!("[SELFTEST] {}", report);
+    report
+}
+
+/// Request a run of the self tests by the `runner` task.
+/// 
+/// This can be called from interrupt handlers, such as the serial escape 
+/// commands, where the tests themselves can't be run.
+pub fn request() {
+    if !REQUESTED.swap(true, Ordering::AcqRel) {
+        RUN_REQUESTED.wake_all();
+    }
+}
+
+/// Background task which runs the self tests whenever they are requested.
+pub async fn runner() {
+    loop {
+        RUN_REQUESTED.wait_until(|| REQUESTED.swap(false, Ordering::AcqRel))
+            .await;
+        run();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// SELF TESTS
+// ---------------------------------------------------------------------------
+
+fn test_box() -> Result<(), &'static str> {
+    let a = Box::new(41);
+    let b = Box::new(1);
+    check(*a + *b == 42, "boxed values corrupted")
+}
+
+fn test_vec() -> Result<(), &'static str> {
+    let n = 100;
+    let v: Vec<u64> = (0..n).collect();
+    check(v.iter().sum::<u64>() == (n - 1) * n / 2, "vector sum wrong")
+}
+
+/// Allocate more than the whole heap in total, which only succeeds if freed
+/// memory is reused.
+fn test_reuse() -> Result<(), &'static str> {
+    for i in 0..allocator::HEAP_SIZE {
+        let x = Box::new(i);
+        if *x != i {
+            return Err("boxed value corrupted");
+        }
+    }
+    Ok(())
+}
+
+/// Print a line and check it reached the VGA buffer.
+fn test_println() -> Result<(), &'static str> {
+    let s = "[SELFTEST] vga_buffer::println output";
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        println!("{}", s);
+        let row = vga_buffer::position().0 - 1;
+        let written = s.bytes().enumerate()
+            .all(|(i, c)| vga_buffer::read_char(row, i) == c);
+        check(written, "line not in the VGA buffer")
+    })
+}
+
+/// Check timer interrupts are arriving.
+fn test_timer() -> Result<(), &'static str> {
+    if !x86_64::instructions::interrupts::are_enabled() {
+        return Err("interrupts are disabled");
+    }
+
+    let start = interrupts::ticks();
+    for _ in 0..TIMER_WAIT_HALTS {
+        x86_64::instructions::hlt();
+        if interrupts::ticks() != start {
+            return Ok(());
+        }
+    }
+    Err("no timer interrupt")
+}
+
+fn test_rng() -> Result<(), &'static str> {
+    check(rng::next_u64() != rng::next_u64(), "repeated output")
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Turn a condition into a test result.
+fn check(ok: bool, reason: &'static str) -> Result<(), &'static str> {
+    if ok {
+        Ok(())
+    }
+    else {
+        Err(reason)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_self_tests_pass() {
+    use crate::{serial_print, serial_println};
+    serial_print!("selftest::self_tests_pass ");
+    assert_eq!(run().failed, 0);
+    serial_println!("[ok]");
+}
@@ -0,0 +1,137 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod threshold;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::arch::x86_64::__cpuid;
+use x86_64::registers::model_specific::Msr;
+use crate::event;
+use crate::interrupts::TIMER_FREQUENCY_HZ;
+use crate::task::workqueue;
+
+pub use threshold::Reading;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The temperature at or above which a warning event is published.
+pub const WARNING_CELSIUS: i32 = 90;
+
+/// MSR giving the digital thermal sensor readout.
+const IA32_THERM_STATUS: u32 = 0x19c;
+
+/// MSR giving the temperature target, TjMax.
+const MSR_TEMPERATURE_TARGET: u32 = 0x1a2;
+
+/// TjMax to assume if the target MSR reads as zero.
+const DEFAULT_TJ_MAX: i32 = 100;
+
+/// The number of timer ticks between threshold checks.
+const CHECK_INTERVAL_TICKS: u64 = 5 * TIMER_FREQUENCY_HZ;
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read every available temperature sensor into `readings`, returning the 
+/// number of readings made.
+/// 
+/// Currently the only sensor is the CPU's digital thermal sensor. ACPI 
+/// thermal zones report their temperature through AML methods, so aren't 
+/// read until there is an AML interpreter.
+pub fn read_all(readings: &mut [Reading]) -> usize {
+    let sensors = [cpu_temperature().map(|celsius| Reading {
+        name: "cpu",
+        celsius
+    })];
+
+    let mut count = 0;
+    for (slot, reading) in readings.iter_mut()
+        .zip(sensors.iter().flatten()) {
+        *slot = *reading;
+        count += 1;
+    }
+    count
+}
+
+/// Read the CPU package temperature from the digital thermal sensor, if the 
+/// CPU has one.
+pub fn cpu_temperature() -> Option<i32> {
+    if !has_digital_thermal_sensor() {
+        return None;
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  Both MSRs exist on Intel CPUs reporting a digital thermal sensor.
+    let (status, target) = unsafe {
+        (Msr::new(IA32_THERM_STATUS).read(), 
+            Msr::new(MSR_TEMPERATURE_TARGET).read())
+    };
+
+    // The readout is only valid if bit 31 is set
+    if status & (1 << 31) == 0 {
+        return None;
+    }
+
+    let tj_max = match ((target >> 16) & 0xff) as i32 {
+        0 => DEFAULT_TJ_MAX,
+        tj_max => tj_max
+    };
+    let below_tj_max = ((status >> 16) & 0x7f) as i32;
+
+    Some(tj_max - below_tj_max)
+}
+
+/// Read the sensors and publish a thermal warning event for any at or above 
+/// `WARNING_CELSIUS`.
+/// 
+/// Returns the hottest reading, if any sensor could be read.
+pub fn check_thresholds() -> Option<Reading> {
+    let mut readings = [Reading { name: "", celsius: 0 }; 4];
+    let count = read_all(&mut readings);
+
+    threshold::check(&readings[..count], WARNING_CELSIUS, 
+        |reading| event::THERMAL_WARNING.publish(reading))
+}
+
+/// Schedule a threshold check if one is due.
+/// 
+/// Should be called from the timer interrupt handler. Events can't be 
+/// published from the handler, so the check is run on the system work queue.
+pub(crate) fn timer_tick(ticks: u64) {
+    if ticks % CHECK_INTERVAL_TICKS == 0 {
+        // A check dropped because the queue is full or not yet initialised 
+        // is just picked up at the next interval
+        let _ = workqueue::SYSTEM.queue(check_work, 0);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Work queue item running `check_thresholds`.
+fn check_work(_data: usize) {
+    check_thresholds();
+}
+
+/// Returns `true` if the CPU is an Intel CPU with a digital thermal sensor.
+fn has_digital_thermal_sensor() -> bool {
+    // NOTE: USE OF UNSAFE
+    //  CPUID is always available in long mode, and leaf 6 is only read if it
+    //  exists.
+    unsafe {
+        let leaf0 = __cpuid(0);
+        let intel = leaf0.ebx == 0x756e_6547 
+            && leaf0.edx == 0x4965_6e69 
+            && leaf0.ecx == 0x6c65_746e;
+
+        intel && leaf0.eax >= 6 && __cpuid(6).eax & 1 != 0
+    }
+}
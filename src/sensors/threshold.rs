@@ -0,0 +1,74 @@
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A temperature reading from a sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    /// The name of the sensor.
+    pub name: &'static str,
+
+    /// The temperature in degrees Celsius.
+    pub celsius: i32
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Call `warn` with every reading at or above `warning_celsius`, in order.
+/// 
+/// Returns the hottest reading, or `None` if there are no readings.
+pub fn check(
+    readings: &[Reading], 
+    warning_celsius: i32, 
+    mut warn: impl FnMut(Reading)
+) -> Option<Reading> {
+    for reading in readings.iter() {
+        if reading.celsius >= warning_celsius {
+            warn(*reading);
+        }
+    }
+
+    readings.iter().copied().max_by_key(|r| r.celsius)
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    const CPU: Reading = Reading { name: "cpu", celsius: 85 };
+    const GPU: Reading = Reading { name: "gpu", celsius: 95 };
+    const DISK: Reading = Reading { name: "disk", celsius: 40 };
+
+    #[test]
+    fn no_readings() {
+        let mut warned = 0;
+        assert_eq!(check(&[], 90, |_| warned += 1), None);
+        assert_eq!(warned, 0);
+    }
+
+    #[test]
+    fn below_threshold() {
+        let mut warned = 0;
+        assert_eq!(check(&[CPU, DISK], 90, |_| warned += 1), Some(CPU));
+        assert_eq!(warned, 0);
+    }
+
+    #[test]
+    fn warns_at_and_above_threshold() {
+        let mut warned = [None; 3];
+        let mut count = 0;
+        let hottest = check(&[CPU, GPU, DISK], 85, |r| {
+            warned[count] = Some(r);
+            count += 1;
+        });
+
+        assert_eq!(hottest, Some(GPU));
+        assert_eq!(warned, [Some(CPU), Some(GPU), None]);
+    }
+}
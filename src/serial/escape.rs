@@ -0,0 +1,181 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use conquer_once::spin::OnceCell;
+use crossbeam_queue::ArrayQueue;
+use x86_64::structures::idt::InterruptStackFrame;
+use crate::emergency_println;
+use crate::sysrq::{self, Action};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The character which starts an escape sequence at the start of a line.
+pub const ESCAPE_CHAR: u8 = b'~';
+
+/// The number of received bytes buffered for `try_receive`.
+const INPUT_QUEUE_SIZE: usize = 256;
+
+/// Bytes received by the serial interrupt which weren't part of an escape.
+static INPUT_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+
+/// The escape parser state, one of the `STATE_*` values.
+static STATE: AtomicU8 = AtomicU8::new(STATE_LINE_START);
+
+/// Whether escape sequences are processed, cleared during binary transfers.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// At the start of a line, where `~` starts an escape.
+const STATE_LINE_START: u8 = 0;
+
+/// In the middle of a line.
+const STATE_MID_LINE: u8 = 1;
+
+/// After a `~` at the start of a line, waiting for the command.
+const STATE_ESCAPE: u8 = 2;
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Initialise the input queue, after which received bytes are buffered by 
+/// the serial interrupt.
+/// 
+/// This must be called once the heap is initialised.
+pub fn init() {
+    INPUT_QUEUE.try_init_once(|| ArrayQueue::new(INPUT_QUEUE_SIZE))
+        .expect("escape::init must only be called once");
+}
+
+/// Enable or disable escape processing.
+/// 
+/// This should be disabled during binary transfers, where the data could 
+/// contain an escape sequence.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    STATE.store(STATE_LINE_START, Ordering::Relaxed);
+}
+
+/// Pop a buffered byte received by the serial interrupt.
+pub(crate) fn pop_input() -> Option<u8> {
+    INPUT_QUEUE.try_get().ok()?.pop().ok()
+}
+
+/// Returns `true` once received bytes are being buffered.
+pub(crate) fn is_buffering() -> bool {
+    INPUT_QUEUE.try_get().is_ok()
+}
+
+/// Process a byte received by the serial interrupt.
+/// 
+/// A `~` at the start of a line followed by a command character runs the 
+/// command straight from the interrupt, so they work even when every task is
+/// stuck:
+/// 
+/// - `~d` dumps the interrupted registers
+/// - `~t` prints the task counts
+/// - `~m` prints heap statistics
+/// - `~r` reboots
+/// - `~p` panics
+/// - `~s` runs the self tests, once the executor gets to them
+/// - `~h` prints the hardware summary
+/// - `~j` dumps the trace events as chrome tracing JSON
+/// - `~l` prints the interrupt latency histograms
+/// - `~f` prints the CPU frequency and idle statistics
+/// - `~~` sends a single `~`
+/// - `~?` lists the commands
+/// 
+/// Anything else is buffered for `try_receive`.
+pub(crate) fn handle_byte(byte: u8, stack_frame: &InterruptStackFrame) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        push_input(byte);
+        return;
+    }
+
+    let state = STATE.load(Ordering::Relaxed);
+    let next_state = match (state, byte) {
+        (STATE_LINE_START, ESCAPE_CHAR) => STATE_ESCAPE,
+        (STATE_ESCAPE, ESCAPE_CHAR) => {
+            push_input(ESCAPE_CHAR);
+            STATE_MID_LINE
+        },
+        (STATE_ESCAPE, command) => {
+            if !run_command(command, stack_frame) {
+                // Not a command, so pass the whole sequence through
+                push_input(ESCAPE_CHAR);
+                push_input(command);
+            }
+            STATE_LINE_START
+        },
+        (_, b'\r') | (_, b'\n') => {
+            push_input(byte);
+            STATE_LINE_START
+        },
+        _ => {
+            push_input(byte);
+            STATE_MID_LINE
+        }
+    };
+    STATE.store(next_state, Ordering::Relaxed);
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Buffer a received byte, dropping it if the queue is full or not yet 
+/// initialised.
+fn push_input(byte: u8) {
+    if let Ok(queue) = INPUT_QUEUE.try_get() {
+        let _ = queue.push(byte);
+    }
+}
+
+/// Run an escape command, returning `false` if the byte isn't a command.
+fn run_command(command: u8, stack_frame: &InterruptStackFrame) -> bool {
+    let action = match command {
+        b'd' => Action::Registers,
+        b't' => Action::Tasks,
+        b'm' => Action::Memory,
+        b'r' => Action::Reboot,
+        b'p' => Action::Panic,
+        b's' => {
+            emergency_println!("\n[ESC] Self test requested");
+            crate::selftest::request();
+            return true;
+        },
+        b'h' => {
+            match crate::hwinfo::report() {
+                Some(info) => emergency_println!("\n[ESC] {}", info),
+                None => emergency_println!("\n[ESC] Hardware not yet probed")
+            }
+            return true;
+        },
+        b'j' => {
+            crate::trace::dump();
+            return true;
+        },
+        b'l' => {
+            emergency_println!("\n[ESC] {}", 
+                crate::interrupts::latency::report());
+            return true;
+        },
+        b'f' => {
+            emergency_println!("\n[ESC] {}", crate::power::cpufreq::stats());
+            return true;
+        },
+        b'?' => {
+            emergency_println!("\n[ESC] ~d registers, ~t tasks, ~m memory, \
+                ~r reboot, ~p panic, ~s self test, ~h hardware, \
+                ~j trace, ~l IRQ latency, ~f CPU frequency, ~~ literal ~");
+            return true;
+        },
+        _ => return false
+    };
+
+    sysrq::run(action, stack_frame);
+    true
+}
@@ -0,0 +1,191 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod escape;
+pub mod xmodem;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use uart_16550::SerialPort;
+use spin::Mutex;
+use lazy_static::lazy_static;
+use core::fmt::Write;
+use conquer_once::spin::OnceCell;
+use crate::ioport::PortRange;
+
+// ---------------------------------------------------------------------------
+// SERIAL PORT OBJECTS AND CONSTANTS
+// ---------------------------------------------------------------------------
+
+lazy_static! {
+    /// Serial port 1, using port `0x3F8`.
+    pub static ref SERIAL1: Mutex<SerialPort> = {
+
+        // NOTE: USE OF UNSAFE
+        //  Unsafe usage here is because the argument to `SerialPort::new()` 
+        //  must point to a valid serial port device.
+        let mut serial_port = unsafe { SerialPort::new(SERIAL1_BASE) };
+        serial_port.init();
+        Mutex::new(serial_port)
+    };
+}
+
+pub const SERIAL_WIDTH: usize = 80;
+
+/// The base I/O port of serial port 1.
+const SERIAL1_BASE: u16 = 0x3F8;
+
+/// Offset of the line status register from the base port.
+const LINE_STATUS_OFFSET: u16 = 5;
+
+/// Line status bit set when a received byte is waiting.
+const LINE_STATUS_DATA_READY: u8 = 1;
+
+/// The number of ports used by a 16550 UART.
+const UART_PORT_COUNT: u16 = 8;
+
+/// The claimed serial port 1 ports.
+/// 
+/// The UART driver accesses the ports itself, the claim stops any other 
+/// driver using them and gives access to the line status register.
+static SERIAL1_PORTS: OnceCell<PortRange> = OnceCell::uninit();
+
+// ---------------------------------------------------------------------------
+// MACRO DEFINITIONS
+// ---------------------------------------------------------------------------
+
+/// Serial equivalent of print!.
+/// 
+/// This macro will print it's argument to serial port SERIAL1.
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => {
+        $crate::serial::_print(format_args!($($arg)*));
+    };
+}
+
+/// Serial equivalent of println!.
+/// 
+/// This macro will print it's argument to serial port SERIAL1 followed by a 
+/// newline.
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($fmt:expr) => ($crate::serial_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(
+        concat!($fmt, "\n"), $($arg)*));
+}
+
+/// Lock-free equivalent of serial_println!.
+/// 
+/// This macro prints to serial port 1 without taking the `SERIAL1` lock, so 
+/// it can be used from paths which may have interrupted the holder of the 
+/// lock, such as the NMI handler. Output may be interleaved with other prints.
+#[macro_export]
+macro_rules! emergency_println {
+    () => ($crate::serial::_emergency_print(format_args!("\n")));
+    ($fmt:expr) => ($crate::serial::_emergency_print(
+        format_args!(concat!($fmt, "\n"))));
+    ($fmt:expr, $($arg:tt)*) => ($crate::serial::_emergency_print(
+        format_args!(concat!($fmt, "\n"), $($arg)*)));
+}
+
+// ---------------------------------------------------------------------------
+// FUNCTION DEFINITIONS
+// ---------------------------------------------------------------------------
+
+#[doc(hidden)]
+pub fn _print(args: ::core::fmt::Arguments) {
+    // Disable interrupts for this print to ensure we don't get a deadlock
+    // while printing to the serial port.
+    x86_64::instructions::interrupts::without_interrupts(||
+        SERIAL1.lock().write_fmt(args)
+            .expect("Unable to print to serial port 1")
+    );
+}
+
+#[doc(hidden)]
+pub fn _emergency_print(args: ::core::fmt::Arguments) {
+    // NOTE: USE OF UNSAFE
+    //  This creates a second handle to the already initialised SERIAL1 port,
+    //  bypassing the lock. Writes may interleave with a print in progress but
+    //  cannot deadlock.
+    let mut serial_port = unsafe { SerialPort::new(SERIAL1_BASE) };
+
+    // Nothing useful can be done if this fails
+    let _ = serial_port.write_fmt(args);
+}
+
+/// Read a byte from serial port 1 if one has been received.
+/// 
+/// Once `escape::init` has been called bytes are received by the serial 
+/// interrupt, and come from its buffer instead.
+pub fn try_receive() -> Option<u8> {
+    if escape::is_buffering() {
+        return escape::pop_input();
+    }
+
+    let ports = SERIAL1_PORTS.try_get().ok()?;
+
+    // The data register is only read when it holds a received byte. The 
+    // `SERIAL1` lock is held so this doesn't interleave with a send.
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let _lock = SERIAL1.lock();
+        if ports.read_u8(LINE_STATUS_OFFSET) & LINE_STATUS_DATA_READY != 0 {
+            Some(ports.read_u8(0))
+        }
+        else {
+            None
+        }
+    })
+}
+
+/// Claim the serial port 1 ports, which must be done before bytes can be 
+/// received.
+pub fn init_ports() {
+    match PortRange::claim(SERIAL1_BASE, UART_PORT_COUNT, "serial1") {
+        Ok(ports) => {
+            SERIAL1_PORTS.try_init_once(|| ports)
+                .expect("serial::init_ports must only be called once");
+        },
+        Err(e) => serial_println!("[SERIAL-ERROR] Cannot claim ports: {}", e)
+    }
+}
+
+/// Handle the serial port 1 interrupt, passing every received byte to the 
+/// escape processor.
+/// 
+/// The `SERIAL1` lock isn't taken since every holder disables interrupts, so
+/// it can't be held while this runs.
+pub(crate) fn handle_interrupt(
+    stack_frame: &x86_64::structures::idt::InterruptStackFrame
+) {
+    let ports = match SERIAL1_PORTS.try_get() {
+        Ok(ports) => ports,
+        Err(_) => return
+    };
+
+    while ports.read_u8(LINE_STATUS_OFFSET) & LINE_STATUS_DATA_READY != 0 {
+        escape::handle_byte(ports.read_u8(0), stack_frame);
+    }
+}
+
+/// Send raw bytes to serial port 1.
+/// 
+/// Interrupts are disabled while sending so that a print from an interrupt 
+/// can't deadlock on the port or be inserted into the middle of the bytes.
+pub fn send_bytes(bytes: &[u8]) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut serial = SERIAL1.lock();
+        for &byte in bytes {
+            serial.send(byte);
+        }
+    });
+}
+
+pub fn divider(chr: u8) {
+    serial_println!("\n{}", core::str::from_utf8(&[chr; SERIAL_WIDTH]).unwrap());
+}
@@ -0,0 +1,215 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod packet;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::{vec, vec::Vec};
+use super::{escape, send_bytes, try_receive};
+use crate::interrupts;
+use packet::{parse, Packet, PacketError, PACKET_SIZE};
+
+pub use packet::PACKET_DATA_SIZE;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// Start of a 128 byte packet.
+const SOH: u8 = 0x01;
+
+/// End of transmission.
+const EOT: u8 = 0x04;
+
+/// Packet acknowledged.
+const ACK: u8 = 0x06;
+
+/// Packet rejected, resend.
+const NAK: u8 = 0x15;
+
+/// Cancel the transfer.
+const CAN: u8 = 0x18;
+
+/// Sent by the receiver to request the CRC variant of the protocol.
+const CRC_REQUEST: u8 = b'C';
+
+/// The padding byte used to fill the last packet.
+const PADDING: u8 = 0x1A;
+
+/// Ticks to wait for each byte before sending a NAK, about 3 seconds.
+const BYTE_TIMEOUT_TICKS: u64 = 3 * interrupts::TIMER_FREQUENCY_HZ;
+
+/// Number of timeouts or bad packets in a row before giving up.
+const MAX_RETRIES: usize = 10;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Errors ending an XMODEM transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmodemError {
+    /// The sender stopped responding or kept sending bad packets.
+    Timeout,
+
+    /// The sender cancelled the transfer.
+    Cancelled,
+
+    /// A packet arrived out of sequence.
+    OutOfSequence,
+
+    /// The file didn't fit in the buffer.
+    BufferFull
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Receive a file over serial port 1 using XMODEM-CRC into `buf`, returning 
+/// the length of the file.
+/// 
+/// Trailing padding bytes (`0x1A`) in the last packet are removed, since 
+/// XMODEM doesn't send the real file length. This blocks until the transfer 
+/// completes, so nothing else should be using serial port 1 at the same time.
+/// Interrupts must be enabled so that timeouts work.
+pub fn receive_into(buf: &mut [u8]) -> Result<usize, XmodemError> {
+    // The file data could contain escape sequences
+    escape::set_enabled(false);
+    let result = receive_packets(buf);
+    escape::set_enabled(true);
+    result
+}
+
+/// Receive a file of at most `max_len` bytes over serial port 1 using 
+/// XMODEM-CRC.
+/// 
+/// See `receive_into`.
+pub fn receive(max_len: usize) -> Result<Vec<u8>, XmodemError> {
+    // Round up to whole packets so a file of exactly max_len fits
+    let packets = (max_len + PACKET_DATA_SIZE - 1) / PACKET_DATA_SIZE;
+    let mut buf = vec![0u8; packets * PACKET_DATA_SIZE];
+
+    let len = receive_into(&mut buf)?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Run the receiving side of the protocol, see `receive_into`.
+fn receive_packets(buf: &mut [u8]) -> Result<usize, XmodemError> {
+    let mut expected_block: u8 = 1;
+    let mut len = 0;
+    let mut retries = 0;
+
+    // Ask the sender to start, in CRC mode
+    send_bytes(&[CRC_REQUEST]);
+
+    loop {
+        if retries > MAX_RETRIES {
+            cancel();
+            return Err(XmodemError::Timeout);
+        }
+
+        // Wait for the start of the next packet
+        let byte = match receive_timeout() {
+            Some(byte) => byte,
+            None => {
+                retries += 1;
+
+                // Keep asking for CRC mode until the first packet arrives
+                let retry = if len == 0 { CRC_REQUEST } else { NAK };
+                send_bytes(&[retry]);
+                continue;
+            }
+        };
+
+        match byte {
+            SOH => (),
+            EOT => {
+                send_bytes(&[ACK]);
+                while len > 0 && buf[len - 1] == PADDING {
+                    len -= 1;
+                }
+                return Ok(len);
+            },
+            CAN => return Err(XmodemError::Cancelled),
+
+            // Ignore any noise between packets
+            _ => continue
+        }
+
+        // Read the block number, its complement, the data and the CRC
+        let mut packet = [0u8; PACKET_SIZE];
+        if !receive_exact(&mut packet) {
+            retries += 1;
+            send_bytes(&[NAK]);
+            continue;
+        }
+
+        let data = match parse(&packet, expected_block) {
+            Ok(Packet::Data(data)) => data,
+            Err(PacketError::Corrupt) => {
+                retries += 1;
+                send_bytes(&[NAK]);
+                continue;
+            },
+
+            // A repeat of the last block means our ACK was lost
+            Ok(Packet::Repeat) => {
+                send_bytes(&[ACK]);
+                continue;
+            },
+            Err(PacketError::OutOfSequence) => {
+                cancel();
+                return Err(XmodemError::OutOfSequence);
+            }
+        };
+
+        if len + PACKET_DATA_SIZE > buf.len() {
+            cancel();
+            return Err(XmodemError::BufferFull);
+        }
+
+        buf[len..len + PACKET_DATA_SIZE].copy_from_slice(data);
+        len += PACKET_DATA_SIZE;
+        expected_block = expected_block.wrapping_add(1);
+        retries = 0;
+        send_bytes(&[ACK]);
+    }
+}
+
+/// Wait for a single byte, returning `None` on timeout.
+fn receive_timeout() -> Option<u8> {
+    let deadline = interrupts::ticks() + BYTE_TIMEOUT_TICKS;
+    while interrupts::ticks() < deadline {
+        if let Some(byte) = try_receive() {
+            return Some(byte);
+        }
+        core::sync::atomic::spin_loop_hint();
+    }
+    None
+}
+
+/// Fill `buf` with received bytes, returning `false` on timeout.
+fn receive_exact(buf: &mut [u8]) -> bool {
+    for byte in buf.iter_mut() {
+        match receive_timeout() {
+            Some(b) => *byte = b,
+            None => return false
+        }
+    }
+    true
+}
+
+/// Tell the sender to cancel the transfer.
+fn cancel() {
+    send_bytes(&[CAN, CAN]);
+}
@@ -0,0 +1,138 @@
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The size of the data in each packet.
+pub const PACKET_DATA_SIZE: usize = 128;
+
+/// The size of a packet following the `SOH` byte: the block number, its 
+/// complement, the data and the CRC.
+pub const PACKET_SIZE: usize = 2 + PACKET_DATA_SIZE + 2;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A packet which passed its checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packet<'a> {
+    /// The expected block, with its data.
+    Data(&'a [u8]),
+
+    /// A repeat of the previous block, sent because its ACK was lost.
+    Repeat
+}
+
+/// Errors which can occur when checking a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    /// The block number complement or CRC is wrong, so it should be resent.
+    Corrupt,
+
+    /// The block isn't the expected one or a repeat of the previous one.
+    OutOfSequence
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Check a received packet, which should carry block `expected_block`.
+pub fn parse(packet: &[u8; PACKET_SIZE], expected_block: u8) 
+    -> Result<Packet<'_>, PacketError> {
+
+    let (block, block_inv) = (packet[0], packet[1]);
+    let data = &packet[2..2 + PACKET_DATA_SIZE];
+    let crc = (packet[2 + PACKET_DATA_SIZE] as u16) << 8 
+        | packet[3 + PACKET_DATA_SIZE] as u16;
+
+    if block != !block_inv || crc16(data) != crc {
+        Err(PacketError::Corrupt)
+    }
+    else if block == expected_block {
+        Ok(Packet::Data(data))
+    }
+    else if block == expected_block.wrapping_sub(1) {
+        Ok(Packet::Repeat)
+    }
+    else {
+        Err(PacketError::OutOfSequence)
+    }
+}
+
+/// Calculate the CRC-16/XMODEM of `data`.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            }
+            else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    /// Build a valid packet carrying `block`, filled with `fill`.
+    fn packet(block: u8, fill: u8) -> [u8; PACKET_SIZE] {
+        let mut packet = [fill; PACKET_SIZE];
+        packet[0] = block;
+        packet[1] = !block;
+        let crc = crc16(&packet[2..2 + PACKET_DATA_SIZE]);
+        packet[2 + PACKET_DATA_SIZE..].copy_from_slice(&crc.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(crc16(&[]), 0);
+    }
+
+    #[test]
+    fn valid_packet() {
+        let packet = packet(1, 0x5a);
+        assert_eq!(parse(&packet, 1), 
+            Ok(Packet::Data(&[0x5a; PACKET_DATA_SIZE][..])));
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut packet = packet(1, 0x5a);
+        packet[PACKET_SIZE - 1] ^= 1;
+        assert_eq!(parse(&packet, 1), Err(PacketError::Corrupt));
+
+        // Corrupt data is caught by the same check
+        let mut packet = self::packet(1, 0x5a);
+        packet[2] ^= 0x80;
+        assert_eq!(parse(&packet, 1), Err(PacketError::Corrupt));
+    }
+
+    #[test]
+    fn bad_block_complement() {
+        let mut packet = packet(1, 0);
+        packet[1] = 0;
+        assert_eq!(parse(&packet, 1), Err(PacketError::Corrupt));
+    }
+
+    #[test]
+    fn wrong_sequence() {
+        assert_eq!(parse(&packet(3, 0), 1), Err(PacketError::OutOfSequence));
+
+        // The previous block is a repeat, including across the wrap
+        assert_eq!(parse(&packet(1, 0), 2), Ok(Packet::Repeat));
+        assert_eq!(parse(&packet(255, 0), 0), Ok(Packet::Repeat));
+    }
+}
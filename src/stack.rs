@@ -0,0 +1,150 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use crate::{gdt, log, log_error};
+use crate::memory::layout::{KERNEL_STACK, KERNEL_STACK_PAGES};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The stack space left for the code which handles a failed check, such as 
+/// printing the diagnostics.
+pub const DEFAULT_RESERVE: u64 = 8 * 1024;
+
+/// The size of a page.
+const PAGE_SIZE: u64 = 4096;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The usable range of a stack, excluding its guard page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackBounds {
+    /// The name of the stack, used in diagnostics.
+    pub name: &'static str,
+
+    /// The lowest usable address.
+    pub bottom: u64,
+
+    /// The address above the highest usable address, where the stack starts.
+    pub top: u64
+}
+
+impl StackBounds {
+    /// The number of bytes below `addr` before the guard page.
+    pub fn remaining(&self, addr: u64) -> u64 {
+        addr.saturating_sub(self.bottom)
+    }
+
+    /// The size of the stack.
+    pub fn size(&self) -> u64 {
+        self.top - self.bottom
+    }
+}
+
+/// Error returned by `check` when there isn't enough stack left.
+#[derive(Debug, Clone, Copy)]
+pub struct StackError {
+    /// The stack which is running out.
+    pub bounds: StackBounds,
+
+    /// The stack pointer at the check.
+    pub stack_pointer: u64,
+
+    /// The number of bytes required.
+    pub required: u64
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} stack has {} of {} bytes left at {:#x}, {} required", 
+            self.bounds.name, self.bounds.remaining(self.stack_pointer), 
+            self.bounds.size(), self.stack_pointer, self.required)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Find the bounds of the stack containing `addr`, if it is a known stack.
+pub fn bounds(addr: u64) -> Option<StackBounds> {
+    if KERNEL_STACK.contains(addr) {
+        // The bootloader leaves the first page unmapped as the guard page
+        let bottom = KERNEL_STACK.start + PAGE_SIZE;
+        let top = KERNEL_STACK.start + KERNEL_STACK_PAGES * PAGE_SIZE;
+        return Some(StackBounds { name: "kernel", bottom, top });
+    }
+
+    gdt::stack_bounds(addr)
+}
+
+/// Get the approximate current stack pointer.
+#[inline(always)]
+pub fn stack_pointer() -> u64 {
+    let marker = 0u8;
+    &marker as *const u8 as u64
+}
+
+/// Get the number of bytes left on the current stack, or `None` if it isn't
+/// a known stack.
+#[inline(always)]
+pub fn remaining() -> Option<u64> {
+    let stack_pointer = stack_pointer();
+    bounds(stack_pointer).map(|bounds| bounds.remaining(stack_pointer))
+}
+
+/// Check there are at least `required` bytes, plus `DEFAULT_RESERVE`, left 
+/// on the current stack.
+/// 
+/// Call this at the entry to recursive or deep code paths, which should 
+/// return an error instead of carrying on when it fails, so that running out
+/// of stack is reported rather than double faulting. The failure is logged 
+/// with the stack's bounds.
+/// 
+/// Passes if the current stack isn't a known stack.
+#[inline(always)]
+pub fn check(required: u64) -> Result<(), StackError> {
+    let stack_pointer = stack_pointer();
+    let bounds = match bounds(stack_pointer) {
+        Some(bounds) => bounds,
+        None => return Ok(())
+    };
+
+    if bounds.remaining(stack_pointer) >= required + DEFAULT_RESERVE {
+        return Ok(());
+    }
+
+    let error = StackError { bounds, stack_pointer, required };
+    log_error!(log::STACK, "{}", error);
+    Err(error)
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_stack_check() {
+    use crate::{serial_print, serial_println};
+    serial_print!("stack::check ");
+
+    // Tests run on the kernel stack
+    let bounds = bounds(stack_pointer()).unwrap();
+    assert_eq!(bounds.name, "kernel");
+    let left = remaining().unwrap();
+    assert!(left > 0 && left < bounds.size());
+
+    assert!(check(1024).is_ok());
+    let error = check(bounds.size()).unwrap_err();
+    assert_eq!(error.required, bounds.size());
+
+    // Addresses outside any known stack have no bounds
+    assert!(crate::stack::bounds(0x1000).is_none());
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,191 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+// NOTE: This module only depends on `core` so that it can be built and tested
+// on the host by the `host-tests` crate.
+
+use core::fmt;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Display wrapper which demangles a legacy Rust symbol name.
+///
+/// Names which are not mangled (or can't be parsed) are printed unchanged.
+/// The trailing hash component is dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct Demangle<'a>(pub &'a str);
+
+impl fmt::Display for Demangle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.0;
+
+        // Legacy mangled names are `_ZN` followed by length-prefixed path
+        // components and a terminating `E`.
+        if !(name.starts_with("_ZN") && name.ends_with('E'))
+            || !is_valid_legacy(&name[3..name.len() - 1]) {
+            return f.write_str(name);
+        }
+
+        let mut rest = &name[3..name.len() - 1];
+        let mut first = true;
+        while !rest.is_empty() {
+            let (ident, remaining) = split_component(rest)
+                .expect("Component already validated");
+            rest = remaining;
+
+            // Skip the hash on the end of the path
+            if rest.is_empty() && is_hash(ident) {
+                break;
+            }
+
+            if !first {
+                f.write_str("::")?;
+            }
+            write_ident(ident, f)?;
+            first = false;
+        }
+
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Split the first length-prefixed component off a legacy mangled path.
+fn split_component(s: &str) -> Option<(&str, &str)> {
+    let digits = s.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+
+    let len: usize = s[..digits].parse().ok()?;
+    let rest = &s[digits..];
+    if len > rest.len() || !rest.is_char_boundary(len) {
+        return None;
+    }
+
+    Some((&rest[..len], &rest[len..]))
+}
+
+/// Check that a legacy mangled path is made only of valid components.
+fn is_valid_legacy(mut s: &str) -> bool {
+    if s.is_empty() {
+        return false;
+    }
+
+    while !s.is_empty() {
+        match split_component(s) {
+            Some((_, rest)) => s = rest,
+            None => return false
+        }
+    }
+
+    true
+}
+
+/// Returns `true` if the component is a symbol hash, `h` followed by 16 hex
+/// digits.
+fn is_hash(ident: &str) -> bool {
+    ident.len() == 17
+        && ident.starts_with('h')
+        && ident[1..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Write a single path component, replacing the `$..$` escapes.
+fn write_ident(ident: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    // A leading underscore is added before an escape at the start of an ident
+    let mut rest = if ident.starts_with("_$") { &ident[1..] } else { ident };
+
+    while !rest.is_empty() {
+        if rest.starts_with("..") {
+            f.write_str("::")?;
+            rest = &rest[2..];
+        }
+        else if rest.starts_with('$') {
+            // Find the closing `$` of the escape
+            let end = match rest[1..].find('$') {
+                Some(end) => end + 1,
+                None => return f.write_str(rest)
+            };
+
+            let unescaped = match &rest[1..end] {
+                "SP" => "@",
+                "BP" => "*",
+                "RF" => "&",
+                "LT" => "<",
+                "GT" => ">",
+                "LP" => "(",
+                "RP" => ")",
+                "C" => ",",
+                "u20" => " ",
+                "u22" => "\"",
+                "u27" => "'",
+                "u2b" => "+",
+                "u3b" => ";",
+                "u5b" => "[",
+                "u5d" => "]",
+                "u7b" => "{",
+                "u7d" => "}",
+                "u7e" => "~",
+                _ => &rest[..=end]
+            };
+            f.write_str(unescaped)?;
+            rest = &rest[end + 1..];
+        }
+        else {
+            // Write everything up to the next escape in one go
+            let end = rest.find(&['$', '.'][..]).unwrap_or(rest.len());
+            let end = if end == 0 { 1 } else { end };
+            f.write_str(&rest[..end])?;
+            rest = &rest[end..];
+        }
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangle_path() {
+        assert_eq!(
+            Demangle(
+                "_ZN4scos10interrupts18page_fault_handler17h0123456789abcdefE"
+            ).to_string(),
+            "scos::interrupts::page_fault_handler");
+    }
+
+    #[test]
+    fn demangle_escapes() {
+        assert_eq!(
+            Demangle(concat!(
+                "_ZN53_$LT$scos..task..Task$u20$as$u20$core..fmt..Debug$GT$",
+                "3fmt17h0123456789abcdefE"
+            )).to_string(),
+            "<scos::task::Task as core::fmt::Debug>::fmt");
+    }
+
+    #[test]
+    fn unmangled_names_unchanged() {
+        assert_eq!(Demangle("_start").to_string(), "_start");
+        assert_eq!(Demangle("_ZN99tooshortE").to_string(), "_ZN99tooshortE");
+        assert_eq!(Demangle("_ZNE").to_string(), "_ZNE");
+    }
+
+    #[test]
+    fn keeps_non_hash_final_component() {
+        assert_eq!(Demangle("_ZN4core3fmt5writeE").to_string(),
+            "core::fmt::write");
+    }
+}
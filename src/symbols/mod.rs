@@ -0,0 +1,278 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::{fmt, ptr, slice, str};
+
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod demangle;
+pub use demangle::Demangle;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The size of the space reserved for the symbol table.
+pub const SYMTAB_SIZE: usize = 64 * 1024;
+
+/// Magic bytes at the start of a populated symbol table.
+const SYMTAB_MAGIC: [u8; 4] = *b"KSYM";
+
+/// The size of the table header (magic and entry count).
+const HEADER_SIZE: usize = 8;
+
+/// The size of each entry (address, name offset and name length).
+const ENTRY_SIZE: usize = 16;
+
+/// The maximum number of frames written by `write_backtrace`.
+const MAX_BACKTRACE_FRAMES: usize = 32;
+
+/// The kernel symbol table.
+///
+/// This is reserved empty at compile time and filled in after linking by
+/// `tools/embed_symbols.py`, which writes a sorted table of the kernel's
+/// function symbols into the `.ksymtab` section. The layout is:
+///
+/// - `[0..4]`: the magic bytes `KSYM`
+/// - `[4..8]`: the number of entries, `n`
+/// - `n` entries of `(addr: u64, name_offset: u32, name_len: u32)`, sorted by
+///   address
+/// - the symbol names, with offsets relative to the end of the entries
+///
+/// All values are little endian.
+///
+/// NOTE: This is a `no_mangle` mutable static so the compiler cannot assume
+/// its contents are the zeroes it was declared with.
+#[no_mangle]
+#[link_section = ".ksymtab"]
+static mut KERNEL_SYMTAB: [u8; SYMTAB_SIZE] = [0; SYMTAB_SIZE];
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A symbol resolved from an address.
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+    /// The mangled name of the symbol.
+    pub name: &'static str,
+
+    /// The symbol's start address.
+    pub addr: u64,
+
+    /// The offset of the resolved address from the start of the symbol.
+    pub offset: u64
+}
+
+impl Symbol {
+    /// Get a displayable, demangled version of the name.
+    pub fn demangled(&self) -> Demangle<'static> {
+        Demangle(self.name)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}+{:#x}", self.demangled(), self.offset)
+    }
+}
+
+/// Writer printing to serial port 1 without taking its lock, used by 
+/// `print_backtrace`.
+struct EmergencySerial;
+
+impl fmt::Write for EmergencySerial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        crate::serial::_emergency_print(format_args!("{}", s));
+        Ok(())
+    }
+}
+
+/// Display wrapper which prints an address along with the symbol it is in,
+/// if it could be resolved.
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolisedAddr(pub u64);
+
+impl fmt::Display for SymbolisedAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match resolve(self.0) {
+            Some(sym) => write!(f, "{:#x} <{}>", self.0, sym),
+            None => write!(f, "{:#x} <unknown>", self.0)
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Resolve an address into the symbol containing it.
+///
+/// Returns `None` if the symbol table has not been embedded or the address
+/// is below the first symbol.
+pub fn resolve(addr: u64) -> Option<Symbol> {
+    let count = entry_count()?;
+
+    // Binary search for the last entry with a start address <= addr
+    let (mut lo, mut hi) = (0, count);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if entry(mid).0 <= addr {
+            lo = mid + 1;
+        }
+        else {
+            hi = mid;
+        }
+    }
+
+    if lo == 0 {
+        return None;
+    }
+
+    let (start, name_offset, name_len) = entry(lo - 1);
+    let names_start = HEADER_SIZE + count * ENTRY_SIZE;
+    let name_start = names_start + name_offset as usize;
+    let name_end = name_start + name_len as usize;
+    if name_end > SYMTAB_SIZE {
+        return None;
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  The table is only written before the kernel is loaded, so creating a
+    //  shared slice into it is OK. The bounds are checked above.
+    let bytes = unsafe {
+        slice::from_raw_parts(
+            table_ptr().add(name_start), name_end - name_start)
+    };
+
+    Some(Symbol {
+        name: str::from_utf8(bytes).ok()?,
+        addr: start,
+        offset: addr - start
+    })
+}
+
+/// Returns `true` if a symbol table has been embedded into the kernel.
+pub fn is_embedded() -> bool {
+    entry_count().is_some()
+}
+
+/// Print a symbolised backtrace of the caller over serial port 1.
+/// 
+/// See `write_backtrace`.
+#[inline(never)]
+pub fn print_backtrace() {
+    // Nothing useful can be done if printing fails
+    let _ = write_backtrace(&mut EmergencySerial);
+}
+
+/// Write a symbolised backtrace of the caller to `writer`, one frame per 
+/// line.
+/// 
+/// Frames are found by following the saved frame pointers, which the target 
+/// never eliminates. The walk stops at the first frame pointer outside the 
+/// current stack, so a corrupt stack ends the backtrace early instead of 
+/// faulting. No locks are taken or allocations made, so this can be used 
+/// from the panic handler as long as `writer` doesn't either.
+#[inline(never)]
+pub fn write_backtrace(writer: &mut impl fmt::Write) -> fmt::Result {
+    let mut frame: u64;
+
+    // NOTE: USE OF UNSAFE
+    //  Reading RBP has no side effects.
+    unsafe { llvm_asm!("mov %rbp, $0" : "=r"(frame) ::: "volatile") };
+
+    let stack = match crate::stack::bounds(frame) {
+        Some(stack) => stack,
+        None => return writeln!(writer, 
+            "Backtrace: frame {:#x} not on a known stack", frame)
+    };
+
+    if is_embedded() {
+        writeln!(writer, "Backtrace:")?;
+    }
+    else {
+        writeln!(writer, "Backtrace (no symbol table embedded):")?;
+    }
+    for _ in 0..MAX_BACKTRACE_FRAMES {
+        if frame % 8 != 0 || frame < stack.bottom || frame + 16 > stack.top {
+            break;
+        }
+
+        // NOTE: USE OF UNSAFE
+        //  The frame is checked to be inside the mapped part of the stack 
+        //  above. Each frame holds the caller's frame pointer followed by the
+        //  return address.
+        let (next, ret) = unsafe {
+            let frame = frame as *const u64;
+            (ptr::read_volatile(frame), ptr::read_volatile(frame.add(1)))
+        };
+        if ret == 0 {
+            break;
+        }
+        writeln!(writer, "  {}", SymbolisedAddr(ret))?;
+
+        // Callers' frames are always higher up the stack
+        if next <= frame {
+            break;
+        }
+        frame = next;
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get a pointer to the start of the symbol table.
+fn table_ptr() -> *const u8 {
+    // NOTE: USE OF UNSAFE
+    //  Taking the address of a mutable static is unsafe, but the table is
+    //  never written at runtime.
+    unsafe { KERNEL_SYMTAB.as_ptr() }
+}
+
+/// Read a little endian value of `len` bytes from the table at the given
+/// offset.
+fn read_le(offset: usize, len: usize) -> u64 {
+    let mut value = 0u64;
+    for i in 0..len {
+        // NOTE: USE OF UNSAFE
+        //  Reads are volatile so that the compiler can't assume the table is
+        //  still all zeroes. Callers keep offsets inside the table.
+        let byte = unsafe { ptr::read_volatile(table_ptr().add(offset + i)) };
+        value |= (byte as u64) << (8 * i);
+    }
+    value
+}
+
+/// Get the number of entries in the table, or `None` if the table is empty.
+fn entry_count() -> Option<usize> {
+    for (i, &b) in SYMTAB_MAGIC.iter().enumerate() {
+        if read_le(i, 1) as u8 != b {
+            return None;
+        }
+    }
+
+    let count = read_le(4, 4) as usize;
+    if HEADER_SIZE + count * ENTRY_SIZE > SYMTAB_SIZE {
+        return None;
+    }
+
+    Some(count)
+}
+
+/// Read the entry at the given index as `(addr, name_offset, name_len)`.
+fn entry(index: usize) -> (u64, u32, u32) {
+    let offset = HEADER_SIZE + index * ENTRY_SIZE;
+    (
+        read_le(offset, 8),
+        read_le(offset + 8, 4) as u32,
+        read_le(offset + 12, 4) as u32
+    )
+}
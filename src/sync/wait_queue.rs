@@ -0,0 +1,260 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::{future::Future, pin::Pin, task::{Context, Poll}};
+use core::sync::atomic::{AtomicBool, Ordering};
+use futures_util::task::AtomicWaker;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS
+// ---------------------------------------------------------------------------
+
+/// The maximum number of tasks which can wait on a queue at once.
+pub const MAX_WAITERS: usize = 8;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A queue of tasks waiting for something to happen, such as data arriving 
+/// from a device.
+/// 
+/// Waking never blocks or allocates, so it can be done from interrupt 
+/// handlers. Async tasks wait with `wait` or `wait_until`, or hold a `Waiter`
+/// when implementing a `Stream` or `Future` by hand. Code outside of tasks 
+/// can wait with `wait_blocking`, which halts until an interrupt wakes it.
+/// 
+/// The queue has `MAX_WAITERS` slots. Tasks waiting when every slot is in 
+/// use are rescheduled immediately instead, so they still make progress but 
+/// poll repeatedly.
+pub struct WaitQueue {
+    slots: [Slot; MAX_WAITERS]
+}
+
+/// A slot in a wait queue, holding one waiting task.
+struct Slot {
+    claimed: AtomicBool,
+    woken: AtomicBool,
+    waker: AtomicWaker
+}
+
+/// A task's place in a wait queue, which is released when dropped.
+pub struct Waiter<'a> {
+    queue: &'a WaitQueue,
+    slot: Option<usize>
+}
+
+/// Future returned by `WaitQueue::wait`.
+pub struct Wait<'a> {
+    waiter: Waiter<'a>,
+    polled: bool
+}
+
+/// Future returned by `WaitQueue::wait_until`.
+pub struct WaitUntil<'a, F> {
+    waiter: Waiter<'a>,
+    condition: F
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        claimed: AtomicBool::new(false),
+        woken: AtomicBool::new(false),
+        waker: AtomicWaker::new()
+    };
+}
+
+impl WaitQueue {
+
+    /// Create an empty wait queue.
+    pub const fn new() -> WaitQueue {
+        WaitQueue {
+            slots: [Slot::EMPTY; MAX_WAITERS]
+        }
+    }
+
+    /// Get a waiter for this queue.
+    /// 
+    /// The waiter doesn't take a slot until it is first registered.
+    pub fn waiter(&self) -> Waiter {
+        Waiter { queue: self, slot: None }
+    }
+
+    /// Wait until the queue is next woken.
+    /// 
+    /// If every slot is in use this completes on the second poll instead, 
+    /// like a spurious wake up.
+    pub fn wait(&self) -> Wait {
+        Wait { waiter: self.waiter(), polled: false }
+    }
+
+    /// Wait until `condition` returns `true`, checking it each time the queue
+    /// is woken.
+    pub fn wait_until<F: FnMut() -> bool>(&self, condition: F) 
+        -> WaitUntil<F> {
+
+        WaitUntil { waiter: self.waiter(), condition }
+    }
+
+    /// Halt the CPU until `condition` returns `true`, checking it after each 
+    /// interrupt.
+    /// 
+    /// This is for code which isn't running in a task. The condition is 
+    /// checked with interrupts disabled, so a wake can't be missed between 
+    /// the check and the halt.
+    pub fn wait_blocking(&self, mut condition: impl FnMut() -> bool) {
+        use x86_64::instructions::interrupts;
+
+        loop {
+            interrupts::disable();
+            if condition() {
+                interrupts::enable();
+                return;
+            }
+            interrupts::enable_and_hlt();
+        }
+    }
+
+    /// Wake one waiting task, returning `false` if no task was waiting.
+    pub fn wake_one(&self) -> bool {
+        for slot in self.slots.iter() {
+            if slot.claimed.load(Ordering::Acquire) 
+                && !slot.woken.swap(true, Ordering::AcqRel) {
+                slot.waker.wake();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Wake every waiting task.
+    pub fn wake_all(&self) {
+        for slot in self.slots.iter() {
+            if slot.claimed.load(Ordering::Acquire) {
+                slot.woken.store(true, Ordering::Release);
+                slot.waker.wake();
+            }
+        }
+    }
+}
+
+impl<'a> Waiter<'a> {
+
+    /// Register the current task to be woken by the queue, taking a slot if 
+    /// the waiter doesn't have one yet.
+    /// 
+    /// As with `AtomicWaker`, the condition being waited for should be 
+    /// checked again after registering, since a wake may have happened just 
+    /// before.
+    pub fn register(&mut self, cx: &mut Context) {
+        if self.slot.is_none() {
+            self.slot = self.queue.slots.iter().position(|slot| 
+                !slot.claimed.compare_and_swap(false, true, Ordering::AcqRel));
+        }
+
+        match self.slot {
+            Some(slot) => self.queue.slots[slot].waker.register(cx.waker()),
+            None => cx.waker().wake_by_ref()
+        }
+    }
+
+    /// Returns `true`, and clears the flag, if the queue has woken this 
+    /// waiter since the flag was last cleared.
+    pub fn take_woken(&mut self) -> bool {
+        match self.slot {
+            Some(slot) => 
+                self.queue.slots[slot].woken.swap(false, Ordering::AcqRel),
+            None => false
+        }
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            let slot = &self.queue.slots[slot];
+            slot.waker.take();
+            slot.woken.store(false, Ordering::Release);
+            slot.claimed.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl Future for Wait<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        // The first poll only registers, so this waits for the next wake
+        if self.waiter.take_woken() 
+            || (self.polled && self.waiter.slot.is_none()) {
+            return Poll::Ready(());
+        }
+
+        self.polled = true;
+        self.waiter.register(cx);
+        Poll::Pending
+    }
+}
+
+impl<F: FnMut() -> bool + Unpin> Future for WaitUntil<'_, F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if (self.condition)() {
+            return Poll::Ready(());
+        }
+
+        self.waiter.register(cx);
+
+        if (self.condition)() {
+            Poll::Ready(())
+        }
+        else {
+            Poll::Pending
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_wake_one_and_all() {
+    use futures_util::task::noop_waker_ref;
+    use crate::{serial_print, serial_println};
+    serial_print!("sync::wait_queue::wake_one_and_all ");
+
+    let queue = WaitQueue::new();
+    let mut cx = Context::from_waker(noop_waker_ref());
+    assert!(!queue.wake_one());
+
+    let mut a = queue.wait();
+    let mut b = queue.wait();
+    assert_eq!(Pin::new(&mut a).poll(&mut cx), Poll::Pending);
+    assert_eq!(Pin::new(&mut b).poll(&mut cx), Poll::Pending);
+
+    // Only one waiter is woken at a time
+    assert!(queue.wake_one());
+    assert_eq!(Pin::new(&mut a).poll(&mut cx), Poll::Ready(()));
+    assert_eq!(Pin::new(&mut b).poll(&mut cx), Poll::Pending);
+
+    queue.wake_all();
+    assert_eq!(Pin::new(&mut b).poll(&mut cx), Poll::Ready(()));
+
+    // Dropping waiters frees their slots
+    drop(a);
+    drop(b);
+    assert!(queue.slots.iter().all(|s| !s.claimed.load(Ordering::Relaxed)));
+
+    let mut ready = false;
+    let mut until = queue.wait_until(|| ready);
+    assert_eq!(Pin::new(&mut until).poll(&mut cx), Poll::Pending);
+    drop(until);
+    ready = true;
+    let mut until = queue.wait_until(|| ready);
+    assert_eq!(Pin::new(&mut until).poll(&mut cx), Poll::Ready(()));
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,159 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::structures::idt::InterruptStackFrame;
+use x86_64::registers::control::{Cr2, Cr3};
+use crate::{allocator, emergency_println, power, task};
+use crate::symbols::SymbolisedAddr;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// Scancode set 1 make code for the left Alt key.
+const SCANCODE_ALT: u8 = 0x38;
+
+/// Scancode set 1 make code sent for Print Screen while Alt is held.
+const SCANCODE_SYSRQ: u8 = 0x54;
+
+/// Bit set in scancode set 1 break codes.
+const BREAK_BIT: u8 = 0x80;
+
+/// Set while Alt is held.
+static ALT_HELD: AtomicBool = AtomicBool::new(false);
+
+/// Set while SysRq is held.
+static SYSRQ_HELD: AtomicBool = AtomicBool::new(false);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// An emergency debug action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Dump the interrupted registers.
+    Registers,
+
+    /// Print the task counts.
+    Tasks,
+
+    /// Print the heap statistics.
+    Memory,
+
+    /// Write back any cached filesystem data.
+    Sync,
+
+    /// Reboot the machine.
+    Reboot,
+
+    /// Panic, leaving a crash dump.
+    Panic,
+
+    /// List the actions.
+    Help
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Filter a scancode in the keyboard interrupt, before it reaches the async 
+/// queue.
+/// 
+/// While Alt+SysRq is held the next key press runs an action:
+/// 
+/// - `R` dumps the registers
+/// - `T` prints the task counts
+/// - `M` prints heap statistics
+/// - `S` syncs filesystem caches
+/// - `B` reboots
+/// - `C` panics
+/// - `H` lists the actions
+/// 
+/// Returns `true` if the scancode was used and shouldn't be queued.
+pub(crate) fn filter_scancode(
+    scancode: u8, 
+    stack_frame: &InterruptStackFrame
+) -> bool {
+    let released = scancode & BREAK_BIT != 0;
+    match scancode & !BREAK_BIT {
+        SCANCODE_ALT => {
+            // Alt is still passed on so the keyboard task tracks it
+            ALT_HELD.store(!released, Ordering::Relaxed);
+            false
+        },
+        SCANCODE_SYSRQ => {
+            SYSRQ_HELD.store(!released, Ordering::Relaxed);
+            true
+        },
+        code if SYSRQ_HELD.load(Ordering::Relaxed) 
+            && ALT_HELD.load(Ordering::Relaxed) => {
+            // Only act on the press, but swallow the release too
+            if !released {
+                if let Some(action) = action_for_scancode(code) {
+                    run(action, stack_frame);
+                }
+            }
+            true
+        },
+        _ => false
+    }
+}
+
+/// Run an emergency debug action.
+/// 
+/// Actions run from interrupt handlers so only print without taking locks, 
+/// and work even if every task is stuck.
+pub fn run(action: Action, stack_frame: &InterruptStackFrame) {
+    match action {
+        Action::Registers => {
+            emergency_println!("\n[SYSRQ] Register dump");
+            emergency_println!("RIP: {}", 
+                SymbolisedAddr(stack_frame.instruction_pointer.as_u64()));
+            emergency_println!("CS: {:#x}  RFLAGS: {:#x}",
+                stack_frame.code_segment, stack_frame.cpu_flags);
+            emergency_println!("RSP: {:#x}  SS: {:#x}",
+                stack_frame.stack_pointer.as_u64(), stack_frame.stack_segment);
+            emergency_println!("CR2: {:#x}  CR3: {:#x}", 
+                Cr2::read().as_u64(), Cr3::read().0.start_address().as_u64());
+        },
+        Action::Tasks => emergency_println!("\n[SYSRQ] {}\n{}", 
+            task::counts(), task::executor::stats()),
+        Action::Memory => match allocator::try_heap_info() {
+            Some(info) => emergency_println!("\n[SYSRQ] {}", info),
+            None => emergency_println!("\n[SYSRQ] Heap is locked")
+        },
+        Action::Sync => {
+            // Block devices are currently all written through
+            emergency_println!("\n[SYSRQ] No cached filesystem data to sync");
+        },
+        Action::Reboot => {
+            emergency_println!("\n[SYSRQ] Rebooting");
+            power::reboot();
+        },
+        Action::Panic => panic!("[SYSRQ] Panic requested"),
+        Action::Help => emergency_println!("\n[SYSRQ] R registers, T tasks, \
+            M memory, S sync, B reboot, C panic")
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Map a scancode set 1 make code to its action.
+fn action_for_scancode(code: u8) -> Option<Action> {
+    match code {
+        0x13 => Some(Action::Registers),
+        0x14 => Some(Action::Tasks),
+        0x32 => Some(Action::Memory),
+        0x1f => Some(Action::Sync),
+        0x30 => Some(Action::Reboot),
+        0x2e => Some(Action::Panic),
+        0x23 => Some(Action::Help),
+        _ => None
+    }
+}
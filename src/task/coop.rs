@@ -0,0 +1,52 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use super::yield_now;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A cooperative scheduling budget.
+/// 
+/// Long running tasks call `Budget::spend` after each unit of work, which 
+/// yields to the executor once every `units` calls. This keeps tasks such as
+/// filesystem scans from blocking keyboard handling, without yielding so 
+/// often that the switching dominates the work.
+pub struct Budget {
+    units: usize,
+    remaining: usize
+}
+
+impl Budget {
+
+    /// Spend one unit of work, yielding to the executor if the budget is 
+    /// exhausted.
+    pub async fn spend(&mut self) {
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.remaining = self.units;
+            yield_now().await;
+        }
+    }
+
+    /// Yield to the executor now and start a fresh budget.
+    pub async fn reschedule(&mut self) {
+        self.remaining = self.units;
+        yield_now().await;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Create a budget which yields after every `units` units of work.
+pub fn budget(units: usize) -> Budget {
+    let units = units.max(1);
+    Budget {
+        units,
+        remaining: units
+    }
+}
@@ -2,20 +2,48 @@
 // USE STATEMENTS
 // ---------------------------------------------------------------------------
 
-use super::{Task, TaskId};
+use super::{Task, TaskId, Priority};
+use crate::{power, kdebug_assert};
+use crate::interrupts::histogram::{Histogram, Snapshot};
+use crate::log::clock;
 use alloc::{collections::{BTreeMap, VecDeque}, sync::Arc, task::Wake};
+use core::arch::x86_64::_rdtsc;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
 use core::task::{Waker, Context, Poll};
 use crossbeam_queue::ArrayQueue;
 
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The maximum number of tasks polled in each iteration of the run loop, 
+/// before checking for newly woken tasks.
+const POLL_BUDGET: usize = 16;
+
+/// The ready queue length, sampled once per iteration of the run loop.
+static QUEUE_DEPTH: Histogram = Histogram::new();
+
+/// The ready queue length at the last sample.
+static CURRENT_DEPTH: AtomicU64 = AtomicU64::new(0);
+
+/// The TSC cycles between a task being woken and it being polled.
+static WAKE_LATENCY: Histogram = Histogram::new();
+
 // ---------------------------------------------------------------------------
 // DATA STRUCTURES
 // ---------------------------------------------------------------------------
 
 /// An executor implementing a simple queue algorithm with waker support.
+/// 
+/// Tasks are polled round-robin: each iteration of the run loop polls at most
+/// `POLL_BUDGET` tasks from the front of the queue, and woken tasks join the 
+/// back, so a task which is always ready can't starve the others. High 
+/// priority tasks join the front instead.
 pub struct Executor {
     task_queue: VecDeque<Task>,
     waiting_tasks: BTreeMap<TaskId, Task>,
-    wake_queue: Arc<ArrayQueue<TaskId>>,
+    wake_queue: Arc<ArrayQueue<(TaskId, u64)>>,
     waker_cache: BTreeMap<TaskId, Waker>
 }
 
@@ -33,26 +61,47 @@ impl Executor {
 
     /// Spawn a new task in the executor.
     pub fn spawn(&mut self, task: Task) {
-        self.task_queue.push_back(task)
+        self.enqueue(task)
     }
 
     /// Run the executor
     pub fn run(&mut self) -> ! {
         loop {
             self.wake_tasks();
+            self.sample_depth();
             self.run_ready_tasks();
+            self.check_invariants();
             self.sleep_if_idle();
         }
     }
 
-    /// If the wake queue is empty sleep the CPU by calling halt.
+    /// Check the task queues are consistent, in debug builds.
+    fn check_invariants(&self) {
+        let queued = self.task_queue.len() + self.waiting_tasks.len();
+        let counts = super::counts();
+        kdebug_assert!(queued as u64 <= counts.created - counts.finished, 
+            "executor holds {} tasks but there are {}", queued, counts);
+        kdebug_assert!(self.waker_cache.len() <= queued, 
+            "executor caches {} wakers for {} tasks", 
+            self.waker_cache.len(), queued);
+    }
+
+    /// Record the ready queue length.
+    fn sample_depth(&self) {
+        let depth = self.task_queue.len() as u64;
+        CURRENT_DEPTH.store(depth, Ordering::Relaxed);
+        QUEUE_DEPTH.record(depth);
+    }
+
+    /// If there are no ready or woken tasks sleep the CPU by calling halt.
     fn sleep_if_idle(&self) {
-        if !self.wake_queue.is_empty() {
+        if !self.task_queue.is_empty() || !self.wake_queue.is_empty() {
             return;
         }
 
         x86_64::instructions::interrupts::disable();
         if self.wake_queue.is_empty() {
+            power::cpufreq::record_idle_halt();
             x86_64::instructions::interrupts::enable_interrupts_and_hlt();
         }
         else {
@@ -60,13 +109,25 @@ impl Executor {
         }
     }
 
-    /// Run all ready-to-execute tasks
+    /// Run up to `POLL_BUDGET` ready-to-execute tasks.
+    /// 
+    /// Any tasks left over stay at the front of the queue, so they are run 
+    /// before tasks woken in the meantime.
     fn run_ready_tasks(&mut self) {
 
-        // While there are tasks to process in the queue
-        while let Some(mut task) = self.task_queue.pop_front() {
+        // While there are tasks to process in the queue and budget remaining
+        for _ in 0..POLL_BUDGET {
+            let mut task = match self.task_queue.pop_front() {
+                Some(task) => task,
+                None => break
+            };
             let task_id = task.id;
 
+            if task.woken_at != 0 {
+                WAKE_LATENCY.record(rdtsc().saturating_sub(task.woken_at));
+                task.woken_at = 0;
+            }
+
             // Check if the task id is already in the waker cache
             if !self.waker_cache.contains_key(&task_id) {
                 // Insert a new waker for this task into the cache
@@ -85,6 +146,7 @@ impl Executor {
                 Poll::Ready(()) => {
                     // Task is complete, remove the waker from the cache
                     self.waker_cache.remove(&task_id);
+                    super::record_finished();
                 },
                 Poll::Pending => {
                     // Add the task to the waiting tasks list
@@ -108,12 +170,21 @@ impl Executor {
     /// Handle task wakeups
     fn wake_tasks(&mut self) {
         // While there are tasks to be woken from the wake queue
-        while let Ok(task_id) = self.wake_queue.pop() {
-            if let Some(task) = self.waiting_tasks.remove(&task_id) {
-                self.task_queue.push_back(task);
+        while let Ok((task_id, woken_at)) = self.wake_queue.pop() {
+            if let Some(mut task) = self.waiting_tasks.remove(&task_id) {
+                task.woken_at = woken_at;
+                self.enqueue(task);
             }
         }
     }
+
+    /// Add a ready task to the queue according to its priority.
+    fn enqueue(&mut self, task: Task) {
+        match task.priority {
+            Priority::Normal => self.task_queue.push_back(task),
+            Priority::High => self.task_queue.push_front(task)
+        }
+    }
 }
 
 /// A waker for a particular task
@@ -122,13 +193,13 @@ struct TaskWaker {
     task_id: TaskId,
 
     /// A sharted reference to the `Executor`'s wake queue
-    wake_queue: Arc<ArrayQueue<TaskId>>
+    wake_queue: Arc<ArrayQueue<(TaskId, u64)>>
 }
 
 impl TaskWaker {
     /// Flag this task for waking
     fn wake_task(&self) {
-        self.wake_queue.push(self.task_id)
+        self.wake_queue.push((self.task_id, rdtsc()))
             .expect("[EXEC-ERROR] Cannot wake task as the wake queue is full.");
     }
 }
@@ -141,4 +212,88 @@ impl Wake for TaskWaker {
     fn wake_by_ref(self: &Arc<Self>) {
         self.wake_task();
     }
-}
\ No newline at end of file
+}
+
+/// Executor queue depth and wake latency statistics.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutorStats {
+    /// The ready queue length at the last iteration of the run loop.
+    pub current_depth: u64,
+
+    /// The ready queue length sampled at each iteration of the run loop.
+    pub queue_depth: Snapshot,
+
+    /// The TSC cycles between tasks being woken and being polled.
+    pub wake_latency: Snapshot
+}
+
+impl fmt::Display for ExecutorStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Run queue: {} ready, mean {}, p99 {}, peak {}", 
+            self.current_depth, self.queue_depth.mean(), 
+            self.queue_depth.percentile(99), self.queue_depth.max)?;
+
+        let unit = if clock::tsc_hz().is_some() { "ns" } else { "cycles" };
+        let convert = |cycles| clock::cycles_to_ns(cycles).unwrap_or(cycles);
+        write!(f, "Wake latency ({}): {} wakes, mean {}, p50 {}, p99 {}, \
+            max {}", unit, self.wake_latency.count, 
+            convert(self.wake_latency.mean()), 
+            convert(self.wake_latency.percentile(50)), 
+            convert(self.wake_latency.percentile(99)), 
+            convert(self.wake_latency.max))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Get the executor statistics.
+/// 
+/// This takes no locks so can be used from interrupt handlers.
+pub fn stats() -> ExecutorStats {
+    ExecutorStats {
+        current_depth: CURRENT_DEPTH.load(Ordering::Relaxed),
+        queue_depth: QUEUE_DEPTH.snapshot(),
+        wake_latency: WAKE_LATENCY.snapshot()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Read the TSC.
+fn rdtsc() -> u64 {
+    // NOTE: USE OF UNSAFE
+    //  RDTSC is always available on x86_64.
+    unsafe { _rdtsc() }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_wake_latency() {
+    use crate::{serial_print, serial_println};
+    serial_print!("task::executor::wake_latency ");
+
+    let before = stats().wake_latency.count;
+
+    // The task wakes itself when it yields, so is polled again after the 
+    // wake is processed
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(super::yield_now()));
+    executor.run_ready_tasks();
+    executor.wake_tasks();
+    executor.sample_depth();
+    assert_eq!(stats().current_depth, 1);
+    executor.run_ready_tasks();
+
+    assert_eq!(stats().wake_latency.count, before + 1);
+    assert!(executor.task_queue.is_empty());
+    assert!(executor.waiting_tasks.is_empty());
+
+    serial_println!("[ok]");
+}
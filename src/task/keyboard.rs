@@ -4,18 +4,35 @@
 // ---------------------------------------------------------------------------
 
 use crate::{print, println};
+use crate::clipboard::{self, Selection, SelectionState};
+use crate::task::coop;
+use crate::{event, log, log_error, trace};
+use crate::ioport::PortRange;
+use crate::sync::{WaitQueue, Waiter};
 use conquer_once::spin::OnceCell;
 use crossbeam_queue::ArrayQueue;
 use core::{pin::Pin, task::{Poll, Context}};
-use futures_util::{stream::{Stream, StreamExt}, task::AtomicWaker};
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use futures_util::stream::{Stream, StreamExt};
+use pc_keyboard::{
+    layouts, DecodedKey, HandleControl, Keyboard, KeyCode, ScancodeSet1
+};
 
 // ---------------------------------------------------------------------------
-// STATICS
+// CONSTANTS AND STATICS
 // ---------------------------------------------------------------------------
 
+/// The number of scancodes handled before yielding to other tasks, so a 
+/// flooded queue can't starve them.
+const SCANCODE_BUDGET: usize = 32;
+
+/// The PS/2 controller data and command ports.
+const PS2_DATA_PORT: u16 = 0x60;
+const PS2_COMMAND_PORT: u16 = 0x64;
+
 static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
-static WAKER: AtomicWaker = AtomicWaker::new();
+static PS2_DATA: OnceCell<PortRange> = OnceCell::uninit();
+static PS2_COMMAND: OnceCell<PortRange> = OnceCell::uninit();
+static SCANCODES_READY: WaitQueue = WaitQueue::new();
 
 // ---------------------------------------------------------------------------
 // DATA STRUCTURES
@@ -23,7 +40,7 @@ static WAKER: AtomicWaker = AtomicWaker::new();
 
 /// A stream object connected to the keyboard incoming scancodes
 pub struct ScancodeStream {
-    _private: ()
+    waiter: Waiter<'static>
 }
 
 impl ScancodeStream {
@@ -35,7 +52,7 @@ impl ScancodeStream {
         SCANCODE_QUEUE.try_init_once(|| ArrayQueue::new(100))
             .expect("ScancodeStream::new must only be called once");
         ScancodeStream { 
-            _private: ()
+            waiter: SCANCODES_READY.waiter()
         }
     }
 }
@@ -44,7 +61,9 @@ impl Stream for ScancodeStream {
     type Item = u8;
 
     /// Get the next item in the stream
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) 
+        -> Poll<Option<u8>> {
+
         // Get the queue
         let queue = SCANCODE_QUEUE.try_get()
             .expect("[KBD-ERROR] Scancode queue not initialised");
@@ -57,14 +76,11 @@ impl Stream for ScancodeStream {
 
         // If no scancode then register the waker so the executor can awaken
         // the keyboard when a key is pressed
-        WAKER.register(&cx.waker());
+        self.waiter.register(cx);
 
         // If there's a scancode in the queue return it, otherwise pending.
         match queue.pop() {
-            Ok(scancode) => {
-                WAKER.take();
-                Poll::Ready(Some(scancode))
-            },
+            Ok(scancode) => Poll::Ready(Some(scancode)),
             Err(crossbeam_queue::PopError) => Poll::Pending
         }
         
@@ -75,42 +91,115 @@ impl Stream for ScancodeStream {
 // PUBLIC FUNCTIONS
 // ---------------------------------------------------------------------------
 
+/// Claim the PS/2 controller ports.
+pub fn init_ports() {
+    let claims = PortRange::claim(PS2_DATA_PORT, 1, "ps2 data")
+        .and_then(|data| Ok((data, 
+            PortRange::claim(PS2_COMMAND_PORT, 1, "ps2 command")?)));
+
+    match claims {
+        Ok((data, command)) => {
+            PS2_DATA.try_init_once(|| data)
+                .expect("keyboard::init_ports must only be called once");
+            PS2_COMMAND.try_init_once(|| command)
+                .expect("keyboard::init_ports must only be called once");
+        },
+        Err(e) => println!("[KBD-ERROR] Cannot claim PS/2 ports: {}", e)
+    }
+}
+
+/// Read a scancode from the PS/2 controller.
+/// 
+/// Should be called from the keyboard interrupt handler.
+pub(crate) fn read_scancode() -> Option<u8> {
+    PS2_DATA.try_get().ok().map(|data| data.read_u8(0))
+}
+
+/// Send a command byte to the PS/2 controller, returning `false` if the 
+/// ports haven't been claimed.
+pub(crate) fn send_controller_command(command: u8) -> bool {
+    match PS2_COMMAND.try_get() {
+        Ok(port) => {
+            port.write_u8(0, command);
+            true
+        },
+        Err(_) => false
+    }
+}
+
 /// Push a new scancode into the queue.
 /// 
 /// Should be called from the keyboard interrupt handler.
 pub(crate) fn push_scancode(scancode: u8) {
     if let Ok(queue) = SCANCODE_QUEUE.try_get() {
         if let Err(_) = queue.push(scancode) {
-            println!("[KBD-ERROR] Scancode push failed, dropping input");
+            log_error!(log::KBD, "Scancode push failed, dropping input");
         }
         else {
             // Awaken the background worker task since a new scancode was 
             // pushed.
-            WAKER.wake();
+            SCANCODES_READY.wake_all();
         }
     }
     else {
-        println!("[KBD-ERROR] Scancode queue not initialised");
+        log_error!(log::KBD, "Scancode queue not initialised");
     }
 }
 
 /// Print the keypresses from the keyboard
+/// 
+/// F1 enters selection mode, in which keys are routed to the `Selection` 
+/// until it is copied or cancelled, and F2 pastes the clipboard.
 pub async fn print_keypresses() {
     let mut scancodes = ScancodeStream::new();
     let mut keyboard = Keyboard::new(
         layouts::Uk105Key,
         ScancodeSet1,
         HandleControl::Ignore);
+    let mut selection: Option<Selection> = None;
+    let mut budget = coop::budget(SCANCODE_BUDGET);
 
     // While there are scancodes available process and print they key
     while let Some(scancode) = scancodes.next().await {
+        budget.spend().await;
+        let _span = trace::span("kbd::scancode");
+
         if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
             if let Some(key) = keyboard.process_keyevent(key_event) {
+                // While selecting all keys go to the selection
+                if let Some(sel) = selection.as_mut() {
+                    if sel.handle_key(key) == SelectionState::Finished {
+                        selection = None;
+                    }
+                    continue;
+                }
+
                 match key {
+                    DecodedKey::RawKey(KeyCode::F1) => 
+                        selection = Some(Selection::begin()),
+                    DecodedKey::RawKey(KeyCode::F2) => clipboard::paste(),
+                    DecodedKey::RawKey(key) if is_hotkey(key) => {
+                        event::HOTKEY.publish(key);
+                    },
                     DecodedKey::Unicode(chr) => print!("{}", chr),
                     DecodedKey::RawKey(key) => print!("{:?}", key)
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Returns `true` if the key is a function key not bound by the keyboard 
+/// task, which is published as a hotkey event instead.
+fn is_hotkey(key: KeyCode) -> bool {
+    match key {
+        KeyCode::F3 | KeyCode::F4 | KeyCode::F5 | KeyCode::F6 | KeyCode::F7
+        | KeyCode::F8 | KeyCode::F9 | KeyCode::F10 | KeyCode::F11 
+        | KeyCode::F12 => true,
+        _ => false
+    }
+}
@@ -0,0 +1,237 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::{any::Any, fmt, marker::PhantomData, ptr};
+use core::sync::atomic::{AtomicPtr, Ordering};
+use alloc::{boxed::Box, collections::BTreeMap};
+use x86_64::instructions::interrupts;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The local storage of the task currently being polled, or null outside of 
+/// a task.
+static CURRENT: AtomicPtr<LocalMap> = AtomicPtr::new(ptr::null_mut());
+
+// ---------------------------------------------------------------------------
+// MACRO DEFINITIONS
+// ---------------------------------------------------------------------------
+
+/// Declare a task local value.
+/// 
+/// Each task gets its own copy of the value, created from the initialiser the
+/// first time the task accesses it and dropped with the task. Values are only
+/// accessible through a shared reference, so use `Cell` or `RefCell` for 
+/// values which change.
+/// 
+/// ```ignore
+/// task_local! {
+///     static CONSOLE: Cell<usize> = Cell::new(0);
+/// }
+/// 
+/// CONSOLE.with(|console| console.set(1));
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr;) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::local::LocalKey<$t> = {
+            fn init() -> $t { $init }
+            $crate::task::local::LocalKey::new(init)
+        };
+    };
+}
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The task local values of a single task, keyed by the address of their 
+/// `LocalKey`.
+#[derive(Default)]
+pub struct LocalMap(BTreeMap<usize, Box<dyn Any>>);
+
+impl LocalMap {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        LocalMap(BTreeMap::new())
+    }
+
+    /// The number of values the task has initialised.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the task hasn't initialised any values.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Makes a task's local storage current until dropped, restoring the 
+/// previously current storage.
+pub(crate) struct EnterGuard<'a> {
+    previous: *mut LocalMap,
+    _map: PhantomData<&'a mut LocalMap>
+}
+
+impl Drop for EnterGuard<'_> {
+    fn drop(&mut self) {
+        CURRENT.store(self.previous, Ordering::SeqCst);
+    }
+}
+
+/// A key for a task local value, declared with `task_local!`.
+pub struct LocalKey<T: 'static> {
+    init: fn() -> T
+}
+
+impl<T: 'static> LocalKey<T> {
+
+    /// Create a new key with the given initialiser.
+    /// 
+    /// Use `task_local!` rather than calling this directly.
+    #[doc(hidden)]
+    pub const fn new(init: fn() -> T) -> Self {
+        LocalKey { init }
+    }
+
+    /// Access the current task's value, initialising it if this is the first
+    /// access.
+    /// 
+    /// Panics if called outside of a task.
+    pub fn with<F, R>(&'static self, f: F) -> R 
+    where
+        F: FnOnce(&T) -> R
+    {
+        self.try_with(f)
+            .expect("[TASK-ERROR] Task local accessed outside of a task")
+    }
+
+    /// Access the current task's value, or return an error if called outside 
+    /// of a task.
+    /// 
+    /// Must not be called from interrupt handlers, which would see the local 
+    /// storage of whichever task they interrupted.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R
+    {
+        let key = self as *const Self as usize;
+
+        let value = match lookup(key)? {
+            Some(value) => value,
+            None => {
+                // The initialiser runs with no borrow of the map so it can
+                // access other task locals
+                let value: Box<dyn Any> = Box::new((self.init)());
+                insert(key, value)?
+            }
+        };
+
+        // NOTE: USE OF UNSAFE
+        //  Values are boxed so don't move when the map changes, and are only
+        //  dropped with the task, which can't happen while it is being polled.
+        let value = unsafe { &*value };
+        Ok(f(value.downcast_ref::<T>()
+            .expect("[TASK-ERROR] Task local has the wrong type")))
+    }
+}
+
+/// Error returned when a task local is accessed outside of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessError;
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "task local accessed outside of a task")
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Make the given local storage current while a task is polled.
+pub(crate) fn enter(map: &mut LocalMap) -> EnterGuard<'_> {
+    EnterGuard {
+        previous: CURRENT.swap(map, Ordering::SeqCst),
+        _map: PhantomData
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Run `f` on the current task's map with interrupts disabled.
+fn with_current<R>(
+    f: impl FnOnce(&mut BTreeMap<usize, Box<dyn Any>>) -> R
+) -> Result<R, AccessError> {
+    interrupts::without_interrupts(|| {
+        let map = CURRENT.load(Ordering::SeqCst);
+        if map.is_null() {
+            return Err(AccessError);
+        }
+
+        // NOTE: USE OF UNSAFE
+        //  The map belongs to the task being polled, which outlives the 
+        //  `EnterGuard`, and the borrow ends before this function returns.
+        Ok(f(unsafe { &mut (*map).0 }))
+    })
+}
+
+/// Find the value for the given key in the current task.
+fn lookup(key: usize) -> Result<Option<*const dyn Any>, AccessError> {
+    with_current(|map| map.get(&key).map(|value| &**value as *const dyn Any))
+}
+
+/// Insert a value for the given key in the current task, keeping the existing
+/// value if there is one.
+fn insert(key: usize, value: Box<dyn Any>) 
+    -> Result<*const dyn Any, AccessError> 
+{
+    with_current(|map| &**map.entry(key).or_insert(value) as *const dyn Any)
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_task_local() {
+    use crate::{serial_print, serial_println};
+    use core::cell::Cell;
+    serial_print!("task::local::task_local ");
+
+    task_local! {
+        static COUNTER: Cell<u32> = Cell::new(7);
+    }
+
+    // Not available outside of a task
+    assert_eq!(COUNTER.try_with(|c| c.get()), Err(AccessError));
+
+    let mut first = LocalMap::new();
+    let mut second = LocalMap::new();
+
+    {
+        let _guard = enter(&mut first);
+        COUNTER.with(|c| c.set(c.get() + 1));
+        COUNTER.with(|c| assert_eq!(c.get(), 8));
+
+        // Nested tasks see their own values, and the outer map is restored
+        {
+            let _guard = enter(&mut second);
+            COUNTER.with(|c| assert_eq!(c.get(), 7));
+        }
+        COUNTER.with(|c| assert_eq!(c.get(), 8));
+    }
+
+    assert_eq!(first.len(), 1);
+    assert_eq!(second.len(), 1);
+    assert_eq!(COUNTER.try_with(|c| c.get()), Err(AccessError));
+
+    serial_println!("[ok]");
+}
@@ -2,17 +2,31 @@
 // MODULES
 // ---------------------------------------------------------------------------
 
+pub mod coop;
 pub mod executor;
 pub mod keyboard;
+pub mod local;
+pub mod stream;
+pub mod workqueue;
 
 // ---------------------------------------------------------------------------
 // USE STATEMENTS
 // ---------------------------------------------------------------------------
 
-use core::{future:: Future, pin::Pin, task::{Poll, Context}};
+use core::{fmt, future:: Future, pin::Pin, task::{Poll, Context}};
 use core::sync::atomic::{AtomicU64, Ordering};
 use alloc::boxed::Box;
 
+// ---------------------------------------------------------------------------
+// STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of tasks created, which is also the next task ID.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The number of tasks which have run to completion.
+static FINISHED: AtomicU64 = AtomicU64::new(0);
+
 // ---------------------------------------------------------------------------
 // DATA STRUCTURES
 // ---------------------------------------------------------------------------
@@ -23,14 +37,31 @@ struct TaskId(u64);
 
 impl TaskId {
     fn new() -> TaskId {
-        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
         TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
     }
 }
 
+/// The scheduling priority of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Woken tasks join the back of the executor's queue.
+    Normal,
+
+    /// Woken tasks join the front of the executor's queue, for work deferred 
+    /// from interrupt handlers.
+    High
+}
+
 /// A task object which contains a future.
 pub struct Task {
     id: TaskId,
+    priority: Priority,
+    locals: local::LocalMap,
+
+    /// The TSC value when the task was last woken, or 0 if it hasn't been 
+    /// polled since it was spawned.
+    woken_at: u64,
+
     future: Pin<Box<dyn Future<Output = ()>>>,
 }
 
@@ -38,14 +69,88 @@ impl Task {
 
     /// Createte a new task from the contained future.
     pub fn new(future: impl Future<Output = ()> + 'static) -> Task {
+        Task::with_priority(future, Priority::Normal)
+    }
+
+    /// Create a new task with the given priority.
+    pub fn with_priority(
+        future: impl Future<Output = ()> + 'static, 
+        priority: Priority
+    ) -> Task {
         Task {
             id: TaskId::new(),
+            priority,
+            locals: local::LocalMap::new(),
+            woken_at: 0,
             future: Box::pin(future)
         }
     }
 
-    /// Poll the contained future using the given context.
+    /// Poll the contained future using the given context, with the task's 
+    /// locals current.
     fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        let _locals = local::enter(&mut self.locals);
         self.future.as_mut().poll(context)
     }
-}
\ No newline at end of file
+}
+
+/// Counts of tasks, readable without access to the executor.
+#[derive(Debug, Clone, Copy)]
+pub struct TaskCounts {
+    pub created: u64,
+    pub finished: u64
+}
+
+impl fmt::Display for TaskCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} live tasks ({} created, {} finished)", 
+            self.created - self.finished, self.created, self.finished)
+    }
+}
+
+/// Future returned by `yield_now`.
+struct YieldNow {
+    yielded: bool
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        }
+        else {
+            // Wake ourselves straight away so the executor requeues us behind
+            // any other ready tasks
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Yield to the executor, letting other ready tasks run before this one 
+/// continues.
+pub fn yield_now() -> impl Future<Output = ()> {
+    YieldNow { yielded: false }
+}
+
+/// Get the current task counts.
+/// 
+/// This takes no locks so can be used from interrupt handlers.
+pub fn counts() -> TaskCounts {
+    TaskCounts {
+        created: NEXT_ID.load(Ordering::Relaxed),
+        finished: FINISHED.load(Ordering::Relaxed)
+    }
+}
+
+/// Record that a task has run to completion.
+fn record_finished() {
+    FINISHED.fetch_add(1, Ordering::Relaxed);
+}
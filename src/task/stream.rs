@@ -0,0 +1,271 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use alloc::vec::Vec;
+use core::{pin::Pin, task::{Context, Poll}};
+use futures_util::stream::Stream;
+use crate::interrupts;
+
+#[cfg(test)]
+use crate::{serial_print, serial_println};
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// Stream returned by `map`.
+pub struct Map<S, F> {
+    stream: S,
+    f: F
+}
+
+impl<S, F, T> Stream for Map<S, F>
+    where S: Stream + Unpin, F: FnMut(S::Item) -> T + Unpin {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) 
+        -> Poll<Option<T>> {
+
+        let this = &mut *self;
+        match Pin::new(&mut this.stream).poll_next(cx) {
+            Poll::Ready(item) => Poll::Ready(item.map(&mut this.f)),
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
+
+/// Stream returned by `merge`.
+pub struct Merge<A, B> {
+    a: Option<A>,
+    b: Option<B>,
+    poll_b_first: bool
+}
+
+impl<A, B> Stream for Merge<A, B> 
+    where A: Stream + Unpin, B: Stream<Item = A::Item> + Unpin {
+    type Item = A::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) 
+        -> Poll<Option<A::Item>> {
+
+        // Alternate which stream is polled first so a busy stream can't 
+        // starve the other
+        let this = &mut *self;
+        this.poll_b_first = !this.poll_b_first;
+
+        if this.poll_b_first {
+            if let Poll::Ready(Some(item)) = poll_side(&mut this.b, cx) {
+                return Poll::Ready(Some(item));
+            }
+            if let Poll::Ready(Some(item)) = poll_side(&mut this.a, cx) {
+                return Poll::Ready(Some(item));
+            }
+        }
+        else {
+            if let Poll::Ready(Some(item)) = poll_side(&mut this.a, cx) {
+                return Poll::Ready(Some(item));
+            }
+            if let Poll::Ready(Some(item)) = poll_side(&mut this.b, cx) {
+                return Poll::Ready(Some(item));
+            }
+        }
+
+        // Only finished once both streams are
+        if this.a.is_none() && this.b.is_none() {
+            Poll::Ready(None)
+        }
+        else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Stream returned by `throttle`.
+pub struct Throttle<S> {
+    stream: S,
+    ticks: u64,
+    last: Option<u64>
+}
+
+impl<S> Stream for Throttle<S> where S: Stream + Unpin {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) 
+        -> Poll<Option<S::Item>> {
+
+        let this = &mut *self;
+
+        // Drain items until one arrives outside the throttle window, since 
+        // dropped items don't register a wake up
+        loop {
+            let item = match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => item,
+                other => return other
+            };
+
+            let now = interrupts::ticks();
+            let within_window = this.last
+                .map(|last| now.wrapping_sub(last) < this.ticks)
+                .unwrap_or(false);
+
+            if !within_window {
+                this.last = Some(now);
+                return Poll::Ready(Some(item));
+            }
+        }
+    }
+}
+
+/// Stream returned by `ready_chunks`.
+pub struct ReadyChunks<S> {
+    stream: Option<S>,
+    max: usize
+}
+
+impl<S> Stream for ReadyChunks<S> where S: Stream + Unpin {
+    type Item = Vec<S::Item>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) 
+        -> Poll<Option<Vec<S::Item>>> {
+
+        let this = &mut *self;
+        let mut chunk = Vec::new();
+
+        while chunk.len() < this.max {
+            match poll_side(&mut this.stream, cx) {
+                Poll::Ready(Some(item)) => chunk.push(item),
+                Poll::Ready(None) | Poll::Pending => break
+            }
+        }
+
+        if !chunk.is_empty() {
+            Poll::Ready(Some(chunk))
+        }
+        else if this.stream.is_none() {
+            Poll::Ready(None)
+        }
+        else {
+            Poll::Pending
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Transform each item of the stream with `f`.
+pub fn map<S, F, T>(stream: S, f: F) -> Map<S, F> 
+    where S: Stream + Unpin, F: FnMut(S::Item) -> T + Unpin {
+
+    Map { stream, f }
+}
+
+/// Merge two streams of the same item type, yielding items from whichever is
+/// ready and alternating between them when both are.
+/// 
+/// The merged stream ends once both streams have ended.
+pub fn merge<A, B>(a: A, b: B) -> Merge<A, B> 
+    where A: Stream + Unpin, B: Stream<Item = A::Item> + Unpin {
+
+    Merge {
+        a: Some(a),
+        b: Some(b),
+        poll_b_first: true
+    }
+}
+
+/// Drop any item which arrives within `ticks` timer ticks of the last item 
+/// yielded, for example to filter key bounce or repeated device events.
+/// 
+/// This yields the first item of a burst straight away (a leading edge 
+/// throttle), unlike a debounce which would wait for the burst to end and 
+/// yield its last item.
+pub fn throttle<S>(stream: S, ticks: u64) -> Throttle<S> 
+    where S: Stream + Unpin {
+
+    Throttle {
+        stream,
+        ticks,
+        last: None
+    }
+}
+
+/// Gather all immediately ready items, up to `max` at a time, into chunks.
+/// 
+/// This lets a consumer handle a burst of items (for example network frames)
+/// in one go, while never waiting to fill a chunk.
+pub fn ready_chunks<S>(stream: S, max: usize) -> ReadyChunks<S> 
+    where S: Stream + Unpin {
+
+    ReadyChunks {
+        stream: Some(stream),
+        max: max.max(1)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Poll a stream which is dropped once it ends, returning `Ready(None)` if it
+/// has already ended.
+fn poll_side<S>(side: &mut Option<S>, cx: &mut Context) 
+    -> Poll<Option<S::Item>> where S: Stream + Unpin {
+
+    let stream = match side.as_mut() {
+        Some(stream) => stream,
+        None => return Poll::Ready(None)
+    };
+
+    let result = Pin::new(stream).poll_next(cx);
+    if let Poll::Ready(None) = result {
+        *side = None;
+    }
+    result
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_merge_and_chunks() {
+    use futures_util::{stream::iter, task::noop_waker_ref};
+    serial_print!("task::stream::merge_and_chunks ");
+
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let merged = merge(iter(alloc::vec![1, 3]), iter(alloc::vec![2, 4]));
+    let mut chunks = ready_chunks(map(merged, |x| x * 10), 3);
+
+    let mut next = || Pin::new(&mut chunks).poll_next(&mut cx);
+    assert_eq!(next(), Poll::Ready(Some(alloc::vec![10, 20, 30])));
+    assert_eq!(next(), Poll::Ready(Some(alloc::vec![40])));
+    assert_eq!(next(), Poll::Ready(None));
+
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn test_throttle() {
+    use futures_util::{stream::iter, task::noop_waker_ref};
+    serial_print!("task::stream::throttle ");
+
+    let mut cx = Context::from_waker(noop_waker_ref());
+
+    // Every item after the first arrives well within the window
+    let mut throttled = throttle(iter(alloc::vec![1, 2, 3]), u64::max_value());
+    let mut next = || Pin::new(&mut throttled).poll_next(&mut cx);
+    assert_eq!(next(), Poll::Ready(Some(1)));
+    assert_eq!(next(), Poll::Ready(None));
+
+    // An empty window lets everything through
+    let mut throttled = throttle(iter(alloc::vec![1, 2]), 0);
+    let mut next = || Pin::new(&mut throttled).poll_next(&mut cx);
+    assert_eq!(next(), Poll::Ready(Some(1)));
+    assert_eq!(next(), Poll::Ready(Some(2)));
+    assert_eq!(next(), Poll::Ready(None));
+
+    serial_println!("[ok]");
+}
@@ -0,0 +1,184 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use conquer_once::spin::OnceCell;
+use crossbeam_queue::ArrayQueue;
+use crate::sync::WaitQueue;
+use super::coop;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of work items each queue can hold before further items are 
+/// dropped.
+const WORK_QUEUE_SIZE: usize = 64;
+
+/// The number of items run before the worker yields to other tasks.
+const WORK_BUDGET: usize = 16;
+
+/// The general purpose work queue, run by `main`.
+pub static SYSTEM: WorkQueue = WorkQueue::new("system");
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A function run as deferred work, with the data it was queued with.
+pub type WorkFn = fn(usize);
+
+/// An item of deferred work.
+#[derive(Clone, Copy)]
+struct Work {
+    func: WorkFn,
+    data: usize
+}
+
+/// Errors which can occur when queueing work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkQueueError {
+    /// The queue hasn't been initialised.
+    Uninitialised,
+
+    /// The queue is full, so the work was dropped.
+    Full
+}
+
+impl fmt::Display for WorkQueueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorkQueueError::Uninitialised => write!(f, "queue not initialised"),
+            WorkQueueError::Full => write!(f, "queue full")
+        }
+    }
+}
+
+/// A queue of work deferred from interrupt handlers, run in order by a 
+/// worker task.
+/// 
+/// Interrupt handlers should do only what must be done with interrupts 
+/// disabled, and queue the rest (such as processing received data) here. 
+/// Queueing never blocks or allocates. Items from one queue run in the order 
+/// they were queued; use separate queues for work which needn't be ordered.
+pub struct WorkQueue {
+    name: &'static str,
+    items: OnceCell<ArrayQueue<Work>>,
+    ready: WaitQueue,
+    dropped: AtomicUsize
+}
+
+impl WorkQueue {
+
+    /// Create a new, uninitialised, work queue.
+    pub const fn new(name: &'static str) -> WorkQueue {
+        WorkQueue {
+            name,
+            items: OnceCell::uninit(),
+            ready: WaitQueue::new(),
+            dropped: AtomicUsize::new(0)
+        }
+    }
+
+    /// Allocate the queue, which must be done once the heap is initialised 
+    /// and before work can be queued.
+    pub fn init(&self) {
+        self.items.try_init_once(|| ArrayQueue::new(WORK_QUEUE_SIZE))
+            .expect("WorkQueue::init must only be called once");
+    }
+
+    /// Queue `func` to be run with `data` by the worker task.
+    /// 
+    /// This can be called from interrupt handlers.
+    pub fn queue(&self, func: WorkFn, data: usize) 
+        -> Result<(), WorkQueueError> {
+
+        let items = self.items.try_get()
+            .map_err(|_| WorkQueueError::Uninitialised)?;
+
+        if items.push(Work { func, data }).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Err(WorkQueueError::Full);
+        }
+
+        self.ready.wake_all();
+        Ok(())
+    }
+
+    /// The name of the queue.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The number of items dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Run queued work until all of it is done, returning the number of items
+    /// run.
+    /// 
+    /// This is for running work outside of the worker task, such as in tests.
+    pub fn run_pending(&self) -> usize {
+        let mut count = 0;
+        if let Ok(items) = self.items.try_get() {
+            while let Ok(work) = items.pop() {
+                (work.func)(work.data);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// The worker task, which runs the queued work.
+    /// 
+    /// This should be spawned with `Priority::High`, so deferred work runs 
+    /// soon after it is queued.
+    pub async fn worker(&'static self) {
+        let items = self.items.try_get()
+            .expect("[WORK-ERROR] Work queue not initialised");
+        let mut budget = coop::budget(WORK_BUDGET);
+
+        loop {
+            self.ready.wait_until(|| !items.is_empty()).await;
+
+            while let Ok(work) = items.pop() {
+                (work.func)(work.data);
+                budget.spend().await;
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_work_runs_in_order() {
+    use crate::{serial_print, serial_println};
+    serial_print!("task::workqueue::work_runs_in_order ");
+
+    static QUEUE: WorkQueue = WorkQueue::new("test");
+    static LAST: AtomicUsize = AtomicUsize::new(0);
+
+    fn record(data: usize) {
+        assert_eq!(LAST.swap(data, Ordering::Relaxed), data - 1);
+    }
+
+    assert_eq!(QUEUE.queue(record, 1), Err(WorkQueueError::Uninitialised));
+    QUEUE.init();
+
+    for i in 1..=WORK_QUEUE_SIZE {
+        QUEUE.queue(record, i).unwrap();
+    }
+    assert_eq!(QUEUE.queue(record, 0), Err(WorkQueueError::Full));
+    assert_eq!(QUEUE.dropped(), 1);
+
+    assert_eq!(QUEUE.run_pending(), WORK_QUEUE_SIZE);
+    assert_eq!(LAST.load(Ordering::Relaxed), WORK_QUEUE_SIZE);
+
+    serial_println!("[ok]");
+}
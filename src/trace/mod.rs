@@ -0,0 +1,162 @@
+// ---------------------------------------------------------------------------
+// MODULES
+// ---------------------------------------------------------------------------
+
+pub mod ring;
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use crate::log::clock;
+use ring::{Event, Phase, Ring};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The recorded events.
+/// 
+/// Every holder of the lock disables interrupts, so it can be taken from 
+/// interrupt handlers.
+static RING: Mutex<Ring> = Mutex::new(Ring::new());
+
+/// Whether events are recorded.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// The ID given to the next span.
+static NEXT_SPAN_ID: AtomicU64 = AtomicU64::new(1);
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// A span which records its end when dropped, created by `span`.
+#[must_use = "the span ends as soon as it is dropped"]
+pub struct Span {
+    name: &'static str,
+    id: u64
+}
+
+impl Span {
+    /// Annotate the span with a value.
+    pub fn annotate(&self, value: u64) {
+        record(Phase::Instant, self.name, self.id, Some(value));
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        record(Phase::End, self.name, self.id, None);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUBLIC FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Start a span, which ends when the returned `Span` is dropped.
+/// 
+/// Names are conventionally `subsystem::what`, for example `kbd::key`.
+pub fn span(name: &'static str) -> Span {
+    let id = NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed);
+    record(Phase::Begin, name, id, None);
+    Span { name, id }
+}
+
+/// Record a point event.
+/// 
+/// This can be used from interrupt handlers.
+pub fn event(name: &'static str) {
+    record(Phase::Instant, name, 0, None);
+}
+
+/// Record a point event annotated with a value.
+pub fn event_value(name: &'static str, value: u64) {
+    record(Phase::Instant, name, 0, Some(value));
+}
+
+/// Enable or disable recording.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Discard all recorded events.
+pub fn clear() {
+    interrupts::without_interrupts(|| RING.lock().clear());
+}
+
+/// Dump the recorded events over serial as chrome tracing JSON, between 
+/// marker lines so it can be cut out of the serial log.
+/// 
+/// This prints without the serial lock so it can be used from the serial 
+/// escape handler. Interrupts are disabled for the whole dump.
+pub fn dump() {
+    interrupts::without_interrupts(|| {
+        let ring = RING.lock();
+        crate::emergency_println!(
+            "\n--- BEGIN TRACE ---\n{}\n--- END TRACE ---", ring.json());
+    });
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Record an event if tracing is enabled.
+fn record(phase: Phase, name: &'static str, id: u64, value: Option<u64>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let event = Event {
+        timestamp_us: clock::now().0,
+        phase,
+        name,
+        id,
+        value
+    };
+    interrupts::without_interrupts(|| RING.lock().push(event));
+}
+
+// ---------------------------------------------------------------------------
+// TEST CASES
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_trace() {
+    use crate::{serial_print, serial_println};
+    serial_print!("trace::trace ");
+
+    clear();
+    {
+        let span = span("test::outer");
+        event("test::point");
+        span.annotate(3);
+    }
+
+    interrupts::without_interrupts(|| {
+        let ring = RING.lock();
+        let phases: alloc::vec::Vec<Phase> = ring.iter()
+            .map(|e| e.phase)
+            .collect();
+        assert_eq!(phases, [Phase::Begin, Phase::Instant, Phase::Instant, 
+            Phase::End]);
+
+        let first = ring.iter().next().unwrap();
+        let last = ring.iter().last().unwrap();
+        assert_eq!(first.id, last.id);
+        assert!(first.timestamp_us <= last.timestamp_us);
+    });
+
+    set_enabled(false);
+    event("test::dropped");
+    set_enabled(true);
+    interrupts::without_interrupts(|| assert_eq!(RING.lock().len(), 4));
+
+    clear();
+    serial_println!("[ok]");
+}
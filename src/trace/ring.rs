@@ -0,0 +1,241 @@
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::fmt;
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// The number of events kept, older events are overwritten.
+pub const CAPACITY: usize = 512;
+
+// ---------------------------------------------------------------------------
+// DATA STRUCTURES
+// ---------------------------------------------------------------------------
+
+/// The kind of a trace event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The start of a span.
+    Begin,
+
+    /// The end of a span.
+    End,
+
+    /// A point event.
+    Instant
+}
+
+/// A single trace event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// The time of the event in microseconds since boot.
+    pub timestamp_us: u64,
+
+    pub phase: Phase,
+    pub name: &'static str,
+
+    /// The span this event belongs to, matching begin and end events. 
+    pub id: u64,
+
+    /// An optional value annotating the event.
+    pub value: Option<u64>
+}
+
+impl Event {
+    const EMPTY: Event = Event {
+        timestamp_us: 0,
+        phase: Phase::Instant,
+        name: "",
+        id: 0,
+        value: None
+    };
+}
+
+/// A fixed size ring of trace events.
+pub struct Ring {
+    events: [Event; CAPACITY],
+
+    /// The index the next event is written to.
+    next: usize,
+
+    /// The number of valid events.
+    len: usize,
+
+    /// The number of events overwritten before being dumped.
+    overwritten: u64
+}
+
+impl Ring {
+
+    /// Create an empty ring.
+    pub const fn new() -> Self {
+        Ring {
+            events: [Event::EMPTY; CAPACITY],
+            next: 0,
+            len: 0,
+            overwritten: 0
+        }
+    }
+
+    /// Add an event, overwriting the oldest if the ring is full.
+    pub fn push(&mut self, event: Event) {
+        self.events[self.next] = event;
+        self.next = (self.next + 1) % CAPACITY;
+        if self.len == CAPACITY {
+            self.overwritten += 1;
+        }
+        else {
+            self.len += 1;
+        }
+    }
+
+    /// The number of events in the ring.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the ring holds no events.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of events lost by being overwritten.
+    pub fn overwritten(&self) -> u64 {
+        self.overwritten
+    }
+
+    /// Remove all events.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.overwritten = 0;
+    }
+
+    /// Iterate over the events from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        let start = (self.next + CAPACITY - self.len) % CAPACITY;
+        (0..self.len).map(move |i| &self.events[(start + i) % CAPACITY])
+    }
+
+    /// Format the events as chrome tracing JSON, which can be loaded into 
+    /// `chrome://tracing` or Perfetto.
+    /// 
+    /// Spans are written as async events, so spans from tasks which 
+    /// interleave on the executor don't have to nest.
+    pub fn json(&self) -> Json<'_> {
+        Json(self)
+    }
+}
+
+impl Default for Ring {
+    fn default() -> Self {
+        Ring::new()
+    }
+}
+
+/// Chrome tracing JSON formatter returned by `Ring::json`.
+pub struct Json<'a>(&'a Ring);
+
+impl fmt::Display for Json<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{\"traceEvents\":[")?;
+        for (i, event) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write_event(f, event)?;
+        }
+        write!(f, "],\"otherData\":{{\"overwritten\":{}}}}}", 
+            self.0.overwritten)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PRIVATE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Write a single event as a chrome tracing JSON object.
+fn write_event(f: &mut fmt::Formatter, event: &Event) -> fmt::Result {
+    write!(f, "{{\"name\":\"")?;
+    write_escaped(f, event.name)?;
+    write!(f, "\",\"cat\":\"scos\",\"pid\":0,\"tid\":0,\"ts\":{}", 
+        event.timestamp_us)?;
+
+    match event.phase {
+        Phase::Begin => write!(f, ",\"ph\":\"b\",\"id\":{}", event.id)?,
+        Phase::End => write!(f, ",\"ph\":\"e\",\"id\":{}", event.id)?,
+        Phase::Instant => write!(f, ",\"ph\":\"i\",\"s\":\"g\"")?
+    }
+
+    if let Some(value) = event.value {
+        write!(f, ",\"args\":{{\"value\":{}}}", value)?;
+    }
+    write!(f, "}}")
+}
+
+/// Write a string with JSON escaping.
+fn write_escaped(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    for chr in s.chars() {
+        match chr {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            chr if (chr as u32) < 0x20 => write!(f, "\\u{:04x}", chr as u32)?,
+            chr => write!(f, "{}", chr)?
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// HOST TESTS
+// ---------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "host-test"))]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    fn event(timestamp_us: u64, phase: Phase, name: &'static str) -> Event {
+        Event { timestamp_us, phase, name, id: 1, value: None }
+    }
+
+    #[test]
+    fn overwrites_oldest() {
+        let mut ring = Ring::new();
+        for i in 0..CAPACITY as u64 + 3 {
+            ring.push(event(i, Phase::Instant, "tick"));
+        }
+
+        assert_eq!(ring.len(), CAPACITY);
+        assert_eq!(ring.overwritten(), 3);
+        assert_eq!(ring.iter().next().unwrap().timestamp_us, 3);
+        assert_eq!(ring.iter().last().unwrap().timestamp_us, 
+            CAPACITY as u64 + 2);
+
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(ring.iter().count(), 0);
+    }
+
+    #[test]
+    fn chrome_json() {
+        let mut ring = Ring::new();
+        ring.push(event(10, Phase::Begin, "kbd"));
+        ring.push(Event { 
+            value: Some(42), 
+            ..event(12, Phase::Instant, "a\"b") 
+        });
+        ring.push(event(15, Phase::End, "kbd"));
+
+        assert_eq!(format!("{}", ring.json()), 
+            "{\"traceEvents\":[\
+            {\"name\":\"kbd\",\"cat\":\"scos\",\"pid\":0,\"tid\":0,\"ts\":10,\
+            \"ph\":\"b\",\"id\":1},\
+            {\"name\":\"a\\\"b\",\"cat\":\"scos\",\"pid\":0,\"tid\":0,\
+            \"ts\":12,\"ph\":\"i\",\"s\":\"g\",\"args\":{\"value\":42}},\
+            {\"name\":\"kbd\",\"cat\":\"scos\",\"pid\":0,\"tid\":0,\"ts\":15,\
+            \"ph\":\"e\",\"id\":1}],\"otherData\":{\"overwritten\":0}}");
+    }
+}
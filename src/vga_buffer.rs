@@ -3,10 +3,13 @@
 // ---------------------------------------------------------------------------
 
 use volatile::Volatile;
-use core::fmt;
+use core::{fmt, str};
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use core::fmt::Write;
+use conquer_once::spin::OnceCell;
+use crate::ioport::PortRange;
 
 // Serial print imports for testing purposes
 #[cfg(test)]
@@ -61,6 +64,34 @@ impl DisplayCode {
         DisplayCode(
             (1u8 << 7) | (background as u8) << 4 | (foreground as u8))
     }
+
+    /// Get the inverse of this `DisplayCode`, swapping the foreground and 
+    /// background colours.
+    /// 
+    /// The background only has 3 bits of colour, so the bright bit of the 
+    /// foreground and the blink bit are left in place. This means inverting 
+    /// twice always gives back the original code.
+    fn inverted(self) -> DisplayCode {
+        let blink = self.0 & 0x80;
+        let bright = self.0 & 0x08;
+        let foreground = self.0 & 0x07;
+        let background = (self.0 >> 4) & 0x07;
+
+        DisplayCode(blink | foreground << 4 | bright | background)
+    }
+
+    /// Get the ANSI foreground and background SGR parameters giving the same
+    /// colours as this `DisplayCode`. Blinking isn't translated.
+    fn ansi(self) -> (u8, u8) {
+        // VGA colours are ordered BGR where ANSI colours are ordered RGB
+        const VGA_TO_ANSI: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+        let foreground = VGA_TO_ANSI[(self.0 & 0x07) as usize];
+        let background = VGA_TO_ANSI[((self.0 >> 4) & 0x07) as usize];
+        let foreground_base = if self.0 & 0x08 != 0 { 90 } else { 30 };
+
+        (foreground_base + foreground, 40 + background)
+    }
 }
 
 /// A single character to be displayed, including both the character and its
@@ -82,6 +113,36 @@ pub const BUFFER_HEIGHT: usize = 25;
 /// The width of the VGA buffer.
 pub const BUFFER_WIDTH: usize = 80;
 
+/// The physical (and identity mapped) address of the VGA text buffer.
+const VGA_BUFFER_ADDR: usize = 0xb8000;
+
+/// The CRT controller index and data ports, used to move the hardware cursor.
+const CRTC_PORT_BASE: u16 = 0x3D4;
+const CRTC_INDEX_OFFSET: u16 = 0;
+const CRTC_DATA_OFFSET: u16 = 1;
+
+/// Whether everything written to the screen is also sent to serial port 1, 
+/// enabled by the `serial-mirror` feature.
+pub const SERIAL_MIRROR: bool = cfg!(feature = "serial-mirror");
+
+/// The size of the buffer exception handlers format messages into.
+const EXCEPTION_BUFFER_SIZE: usize = 1024;
+
+/// Buffer for `exception_println!`, allocated up front so that exception 
+/// handlers never allocate.
+static mut EXCEPTION_BUFFER: [u8; EXCEPTION_BUFFER_SIZE] = 
+    [0; EXCEPTION_BUFFER_SIZE];
+
+/// Set while `EXCEPTION_BUFFER` is in use.
+static EXCEPTION_BUFFER_BUSY: AtomicBool = AtomicBool::new(false);
+
+/// The claimed CRT controller ports.
+static CRTC_PORTS: OnceCell<PortRange> = OnceCell::uninit();
+
+/// The CRT controller registers holding the cursor location.
+const CRTC_CURSOR_LOC_HIGH: u8 = 0x0E;
+const CRTC_CURSOR_LOC_LOW: u8 = 0x0F;
+
 /// Buffer object which encapsulates the VGA in-memory buffer.
 /// 
 /// `repr(transparent)` is used to ensure the buffer has the same size as its
@@ -95,7 +156,10 @@ struct VgaBuffer {
 pub struct Writer {
     col_pos: usize,
     display_code: DisplayCode,
-    buffer: &'static mut VgaBuffer
+    buffer: &'static mut VgaBuffer,
+
+    /// The colours last sent to the serial mirror.
+    mirror_code: Option<DisplayCode>
 }
 
 impl Writer {
@@ -104,9 +168,11 @@ impl Writer {
     pub fn write_byte(&mut self, byte: u8) {
 
         // If the byte to write is a new line we must handle that as a newline
-        // print, otherwise write the byte.
+        // print, a carriage return moves back to the start of the row so it 
+        // can be redrawn, otherwise write the byte.
         match byte {
             b'\n' => self.new_line(),
+            b'\r' => self.col_pos = 0,
             byte => {
                 // If at the right-hand edge of the screen add a new line 
                 // before writing.
@@ -131,16 +197,53 @@ impl Writer {
 
     /// Write a string on the bottom line of the terminal.
     pub fn write_string(&mut self, string: &str) {
+        if SERIAL_MIRROR {
+            self.mirror(string);
+        }
+        self.write_screen(string);
+    }
+
+    /// Write a string to the screen only, without mirroring it to serial.
+    fn write_screen(&mut self, string: &str) {
         for byte in string.bytes() {
             // Since rust strings are UTF-8 we need to select only the 
             // printable VGA characters. Any other character gets a placeholder.
             match byte {
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
+                0x20..=0x7e | b'\n' | b'\r' => self.write_byte(byte),
                 _ => self.write_byte(0xfe)
             }
         }
     }
 
+    /// Get the (row, column) position the next character will be written at.
+    pub fn position(&self) -> (usize, usize) {
+        (BUFFER_HEIGHT - 1, self.col_pos.min(BUFFER_WIDTH - 1))
+    }
+
+    /// Read the ASCII character at the given position in the buffer.
+    pub fn read_char(&self, row: usize, col: usize) -> u8 {
+        self.buffer.chars[row][col].read().ascii_char
+    }
+
+    /// Invert the colours of the character at the given position in the 
+    /// buffer.
+    pub fn invert_cell(&mut self, row: usize, col: usize) {
+        let mut chr = self.buffer.chars[row][col].read();
+        chr.display_code = chr.display_code.inverted();
+        self.buffer.chars[row][col].write(chr);
+    }
+
+    /// Send a string to serial port 1, preceded by an ANSI colour sequence if 
+    /// the colours have changed since the last string.
+    fn mirror(&mut self, string: &str) {
+        if self.mirror_code != Some(self.display_code) {
+            let (foreground, background) = self.display_code.ansi();
+            crate::serial_print!("\x1b[{};{}m", foreground, background);
+            self.mirror_code = Some(self.display_code);
+        }
+        crate::serial::send_bytes(string.as_bytes());
+    }
+
     /// Handle a newline by moving the buffer upwards 1 row
     fn new_line(&mut self) {
         for row in 1..BUFFER_HEIGHT {
@@ -181,6 +284,34 @@ impl fmt::Write for Writer {
     }
 }
 
+/// Writer which fills a fixed buffer, dropping anything that doesn't fit.
+struct BufferWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize
+}
+
+impl BufferWriter<'_> {
+    /// The text written so far.
+    fn as_str(&self) -> &str {
+        // Only whole characters are copied in so this can't fail
+        str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl fmt::Write for BufferWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Truncate on a character boundary so the buffer stays valid UTF-8
+        let mut count = s.len().min(self.buf.len() - self.len);
+        while !s.is_char_boundary(count) {
+            count -= 1;
+        }
+        self.buf[self.len..self.len + count]
+            .copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
 // Use lazy_static here to get around const limitations.
 lazy_static! {
 
@@ -192,7 +323,8 @@ lazy_static! {
     pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
         col_pos: 0,
         display_code: DisplayCode::new(Colour::White, Colour::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut VgaBuffer) }
+        buffer: unsafe { &mut *(VGA_BUFFER_ADDR as *mut VgaBuffer) },
+        mirror_code: None
     });
 }
 
@@ -211,6 +343,19 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
+/// Allocation and lock free equivalent of println!, for exception handlers.
+/// 
+/// An exception can be raised while the `WRITER` or serial locks are held, or
+/// from inside the allocator, so the message is formatted into a 
+/// pre-allocated buffer and written without waiting on either lock. Messages 
+/// longer than the buffer are truncated.
+#[macro_export]
+macro_rules! exception_println {
+    () => ($crate::vga_buffer::_exception_print(format_args!("\n")));
+    ($($arg:tt)*) => ($crate::vga_buffer::_exception_print(
+        format_args!("{}\n", format_args!($($arg)*))));
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
 
@@ -221,6 +366,51 @@ pub fn _print(args: fmt::Arguments) {
     );
 }
 
+#[doc(hidden)]
+pub fn _exception_print(args: fmt::Arguments) {
+    // A nested exception while the buffer is in use can only go to serial
+    if EXCEPTION_BUFFER_BUSY.swap(true, Ordering::Acquire) {
+        crate::serial::_emergency_print(args);
+        return;
+    }
+
+    // NOTE: USE OF UNSAFE
+    //  The busy flag gives this call sole use of the buffer.
+    let mut writer = BufferWriter { 
+        buf: unsafe { &mut EXCEPTION_BUFFER }, 
+        len: 0 
+    };
+
+    // Errors can't happen as the writer truncates instead
+    let _ = writer.write_fmt(args);
+    let text = writer.as_str();
+
+    crate::serial::_emergency_print(format_args!("{}", text));
+
+    // Write to the screen through the `WRITER` if it is free, otherwise 
+    // through a second writer starting on a new line. Neither mirrors to 
+    // serial, which would take the serial lock.
+    match WRITER.try_lock() {
+        Some(mut writer) => writer.write_screen(text),
+        None => {
+            // NOTE: USE OF UNSAFE
+            //  A second reference to the VGA buffer can only tear the output
+            //  of the print which holds the lock, and the exception handler 
+            //  is not returning to that print intact anyway.
+            let mut writer = Writer {
+                col_pos: 0,
+                display_code: DisplayCode::new(Colour::White, Colour::Black),
+                buffer: unsafe { &mut *(VGA_BUFFER_ADDR as *mut VgaBuffer) },
+                mirror_code: None
+            };
+            writer.write_screen("\n");
+            writer.write_screen(text);
+        }
+    }
+
+    EXCEPTION_BUFFER_BUSY.store(false, Ordering::Release);
+}
+
 // ---------------------------------------------------------------------------
 // PUBLIC FUNCTION DEFINITIONS
 // ---------------------------------------------------------------------------
@@ -235,12 +425,87 @@ pub fn divider(chr: u8) {
 /// 
 /// Use `vga_buffer::reset_colour()` to return to the original colors.
 pub fn set_colour(foreground: Colour, background: Colour) {
-    WRITER.lock().display_code = DisplayCode::new(foreground, background);
+    // Interrupts are disabled like `_print`, so a print from an interrupt 
+    // can't deadlock on the lock
+    x86_64::instructions::interrupts::without_interrupts(|| 
+        WRITER.lock().display_code = DisplayCode::new(foreground, background)
+    );
 }
 
 /// Reset the VGA buffer colours to white on black.
 pub fn reset_colour() {
-    WRITER.lock().display_code = DisplayCode::new(Colour::White, Colour::Black);
+    set_colour(Colour::White, Colour::Black);
+}
+
+/// Get the (row, column) position the next character will be written at.
+pub fn position() -> (usize, usize) {
+    x86_64::instructions::interrupts::without_interrupts(|| 
+        WRITER.lock().position()
+    )
+}
+
+/// Read the ASCII character at the given position on the screen.
+pub fn read_char(row: usize, col: usize) -> u8 {
+    x86_64::instructions::interrupts::without_interrupts(|| 
+        WRITER.lock().read_char(row, col)
+    )
+}
+
+/// Invert the colours of the character at the given position on the screen.
+/// 
+/// Inverting the same cell twice returns it to its original colours.
+pub fn invert_cell(row: usize, col: usize) {
+    x86_64::instructions::interrupts::without_interrupts(|| 
+        WRITER.lock().invert_cell(row, col)
+    );
+}
+
+/// Copy the last `lines.len()` rows of the screen into `lines`, oldest first.
+/// 
+/// This reads the VGA memory directly without taking the `WRITER` lock, so it
+/// is safe to call from crash paths which may have interrupted a print. The 
+/// rows may be torn if a print is in progress.
+pub fn recent_lines(lines: &mut [[u8; BUFFER_WIDTH]]) {
+    // NOTE: USE OF UNSAFE
+    //  Creating a second reference to the VGA buffer is normally unsafe, but 
+    //  this one is only read from with volatile reads.
+    let buffer = unsafe { &*(VGA_BUFFER_ADDR as *const VgaBuffer) };
+
+    let count = lines.len().min(BUFFER_HEIGHT);
+    let first_row = BUFFER_HEIGHT - count;
+    for (i, line) in lines.iter_mut().take(count).enumerate() {
+        for col in 0..BUFFER_WIDTH {
+            line[col] = buffer.chars[first_row + i][col].read().ascii_char;
+        }
+    }
+}
+
+/// Claim the CRT controller ports.
+pub fn init_ports() {
+    match PortRange::claim(CRTC_PORT_BASE, 2, "vga crtc") {
+        Ok(ports) => {
+            CRTC_PORTS.try_init_once(|| ports)
+                .expect("vga_buffer::init_ports must only be called once");
+        },
+        Err(e) => crate::serial_println!(
+            "[VGA-ERROR] Cannot claim CRTC ports: {}", e)
+    }
+}
+
+/// Move the blinking hardware cursor to the given position on the screen.
+/// 
+/// Does nothing if the CRT controller ports haven't been claimed.
+pub fn set_cursor(row: usize, col: usize) {
+    let ports = match CRTC_PORTS.try_get() {
+        Ok(ports) => ports,
+        Err(_) => return
+    };
+
+    let pos = (row * BUFFER_WIDTH + col) as u16;
+    ports.write_u8(CRTC_INDEX_OFFSET, CRTC_CURSOR_LOC_LOW);
+    ports.write_u8(CRTC_DATA_OFFSET, (pos & 0xff) as u8);
+    ports.write_u8(CRTC_INDEX_OFFSET, CRTC_CURSOR_LOC_HIGH);
+    ports.write_u8(CRTC_DATA_OFFSET, (pos >> 8) as u8);
 }
 
 // ---------------------------------------------------------------------------
@@ -293,4 +558,72 @@ pub fn test_println_output() {
     });
 
     serial_println!("[ok]");
-}
\ No newline at end of file
+}
+
+/// Test that exception prints reach the screen even while the writer is 
+/// locked.
+#[test_case]
+pub fn test_exception_println() {
+    serial_print!("vga_buffer::exception_println ");
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let _lock = WRITER.lock();
+        crate::exception_println!("Exception {}", 42);
+
+        let mut lines = [[0; BUFFER_WIDTH]; 1];
+        recent_lines(&mut lines);
+        assert!(lines[0].iter().all(|&b| b == b' '));
+
+        let mut lines = [[0; BUFFER_WIDTH]; 2];
+        recent_lines(&mut lines);
+        assert_eq!(&lines[0][..12], b"Exception 42");
+    });
+
+    // Long messages are truncated to the buffer
+    let mut buf = [0u8; 7];
+    let mut writer = BufferWriter { buf: &mut buf, len: 0 };
+    write!(writer, "abcdef\u{e9}gh").unwrap();
+    assert_eq!(writer.as_str(), "abcdef");
+
+    serial_println!("[ok]");
+}
+
+/// Test that inverting a cell twice restores its original colours.
+#[test_case]
+pub fn test_invert_cell() {
+    serial_print!("vga_buffer::invert_cell ");
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writeln!(writer, "\nX").expect("Writeln failed!");
+
+        let row = BUFFER_HEIGHT - 2;
+        let original = writer.buffer.chars[row][0].read();
+
+        writer.invert_cell(row, 0);
+        let inverted = writer.buffer.chars[row][0].read();
+        assert_eq!(inverted.ascii_char, original.ascii_char);
+        assert_ne!(inverted.display_code, original.display_code);
+
+        writer.invert_cell(row, 0);
+        assert_eq!(writer.buffer.chars[row][0].read(), original);
+    });
+
+    serial_println!("[ok]");
+}
+
+/// Test the translation of VGA colours into ANSI colours.
+#[test_case]
+pub fn test_ansi_colours() {
+    serial_print!("vga_buffer::ansi_colours ");
+
+    assert_eq!(DisplayCode::new(Colour::White, Colour::Black).ansi(), 
+        (97, 40));
+    assert_eq!(DisplayCode::new(Colour::LightGray, Colour::Blue).ansi(), 
+        (37, 44));
+    assert_eq!(DisplayCode::new(Colour::Red, Colour::Cyan).ansi(), (31, 46));
+    assert_eq!(DisplayCode::new(Colour::Yellow, Colour::Brown).ansi(), 
+        (93, 43));
+
+    serial_println!("[ok]");
+}
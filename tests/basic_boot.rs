@@ -18,6 +18,7 @@ use scos::{println, serial_print, serial_println};
 /// Main entry point for this test
 #[no_mangle] 
 pub extern "C" fn _start() -> ! {
+    scos::init_exit_port();
     test_main();
 
     loop {}
@@ -0,0 +1,234 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![feature(abi_x86_interrupt)]
+#![feature(llvm_asm)]
+#![test_runner(scos::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicU64, Ordering};
+use lazy_static::lazy_static;
+use x86_64::registers::control::Cr2;
+use x86_64::structures::idt::{
+    InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode
+};
+use scos::{serial_print, serial_println};
+
+// ---------------------------------------------------------------------------
+// CONSTANTS AND STATICS
+// ---------------------------------------------------------------------------
+
+/// A canonical address which the bootloader doesn't map.
+const UNMAPPED_ADDR: u64 = 0x0000_dead_beef_0000;
+
+/// A selector beyond the end of the GDT.
+const BAD_SELECTOR: u64 = 0x1230;
+
+/// The vector of the last exception handled, or `NO_EXCEPTION`.
+static LAST_VECTOR: AtomicU64 = AtomicU64::new(NO_EXCEPTION);
+
+/// The error code pushed by the last exception handled.
+static LAST_ERROR_CODE: AtomicU64 = AtomicU64::new(0);
+
+/// The length of the faulting instruction, which handlers skip over to 
+/// resume the test.
+static SKIP_BYTES: AtomicU64 = AtomicU64::new(0);
+
+const NO_EXCEPTION: u64 = u64::MAX;
+const DIVIDE_ERROR: u64 = 0;
+const INVALID_OPCODE: u64 = 6;
+const GENERAL_PROTECTION: u64 = 13;
+const PAGE_FAULT: u64 = 14;
+
+lazy_static! {
+    /// An IDT whose handlers record the exception and resume after the 
+    /// faulting instruction, instead of halting as the kernel's do.
+    static ref TEST_IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+
+        idt.divide_error.set_handler_fn(divide_error_handler);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+        idt.general_protection_fault
+            .set_handler_fn(general_protection_fault_handler);
+        idt.page_fault.set_handler_fn(page_fault_handler);
+
+        // NOTE: USE OF UNSAFE
+        //  The double fault index is reserved for double faults by the GDT 
+        //  module.
+        unsafe {
+            idt.double_fault.set_handler_fn(double_fault_handler)
+                .set_stack_index(scos::gdt::DOUBLE_FAULT_IST_INDEX);
+        }
+
+        idt
+    };
+}
+
+// ---------------------------------------------------------------------------
+// CORE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+/// Main entry point for this test
+#[no_mangle] 
+pub extern "C" fn _start() -> ! {
+    scos::gdt::init();
+    TEST_IDT.load();
+    scos::init_exit_port();
+
+    test_main();
+
+    loop {}
+}
+
+/// Panic handler
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    scos::test_panic_handler(info)
+}
+
+/// Run `trigger`, which must raise an exception from an instruction 
+/// `length` bytes long, and return the vector and error code it raised.
+fn expect_exception(length: u64, trigger: impl FnOnce()) -> (u64, u64) {
+    LAST_VECTOR.store(NO_EXCEPTION, Ordering::SeqCst);
+    LAST_ERROR_CODE.store(0, Ordering::SeqCst);
+    SKIP_BYTES.store(length, Ordering::SeqCst);
+
+    trigger();
+
+    let vector = LAST_VECTOR.load(Ordering::SeqCst);
+    assert_ne!(vector, NO_EXCEPTION, "no exception was raised");
+    (vector, LAST_ERROR_CODE.load(Ordering::SeqCst))
+}
+
+// ---------------------------------------------------------------------------
+// HANDLERS
+// ---------------------------------------------------------------------------
+
+/// Record an exception and skip the instruction which raised it.
+fn record(stack_frame: &mut InterruptStackFrame, vector: u64, code: u64) {
+    LAST_VECTOR.store(vector, Ordering::SeqCst);
+    LAST_ERROR_CODE.store(code, Ordering::SeqCst);
+
+    // NOTE: USE OF UNSAFE
+    //  The tests set the length of the faulting instruction, so execution 
+    //  resumes at the next one.
+    unsafe {
+        stack_frame.as_mut().instruction_pointer += 
+            SKIP_BYTES.load(Ordering::SeqCst);
+    }
+}
+
+extern "x86-interrupt" fn divide_error_handler(
+    stack_frame: &mut InterruptStackFrame
+) {
+    record(stack_frame, DIVIDE_ERROR, 0);
+}
+
+extern "x86-interrupt" fn invalid_opcode_handler(
+    stack_frame: &mut InterruptStackFrame
+) {
+    record(stack_frame, INVALID_OPCODE, 0);
+}
+
+extern "x86-interrupt" fn general_protection_fault_handler(
+    stack_frame: &mut InterruptStackFrame,
+    error_code: u64
+) {
+    record(stack_frame, GENERAL_PROTECTION, error_code);
+}
+
+extern "x86-interrupt" fn page_fault_handler(
+    stack_frame: &mut InterruptStackFrame,
+    error_code: PageFaultErrorCode
+) {
+    assert_eq!(Cr2::read().as_u64(), UNMAPPED_ADDR);
+    record(stack_frame, PAGE_FAULT, error_code.bits());
+}
+
+/// A double fault means one of the handlers above faulted, which fails the 
+/// test.
+extern "x86-interrupt" fn double_fault_handler(
+    _stack_frame: &mut InterruptStackFrame,
+    _error_code: u64
+) -> ! {
+    panic!("double fault while handling a test exception");
+}
+
+// ---------------------------------------------------------------------------
+// TESTS
+// ---------------------------------------------------------------------------
+
+#[test_case]
+fn test_divide_error() {
+    serial_print!("exceptions::divide_error ");
+
+    // `div rcx` is 3 bytes long
+    let (vector, _) = expect_exception(3, || unsafe {
+        llvm_asm!("div rcx" 
+            :: "{rax}"(1u64), "{rdx}"(0u64), "{rcx}"(0u64) 
+            : "rax", "rdx" 
+            : "intel", "volatile");
+    });
+    assert_eq!(vector, DIVIDE_ERROR);
+
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn test_invalid_opcode() {
+    serial_print!("exceptions::invalid_opcode ");
+
+    // `ud2` is 2 bytes long
+    let (vector, _) = expect_exception(2, || unsafe {
+        llvm_asm!("ud2" :::: "intel", "volatile");
+    });
+    assert_eq!(vector, INVALID_OPCODE);
+
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn test_bad_segment_load() {
+    serial_print!("exceptions::bad_segment_load ");
+
+    // `mov ds, eax` is 2 bytes long. The error code is the bad selector.
+    let (vector, code) = expect_exception(2, || unsafe {
+        llvm_asm!("mov ds, eax" 
+            :: "{eax}"(BAD_SELECTOR as u32) 
+            :: "intel", "volatile");
+    });
+    assert_eq!(vector, GENERAL_PROTECTION);
+    assert_eq!(code, BAD_SELECTOR);
+
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn test_page_fault() {
+    serial_print!("exceptions::page_fault ");
+
+    // `mov rax, [rcx]` is 3 bytes long
+    let (vector, code) = expect_exception(3, || unsafe {
+        llvm_asm!("mov rax, [rcx]" 
+            :: "{rcx}"(UNMAPPED_ADDR) 
+            : "rax" 
+            : "intel", "volatile");
+    });
+    assert_eq!(vector, PAGE_FAULT);
+
+    // A read of a non-present page from ring 0
+    let code = PageFaultErrorCode::from_bits_truncate(code);
+    assert!(!code.contains(PageFaultErrorCode::PROTECTION_VIOLATION));
+    assert!(!code.contains(PageFaultErrorCode::CAUSED_BY_WRITE));
+    assert!(!code.contains(PageFaultErrorCode::USER_MODE));
+
+    serial_println!("[ok]");
+}
+
+// Alignment check exceptions are only raised at CPL 3, and the kernel has no
+// way to run code in ring 3 yet, so they can't be tested here.
@@ -0,0 +1,175 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(scos::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+// ---------------------------------------------------------------------------
+// USE STATEMENTS
+// ---------------------------------------------------------------------------
+
+extern crate alloc;
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use scos::{serial_print, serial_println};
+use scos::fs::{BlockDevice, BlockDeviceError, testing::RamDisk};
+use scos::fs::cow::CowDevice;
+use scos::fs::queue::{RequestQueue, RequestFuture, RequestResult};
+use alloc::vec;
+use core::{future::Future, pin::Pin, task::{Context, Poll}};
+use futures_util::task::noop_waker_ref;
+
+// ---------------------------------------------------------------------------
+// CORE FUNCTIONS
+// ---------------------------------------------------------------------------
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    scos::init(boot_info);
+
+    test_main();
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    scos::test_panic_handler(info)
+}
+
+// ---------------------------------------------------------------------------
+// TESTS
+// ---------------------------------------------------------------------------
+
+/// A small image with a recognisable pattern in each block.
+const IMAGE: &[u8] = b"block zero......block one.......block two";
+
+#[test_case]
+fn ramdisk_from_image() {
+    serial_print!("fs::ramdisk_from_image ");
+    let disk = RamDisk::from_image(16, IMAGE);
+
+    // The partial final block is padded
+    assert_eq!(disk.block_count(), 3);
+    assert_eq!(disk.size(), 48);
+
+    let mut buf = [0u8; 16];
+    disk.read_block(1, &mut buf).unwrap();
+    assert_eq!(&buf, b"block one.......");
+    disk.read_block(2, &mut buf).unwrap();
+    assert_eq!(&buf[..9], b"block two");
+    assert!(buf[9..].iter().all(|&b| b == 0));
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn ramdisk_write_read() {
+    serial_print!("fs::ramdisk_write_read ");
+    let mut disk = RamDisk::new(512, 4);
+    let block = [0xa5u8; 512];
+    disk.write_block(3, &block).unwrap();
+
+    let mut buf = [0u8; 512];
+    disk.read_block(3, &mut buf).unwrap();
+    assert_eq!(&buf[..], &block[..]);
+    disk.read_block(2, &mut buf).unwrap();
+    assert!(buf.iter().all(|&b| b == 0));
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn ramdisk_errors() {
+    serial_print!("fs::ramdisk_errors ");
+    let mut disk = RamDisk::new(16, 2);
+    let mut buf = [0u8; 16];
+
+    assert_eq!(disk.read_block(2, &mut buf), Err(BlockDeviceError::OutOfRange));
+    assert_eq!(disk.read_block(0, &mut buf[..8]), 
+        Err(BlockDeviceError::BadBufferSize));
+
+    disk.set_read_only(true);
+    assert_eq!(disk.write_block(0, &buf), Err(BlockDeviceError::ReadOnly));
+    serial_println!("[ok]");
+}
+
+/// Poll a request once, returning its result if it has completed.
+fn poll_request(request: &mut RequestFuture) -> Option<RequestResult> {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    match Pin::new(request).poll(&mut cx) {
+        Poll::Ready(result) => Some(result),
+        Poll::Pending => None
+    }
+}
+
+#[test_case]
+fn queue_read_write() {
+    serial_print!("fs::queue_read_write ");
+    let queue = RequestQueue::new("test", RamDisk::from_image(16, IMAGE));
+
+    // Nothing completes until the queue is dispatched
+    let mut write = queue.write(0, vec![b'x'; 16]);
+    let mut read = queue.read(0, 2);
+    assert!(poll_request(&mut write).is_none());
+    assert_eq!(queue.dispatch(), 2);
+
+    // The read was submitted after the overlapping write so sees its data
+    assert_eq!(poll_request(&mut write), Some(Ok(vec![])));
+    let data = poll_request(&mut read).unwrap().unwrap();
+    assert_eq!(&data[..16], &[b'x'; 16]);
+    assert_eq!(&data[16..], b"block one.......");
+
+    // Errors are returned through the future
+    let mut bad = queue.write(0, vec![0; 8]);
+    assert_eq!(poll_request(&mut bad), 
+        Some(Err(BlockDeviceError::BadBufferSize)));
+    let mut past_end = queue.read(2, 2);
+    queue.dispatch();
+    assert_eq!(poll_request(&mut past_end), 
+        Some(Err(BlockDeviceError::OutOfRange)));
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn queue_merges_requests() {
+    serial_print!("fs::queue_merges_requests ");
+    let queue = RequestQueue::new("test", RamDisk::new(16, 8));
+
+    // Submitted out of order, the reads are sorted into one contiguous run
+    let mut requests = [queue.read(4, 2), queue.read(0, 2), queue.read(2, 2)];
+    assert_eq!(queue.dispatch(), 3);
+    for request in requests.iter_mut() {
+        assert!(poll_request(request).unwrap().is_ok());
+    }
+
+    let stats = queue.stats();
+    assert_eq!(stats.completed, 3);
+    assert_eq!(stats.merged, 2);
+    serial_println!("[ok]");
+}
+
+#[test_case]
+fn cow_overlay() {
+    serial_print!("fs::cow_overlay ");
+    let mut base = RamDisk::from_image(16, IMAGE);
+    base.set_read_only(true);
+    let mut disk = CowDevice::new(base);
+    let mut buf = [0u8; 16];
+
+    // Writes are visible through the overlay but don't reach the base
+    disk.write_block(1, &[b'y'; 16]).unwrap();
+    disk.read_block(1, &mut buf).unwrap();
+    assert_eq!(buf, [b'y'; 16]);
+    disk.read_block(0, &mut buf).unwrap();
+    assert_eq!(&buf, b"block zero......");
+    assert_eq!(disk.dirty_blocks(), 1);
+    assert_eq!(&disk.base().as_bytes()[16..32], b"block one.......");
+
+    // Resetting returns to the base image
+    disk.reset();
+    disk.read_block(1, &mut buf).unwrap();
+    assert_eq!(&buf, b"block one.......");
+
+    assert_eq!(disk.write_block(3, &buf), Err(BlockDeviceError::OutOfRange));
+    serial_println!("[ok]");
+}
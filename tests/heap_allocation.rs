@@ -61,4 +61,70 @@ fn many_boxes() {
         assert_eq!(*x, i);
     }
     serial_println!("[ok]");
-}
\ No newline at end of file
+}
+
+/// Fill most of the heap with small blocks, free them, and then make an 
+/// allocation larger than the free space outside the block lists. This only 
+/// succeeds if the free blocks are returned to the fallback heap.
+#[test_case]
+fn large_after_many_small() {
+    serial_print!("heap_allocation::large_after_many_small ");
+    let count = scos::allocator::HEAP_SIZE / 2 / 16;
+    let mut boxes = Vec::with_capacity(count);
+    for i in 0..count {
+        boxes.push(Box::new([i as u8; 16]));
+    }
+    drop(boxes);
+
+    let large = alloc::vec![0xa5u8; scos::allocator::HEAP_SIZE / 2];
+    assert!(large.iter().all(|&b| b == 0xa5));
+    serial_println!("[ok]");
+}
+
+/// Check that large allocations can be made and freed repeatedly without 
+/// leaking space from the large allocation reserve.
+#[test_case]
+fn repeated_large() {
+    serial_print!("heap_allocation::repeated_large ");
+    for i in 0..100 {
+        let large = alloc::vec![i as u8; scos::allocator::HEAP_SIZE / 8];
+        assert_eq!(large[large.len() - 1], i as u8);
+    }
+    serial_println!("[ok]");
+}
+
+/// Check that zeroed allocations are zeroed even when reusing a block which
+/// previously held data.
+#[test_case]
+fn zeroed_reuses_dirty_block() {
+    serial_print!("heap_allocation::zeroed_reuses_dirty_block ");
+    drop(Box::new([0xffu8; 32]));
+    let zeroed = alloc::vec![0u8; 32];
+    assert!(zeroed.iter().all(|&b| b == 0));
+    serial_println!("[ok]");
+}
+
+/// Check that registered shrinkers are called when memory pressure is 
+/// relieved.
+#[test_case]
+fn pressure_calls_shrinkers() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use scos::memory::pressure::{self, Pressure};
+
+    serial_print!("heap_allocation::pressure_calls_shrinkers ");
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    pressure::register("test", |_| {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        0
+    }).unwrap();
+    pressure::relieve(Pressure::Low);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+
+    // Once unregistered it isn't called again, and the slot is free for 
+    // other tests
+    assert!(pressure::unregister("test"));
+    pressure::relieve(Pressure::Low);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    assert!(!pressure::unregister("test"));
+    serial_println!("[ok]");
+}
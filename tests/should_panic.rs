@@ -24,6 +24,7 @@ fn panic(_info: &PanicInfo) -> ! {
 
 #[no_mangle]
 pub extern "C" fn _start() -> ! {
+    scos::init_exit_port();
     test_main();
 
     loop {}
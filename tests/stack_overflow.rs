@@ -23,6 +23,7 @@ pub extern "C" fn _start() {
     // Initiailise necessary items
     scos::gdt::init();
     init_test_idt();
+    scos::init_exit_port();
 
     // Trigger stack overflow
     stack_overflow();
@@ -0,0 +1,105 @@
+#!/usr/bin/env python3
+"""Embed a sorted kernel symbol table into the `.ksymtab` section.
+
+The kernel reserves a fixed size `.ksymtab` section (see `src/symbols/mod.rs`).
+Since symbol addresses are only known after linking this is run as a
+post-link step, overwriting the section in place so no addresses move.
+`tools/runner.py`, the cargo runner, does this before every `cargo run` and
+`cargo xtest`, but it can also be run by hand:
+
+    cargo build
+    tools/embed_symbols.py target/x86_64-scos/debug/scos
+    bootimage runner target/x86_64-scos/debug/scos
+
+Requires `nm` and `objcopy` from binutils.
+"""
+
+import os
+import struct
+import subprocess
+import sys
+import tempfile
+
+SECTION = ".ksymtab"
+SYMTAB_SIZE = 64 * 1024
+MAGIC = b"KSYM"
+HEADER_SIZE = 8
+ENTRY_SIZE = 16
+
+
+def read_symbols(kernel):
+    """Get the sorted (address, name) list of function symbols."""
+    out = subprocess.run(
+        ["nm", "--defined-only", kernel],
+        check=True, stdout=subprocess.PIPE, universal_newlines=True).stdout
+
+    symbols = []
+    for line in out.splitlines():
+        parts = line.split()
+        if len(parts) != 3:
+            continue
+        addr, kind, name = parts
+        if kind not in ("t", "T", "w", "W"):
+            continue
+        symbols.append((int(addr, 16), name))
+
+    symbols.sort()
+    return symbols
+
+
+def build_table(symbols):
+    """Build the table, dropping symbols from the end if it doesn't fit."""
+    while True:
+        names = b""
+        entries = b""
+        for addr, name in symbols:
+            encoded = name.encode("utf-8")
+            entries += struct.pack("<QII", addr, len(names), len(encoded))
+            names += encoded
+
+        table = MAGIC + struct.pack("<I", len(symbols)) + entries + names
+        if len(table) <= SYMTAB_SIZE:
+            return table, len(symbols)
+
+        # Estimate how many symbols to drop to fit
+        excess = len(table) - SYMTAB_SIZE
+        average = len(table) // max(len(symbols), 1)
+        symbols = symbols[:-max(excess // average, 1)]
+
+
+def embed(kernel):
+    """Embed the symbol table into `kernel`, returning the symbol count."""
+    symbols = read_symbols(kernel)
+    table, count = build_table(symbols)
+    if count < len(symbols):
+        print("warning: symbol table full, dropped {} symbols".format(
+            len(symbols) - count), file=sys.stderr)
+
+    table += b"\0" * (SYMTAB_SIZE - len(table))
+
+    with tempfile.NamedTemporaryFile(delete=False) as f:
+        f.write(table)
+        blob = f.name
+
+    try:
+        subprocess.run(
+            ["objcopy", "--update-section", "{}={}".format(SECTION, blob),
+             kernel],
+            check=True)
+    finally:
+        os.unlink(blob)
+
+    return count
+
+
+def main():
+    if len(sys.argv) != 2:
+        sys.exit("usage: embed_symbols.py <kernel-elf>")
+    kernel = sys.argv[1]
+
+    count = embed(kernel)
+    print("embedded {} symbols into {}".format(count, kernel))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,36 @@
+#!/usr/bin/env python3
+"""Cargo runner which embeds the kernel symbol table before booting.
+
+Cargo calls this with the kernel ELF followed by any arguments. The symbol
+table is embedded with `embed_symbols.py`, then `bootimage runner` builds the
+boot image and runs it in QEMU as usual, passing on its exit code.
+
+If the symbol table can't be embedded (for example binutils isn't installed)
+a warning is printed and the kernel is run anyway, with backtraces showing
+bare addresses.
+"""
+
+import os
+import subprocess
+import sys
+
+sys.path.insert(0, os.path.dirname(os.path.abspath(__file__)))
+
+import embed_symbols
+
+
+def main():
+    if len(sys.argv) < 2:
+        sys.exit("usage: runner.py <kernel-elf> [args...]")
+    kernel = sys.argv[1]
+
+    try:
+        embed_symbols.embed(kernel)
+    except (OSError, subprocess.CalledProcessError) as e:
+        print("warning: cannot embed symbols: {}".format(e), file=sys.stderr)
+
+    sys.exit(subprocess.call(["bootimage", "runner"] + sys.argv[1:]))
+
+
+if __name__ == "__main__":
+    main()